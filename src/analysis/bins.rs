@@ -0,0 +1,332 @@
+use std::fs::File;
+use std::io::BufRead;
+use std::io::BufReader;
+use std::path::Path;
+use rand::prelude::*;
+use rand::rngs::StdRng;
+use super::expression::Expression;
+use super::resampling::Estimate;
+use super::resampling::bootstrap;
+use super::resampling::jackknife;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use std::fs;
+    use std::io::prelude::*;
+    use std::path::PathBuf;
+    use analysis::expression::parse_derived;
+
+    fn write_file(dir: &Path, name: &str, contents: &str) {
+        File::create(dir.join(name)).unwrap().write_all(contents.as_bytes()).unwrap();
+    }
+
+    fn estimator_dir(name: &str) -> PathBuf {
+        let dir = env::temp_dir().join(name);
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        write_file(&dir, "run_parameters.csv", "parameter,value\nlx,4\nly,2\nresume,\n");
+        write_file(&dir, "total_link_count_estimator.csv", "Average Total Link Counts\n10\n12\n11\n13\n");
+        write_file(&dir, "winding_number_variance_estimator.csv", "Horizontal,Vertical\n1,2\n2,1\n1,1\n2,2\n");
+        write_file(&dir, "density_estimator.csv",
+                   "x,y,N,E,S,W\n0,0,1,0,0,0\n2,0,0,1,0,0\n\
+                    0,0,1,1,0,0\n2,0,1,1,0,0\n\
+                    0,0,0,0,0,0\n2,0,0,0,0,0\n\
+                    0,0,1,0,1,0\n2,0,1,0,1,0\n");
+        dir
+    }
+
+    #[test]
+    fn test_read_estimator_bins() {
+        let dir = estimator_dir("z3stringnet_test_read_estimator_bins");
+        let observables = read_estimator_bins(&dir).unwrap();
+        assert_eq!(observables.names, vec!["density_N", "density_E", "density_S", "density_W",
+                                           "total_link_count",
+                                           "winding_variance_horizontal", "winding_variance_vertical"]);
+        assert_eq!(observables.bins("density_N").unwrap(), &[0.5, 1.0, 0.0, 1.0][..]);
+        assert_eq!(observables.bins("total_link_count").unwrap(), &[10.0, 12.0, 11.0, 13.0][..]);
+        assert_eq!(observables.constant("lx"), Some(4.0));
+        assert_eq!(observables.constant("resume"), None);
+
+        let mut later = observables.clone();
+        later.discard(1).unwrap();
+        assert_eq!(later.bins("total_link_count").unwrap(), &[12.0, 11.0, 13.0][..]);
+        assert!(later.discard(2).is_err());
+
+        write_file(&dir, "wilson_loop_estimator.csv", "wilson_1_1,wilson_1_2\n0.5,0.25\n0.5,0.2\n0.4,0.2\n0.6,0.3\n");
+        let observables = read_estimator_bins(&dir).unwrap();
+        assert_eq!(observables.bins("wilson_1_2").unwrap(), &[0.25, 0.2, 0.2, 0.3][..]);
+        write_file(&dir, "vertex_type_estimator.csv", "vertex_empty,vertex_corner\n0.5,0.5\n0.75,0.25\n1,0\n0.5,0.5\n");
+        let observables = read_estimator_bins(&dir).unwrap();
+        assert_eq!(observables.bins("vertex_corner").unwrap(), &[0.5, 0.25, 0.0, 0.5][..]);
+
+        write_file(&dir, "cluster_size_estimator.csv", "3.5\n4.5\n");
+        assert!(read_estimator_bins(&dir).is_err());
+    }
+
+    #[test]
+    fn test_analyze_observables() {
+        let dir = estimator_dir("z3stringnet_test_analyze_observables");
+        let observables = read_estimator_bins(&dir).unwrap();
+        let derived = vec![parse_derived("per_link = total_link_count / (2 * lx * ly)").unwrap()];
+        let results = analyze_observables(&observables, &derived, 100, 3).unwrap();
+        assert_eq!(results.len(), 8);
+        let link_count = &results[4];
+        assert_eq!(link_count.name, "total_link_count");
+        assert_eq!(link_count.number_bins, 4);
+        assert_eq!(link_count.jackknife.value, 11.5);
+        assert!(link_count.bootstrap.is_some());
+        let per_link = &results[7];
+        assert_eq!(per_link.name, "per_link");
+        assert!((per_link.jackknife.value - 11.5 / 16.0).abs() < 1e-12);
+        assert!((per_link.jackknife.error - link_count.jackknife.error / 16.0).abs() < 1e-12);
+
+        let unknown = vec![parse_derived("bad = total_link_count / volume").unwrap()];
+        assert!(analyze_observables(&observables, &unknown, 0, 3).is_err());
+    }
+}
+
+/// Per-bin averages of the observables written by the estimators of one run, plus the
+/// numeric run parameters (`lx`, `ly`, `weights`, ...) from `run_parameters.csv` as constants.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BinnedObservables {
+    pub names: Vec<String>,
+    /// One column of bin averages per name, all of the same length.
+    pub columns: Vec<Vec<f64>>,
+    pub constants: Vec<(String, f64)>,
+}
+
+impl BinnedObservables {
+    pub fn bins(&self, name: &str) -> Option<&[f64]> {
+        self.names.iter().position(|n| n == name).map(|index| &self.columns[index][..])
+    }
+
+    pub fn constant(&self, name: &str) -> Option<f64> {
+        self.constants.iter().find(|c| c.0 == name).map(|c| c.1)
+    }
+
+    pub fn number_bins(&self) -> usize {
+        self.columns.first().map_or(0, |column| column.len())
+    }
+
+    /// Drop the first `number` bins, e.g. when the run was not equilibrated.
+    pub fn discard(&mut self, number: usize) -> Result<(), String> {
+        if number + 2 > self.number_bins() {
+            return Err(format!("Discarding {} of {} bins leaves fewer than two", number, self.number_bins()));
+        }
+        for column in self.columns.iter_mut() {
+            column.drain(..number);
+        }
+        Ok(())
+    }
+
+    fn push(&mut self, name: &str, column: Vec<f64>, file_name: &str) -> Result<(), String> {
+        if !self.columns.is_empty() && column.len() != self.number_bins() {
+            return Err(format!("{} has {} bins but {} has {}",
+                               file_name, column.len(), self.names[0], self.number_bins()));
+        }
+        self.names.push(String::from(name));
+        self.columns.push(column);
+        Ok(())
+    }
+}
+
+/// Numeric rows of a CSV file, `None` if the file does not exist. A first line that is not
+/// numeric is a header; the estimators write one unless they append to an earlier run.
+fn read_numeric_rows(path: &Path) -> Result<Option<Vec<Vec<f64>>>, String> {
+    let file = match File::open(path) {
+        Ok(f) => f,
+        Err(_) => return Ok(None),
+    };
+    let mut rows: Vec<Vec<f64>> = Vec::new();
+    for (line_number, line) in BufReader::new(file).lines().enumerate() {
+        let line = match line {
+            Ok(l) => l,
+            Err(e) => return Err(format!("Problem reading {}: {}", path.display(), e)),
+        };
+        if line.trim().is_empty() {
+            continue;
+        }
+        let row: Result<Vec<f64>, _> = line.split(',').map(|v| v.trim().parse::<f64>()).collect();
+        match row {
+            Ok(row) => rows.push(row),
+            Err(_) if line_number == 0 => (),
+            Err(_) => return Err(format!("{} line {} is not numeric", path.display(), line_number + 1)),
+        }
+    }
+    Ok(Some(rows))
+}
+
+/// Column `index` of `rows`.
+fn column(rows: &[Vec<f64>], index: usize, path: &Path) -> Result<Vec<f64>, String> {
+    rows.iter().map(|row| match row.get(index) {
+        Some(value) => Ok(*value),
+        None => Err(format!("{} has a line with fewer than {} values", path.display(), index + 1)),
+    }).collect()
+}
+
+/// `density_estimator.csv` has a line per vertex (x, y, N, E, S, W) and a block of them per
+/// bin. Returns the density of each direction averaged over the vertices, per bin.
+fn density_bins(rows: &[Vec<f64>], path: &Path) -> Result<Vec<Vec<f64>>, String> {
+    let mut directions: Vec<Vec<f64>> = vec![Vec::new(); 4];
+    if rows.is_empty() {
+        return Ok(directions);
+    }
+    if rows.iter().any(|row| row.len() != 6) {
+        return Err(format!("{} should have six values per line", path.display()));
+    }
+    let first_vertex = (rows[0][0], rows[0][1]);
+    let vertices_per_bin = 1 + rows[1..].iter().position(|row| (row[0], row[1]) == first_vertex)
+        .unwrap_or(rows.len() - 1);
+    if rows.len() % vertices_per_bin != 0 {
+        return Err(format!("{} ends in the middle of a bin", path.display()));
+    }
+    for block in rows.chunks(vertices_per_bin) {
+        for (direction, values) in directions.iter_mut().enumerate() {
+            values.push(block.iter().map(|row| row[direction + 2]).sum::<f64>() / vertices_per_bin as f64);
+        }
+    }
+    Ok(directions)
+}
+
+/// Read the binned estimator files in `dir`. Every file that is there is read:
+///
+/// - `density_estimator.csv`: `density_N`, `density_E`, `density_S`, `density_W`, averaged over
+///   the vertices,
+/// - `total_link_count_estimator.csv`: `total_link_count`,
+/// - `winding_number_variance_estimator.csv`: `winding_variance_horizontal` and
+///   `winding_variance_vertical`,
+/// - `cluster_size_estimator.csv`: `cluster_size`,
+/// - `wilson_loop_estimator.csv`: `wilson_R_T` for every loop size,
+/// - `vertex_type_estimator.csv`: `vertex_<type>` for every vertex type,
+/// - `winding_sector_estimator.csv`: `sector_h_v` for every sector, `winding_squared_horizontal`,
+///   `winding_squared_vertical` and `winding_stiffness`,
+/// - `cluster_percolation_estimator.csv`: `largest_cluster` and the `wrapping_` probabilities.
+///
+/// The correlation and winding number count files are not per bin and are left out.
+pub fn read_estimator_bins(dir: &Path) -> Result<BinnedObservables, String> {
+    let mut observables = BinnedObservables {names: Vec::new(), columns: Vec::new(), constants: Vec::new()};
+
+    let path = dir.join("density_estimator.csv");
+    if let Some(rows) = read_numeric_rows(&path)? {
+        let directions = density_bins(&rows, &path)?;
+        for (name, values) in ["density_N", "density_E", "density_S", "density_W"].iter().zip(directions) {
+            observables.push(name, values, "density_estimator.csv")?;
+        }
+    }
+    let single_files: [(&str, &[&str]); 3] = [
+        ("total_link_count_estimator.csv", &["total_link_count"]),
+        ("winding_number_variance_estimator.csv", &["winding_variance_horizontal", "winding_variance_vertical"]),
+        ("cluster_size_estimator.csv", &["cluster_size"]),
+    ];
+    for &(file_name, names) in single_files.iter() {
+        let path = dir.join(file_name);
+        if let Some(rows) = read_numeric_rows(&path)? {
+            for (index, name) in names.iter().enumerate() {
+                observables.push(name, column(&rows, index, &path)?, file_name)?;
+            }
+        }
+    }
+    // These files name their columns in the header.
+    let named_files = ["wilson_loop_estimator.csv", "vertex_type_estimator.csv", "winding_sector_estimator.csv",
+                       "cluster_percolation_estimator.csv"];
+    for file_name in named_files.iter() {
+        let path = dir.join(file_name);
+        if let Some(rows) = read_numeric_rows(&path)? {
+            let header = match File::open(&path).ok().and_then(|f| BufReader::new(f).lines().next()) {
+                Some(Ok(header)) => header,
+                _ => return Err(format!("{} has no header", path.display())),
+            };
+            for (index, name) in header.split(',').enumerate() {
+                observables.push(name.trim(), column(&rows, index, &path)?, file_name)?;
+            }
+        }
+    }
+    if observables.names.is_empty() {
+        return Err(format!("No estimator files in {}", dir.display()));
+    }
+
+    let path = dir.join("run_parameters.csv");
+    if let Ok(file) = File::open(&path) {
+        for line in BufReader::new(file).lines().skip(1) {
+            let line = match line {
+                Ok(l) => l,
+                Err(e) => return Err(format!("Problem reading {}: {}", path.display(), e)),
+            };
+            let mut parts = line.splitn(2, ',');
+            if let (Some(name), Some(value)) = (parts.next(), parts.next()) {
+                if let Ok(value) = value.trim().parse::<f64>() {
+                    observables.constants.push((String::from(name.trim()), value));
+                }
+            }
+        }
+    }
+    Ok(observables)
+}
+
+/// Error bars of one observable.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AnalyzedObservable {
+    pub name: String,
+    pub number_bins: usize,
+    pub jackknife: Estimate,
+    pub bootstrap: Option<Estimate>,
+}
+
+/// Jackknife, and with `resamples` > 0 bootstrap, estimates of every observable in
+/// `observables` followed by every derived one. Derived observables are functions of the
+/// means of the raw ones and of the constants; unknown names are an error.
+pub fn analyze_observables(observables: &BinnedObservables, derived: &[(String, Expression)],
+                           resamples: usize, seed: u64) -> Result<Vec<AnalyzedObservable>, String> {
+    let mut rng = StdRng::seed_from_u64(seed);
+    let mut results: Vec<AnalyzedObservable> = Vec::new();
+
+    for (name, values) in observables.names.iter().zip(&observables.columns) {
+        let columns: [&[f64]; 1] = [values];
+        let function = |means: &[f64]| -> Result<f64, String> { Ok(means[0]) };
+        results.push(AnalyzedObservable {
+            name: name.clone(),
+            number_bins: values.len(),
+            jackknife: jackknife(&columns, &function)?,
+            bootstrap: match resamples {
+                0 => None,
+                _ => Some(bootstrap(&columns, &function, resamples, &mut rng)?),
+            },
+        });
+    }
+
+    for (name, expression) in derived {
+        let variables = expression.variables();
+        let mut columns: Vec<&[f64]> = Vec::new();
+        for variable in &variables {
+            match observables.bins(variable) {
+                Some(values) => columns.push(values),
+                None if observables.constant(variable).is_some() => (),
+                None => return Err(format!("{} uses {}, which is neither an observable ({}) nor a run parameter",
+                                           name, variable, observables.names.join(", "))),
+            }
+        }
+        if columns.is_empty() {
+            return Err(format!("{} does not use any observable", name));
+        }
+        let function = |means: &[f64]| {
+            expression.evaluate(&|variable: &str| {
+                match variables.iter().filter(|v| observables.bins(v).is_some()).position(|v| v == variable) {
+                    Some(index) => Some(means[index]),
+                    None => observables.constant(variable),
+                }
+            })
+        };
+        results.push(AnalyzedObservable {
+            name: name.clone(),
+            number_bins: columns[0].len(),
+            jackknife: jackknife(&columns, &function)?,
+            bootstrap: match resamples {
+                0 => None,
+                _ => Some(bootstrap(&columns, &function, resamples, &mut rng)?),
+            },
+        });
+    }
+    Ok(results)
+}
@@ -0,0 +1,295 @@
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lookup(name: &str) -> Option<f64> {
+        match name {
+            "a" => Some(2.0),
+            "b" => Some(3.0),
+            "lx" => Some(4.0),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn test_evaluate() {
+        let cases = [
+            ("1 + 2 * 3", 7.0),
+            ("(1 + 2) * 3", 9.0),
+            ("2 ^ 3 ^ 2", 512.0),
+            ("-2 ^ 2", -4.0),
+            ("a * b - 1 / 4", 5.75),
+            ("sqrt(lx * lx) + abs(-1)", 5.0),
+            ("1.5e1 - exp(ln(b))", 12.0),
+            ("1 - a / (3 * b ^ 2)", 1.0 - 2.0 / 27.0),
+        ];
+        for &(text, value) in cases.iter() {
+            let expression = Expression::parse(text).unwrap();
+            assert!((expression.evaluate(&lookup).unwrap() - value).abs() < 1e-12, "{}", text);
+        }
+        assert_eq!(Expression::parse("a * (b + lx) - a").unwrap().variables(), vec!["a", "b", "lx"]);
+    }
+
+    #[test]
+    fn test_errors() {
+        for text in &["", "1 +", "(1 + 2", "1 2", "sin(1)", "a $ b", "sqrt 2"] {
+            assert!(Expression::parse(text).is_err(), "{}", text);
+        }
+        assert!(Expression::parse("c + 1").unwrap().evaluate(&lookup).is_err());
+        assert_eq!(parse_derived("ratio = a / b").unwrap().0, "ratio");
+        assert!(parse_derived("a / b").is_err());
+        assert!(parse_derived("2x = a").is_err());
+    }
+}
+
+/// Functions that can be used in an expression.
+pub const FUNCTION_NAMES: [&'static str; 4] = ["sqrt", "ln", "exp", "abs"];
+
+/// A formula of observables like `1 - w4 / (3 * w2 ^ 2)`, for quantities derived from the bin
+/// averages of the estimators.
+///
+/// Expressions have numbers, names of observables, `+ - * / ^` (`^` binds tightest and to the
+/// right), parentheses and the functions in `FUNCTION_NAMES`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expression {
+    Number(f64),
+    Variable(String),
+    Negate(Box<Expression>),
+    Binary(char, Box<Expression>, Box<Expression>),
+    Function(String, Box<Expression>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Number(f64),
+    Name(String),
+    Operator(char),
+    Open,
+    Close,
+}
+
+fn tokenize(text: &str) -> Result<Vec<Token>, String> {
+    let chars: Vec<char> = text.chars().collect();
+    let mut tokens: Vec<Token> = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        if c.is_whitespace() {
+            i += 1;
+        } else if c.is_ascii_digit() || c == '.' {
+            let start = i;
+            while i < chars.len() && (chars[i].is_ascii_digit() || chars[i] == '.') {
+                i += 1;
+            }
+            // An exponent like 1e-3.
+            if i < chars.len() && (chars[i] == 'e' || chars[i] == 'E') {
+                let mut j = i + 1;
+                if j < chars.len() && (chars[j] == '+' || chars[j] == '-') {
+                    j += 1;
+                }
+                if j < chars.len() && chars[j].is_ascii_digit() {
+                    i = j;
+                    while i < chars.len() && chars[i].is_ascii_digit() {
+                        i += 1;
+                    }
+                }
+            }
+            let number_str: String = chars[start..i].iter().collect();
+            match number_str.parse::<f64>() {
+                Ok(number) => tokens.push(Token::Number(number)),
+                Err(_) => return Err(format!("{} is not a number", number_str)),
+            }
+        } else if c.is_alphabetic() || c == '_' {
+            let start = i;
+            while i < chars.len() && (chars[i].is_alphanumeric() || chars[i] == '_') {
+                i += 1;
+            }
+            tokens.push(Token::Name(chars[start..i].iter().collect()));
+        } else if "+-*/^".contains(c) {
+            tokens.push(Token::Operator(c));
+            i += 1;
+        } else if c == '(' {
+            tokens.push(Token::Open);
+            i += 1;
+        } else if c == ')' {
+            tokens.push(Token::Close);
+            i += 1;
+        } else {
+            return Err(format!("Unexpected {} in {}", c, text));
+        }
+    }
+    Ok(tokens)
+}
+
+/// Recursive descent over the tokens, one method per level of precedence.
+struct Parser {
+    tokens: Vec<Token>,
+    position: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.position)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.position).cloned();
+        self.position += 1;
+        token
+    }
+
+    fn sum(&mut self) -> Result<Expression, String> {
+        let mut expression = self.product()?;
+        while let Some(&Token::Operator(op)) = self.peek() {
+            if op != '+' && op != '-' {
+                break;
+            }
+            self.position += 1;
+            expression = Expression::Binary(op, Box::new(expression), Box::new(self.product()?));
+        }
+        Ok(expression)
+    }
+
+    fn product(&mut self) -> Result<Expression, String> {
+        let mut expression = self.unary()?;
+        while let Some(&Token::Operator(op)) = self.peek() {
+            if op != '*' && op != '/' {
+                break;
+            }
+            self.position += 1;
+            expression = Expression::Binary(op, Box::new(expression), Box::new(self.unary()?));
+        }
+        Ok(expression)
+    }
+
+    fn unary(&mut self) -> Result<Expression, String> {
+        if let Some(&Token::Operator('-')) = self.peek() {
+            self.position += 1;
+            return Ok(Expression::Negate(Box::new(self.unary()?)));
+        }
+        self.power()
+    }
+
+    fn power(&mut self) -> Result<Expression, String> {
+        let base = self.atom()?;
+        if let Some(&Token::Operator('^')) = self.peek() {
+            self.position += 1;
+            return Ok(Expression::Binary('^', Box::new(base), Box::new(self.unary()?)));
+        }
+        Ok(base)
+    }
+
+    fn parenthesized(&mut self) -> Result<Expression, String> {
+        match self.next() {
+            Some(Token::Open) => (),
+            _ => return Err(String::from("Expected (")),
+        }
+        let expression = self.sum()?;
+        match self.next() {
+            Some(Token::Close) => Ok(expression),
+            _ => Err(String::from("Expected )")),
+        }
+    }
+
+    fn atom(&mut self) -> Result<Expression, String> {
+        match self.peek().cloned() {
+            Some(Token::Number(number)) => {
+                self.position += 1;
+                Ok(Expression::Number(number))
+            },
+            Some(Token::Name(name)) => {
+                self.position += 1;
+                if FUNCTION_NAMES.contains(&name.as_str()) {
+                    Ok(Expression::Function(name, Box::new(self.parenthesized()?)))
+                } else if self.peek() == Some(&Token::Open) {
+                    Err(format!("Unknown function {}, known are {}", name, FUNCTION_NAMES.join(", ")))
+                } else {
+                    Ok(Expression::Variable(name))
+                }
+            },
+            Some(Token::Open) => self.parenthesized(),
+            Some(token) => Err(format!("Unexpected {:?}", token)),
+            None => Err(String::from("Unexpected end of the expression")),
+        }
+    }
+}
+
+impl Expression {
+    pub fn parse(text: &str) -> Result<Expression, String> {
+        let mut parser = Parser {tokens: tokenize(text)?, position: 0};
+        let expression = parser.sum()?;
+        if parser.position < parser.tokens.len() {
+            return Err(format!("Unexpected {:?} in {}", parser.tokens[parser.position], text));
+        }
+        Ok(expression)
+    }
+
+    /// Value of the expression with the values of the variables from `lookup`.
+    pub fn evaluate(&self, lookup: &Fn(&str) -> Option<f64>) -> Result<f64, String> {
+        Ok(match *self {
+            Expression::Number(number) => number,
+            Expression::Variable(ref name) => match lookup(name) {
+                Some(value) => value,
+                None => return Err(format!("Unknown observable {}", name)),
+            },
+            Expression::Negate(ref inner) => -inner.evaluate(lookup)?,
+            Expression::Binary(op, ref left, ref right) => {
+                let left = left.evaluate(lookup)?;
+                let right = right.evaluate(lookup)?;
+                match op {
+                    '+' => left + right,
+                    '-' => left - right,
+                    '*' => left * right,
+                    '/' => left / right,
+                    _ => left.powf(right),
+                }
+            },
+            Expression::Function(ref name, ref inner) => {
+                let argument = inner.evaluate(lookup)?;
+                match name.as_str() {
+                    "sqrt" => argument.sqrt(),
+                    "ln" => argument.ln(),
+                    "exp" => argument.exp(),
+                    _ => argument.abs(),
+                }
+            },
+        })
+    }
+
+    /// Names of the variables in the expression, sorted and without duplicates.
+    pub fn variables(&self) -> Vec<String> {
+        let mut names: Vec<String> = Vec::new();
+        self.collect_variables(&mut names);
+        names.sort();
+        names.dedup();
+        names
+    }
+
+    fn collect_variables(&self, names: &mut Vec<String>) {
+        match *self {
+            Expression::Number(_) => (),
+            Expression::Variable(ref name) => names.push(name.clone()),
+            Expression::Negate(ref inner) | Expression::Function(_, ref inner) => inner.collect_variables(names),
+            Expression::Binary(_, ref left, ref right) => {
+                left.collect_variables(names);
+                right.collect_variables(names);
+            },
+        }
+    }
+}
+
+/// Parse a derived observable given as `name = expression`.
+pub fn parse_derived(spec: &str) -> Result<(String, Expression), String> {
+    let mut parts = spec.splitn(2, '=');
+    let name = parts.next().unwrap_or("").trim();
+    let expression = match parts.next() {
+        Some(expression) => expression,
+        None => return Err(format!("{} is not of the form name = expression", spec)),
+    };
+    let valid_name = name.chars().next().map_or(false, |c| c.is_alphabetic() || c == '_')
+        && name.chars().all(|c| c.is_alphanumeric() || c == '_');
+    if !valid_name {
+        return Err(format!("{} is not a name for a derived observable", name));
+    }
+    Ok((String::from(name), Expression::parse(expression)?))
+}
@@ -0,0 +1,262 @@
+pub mod expression;
+pub mod resampling;
+pub mod bins;
+
+use std::fs::File;
+use std::io::BufRead;
+use std::io::BufReader;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use std::io::prelude::*;
+    use rand::prelude::*;
+    use rand::rngs::StdRng;
+
+    /// x_t = a x_{t-1} + noise, which has tau_int = (1 + a) / (2 (1 - a)).
+    fn ar1_series(a: f64, length: usize, seed: u64) -> Vec<f64> {
+        let mut rng = StdRng::seed_from_u64(seed);
+        let mut series = Vec::with_capacity(length);
+        let mut x = 0.0;
+        for _ in 0..length {
+            x = a * x + rng.gen::<f64>() - 0.5;
+            series.push(x);
+        }
+        series
+    }
+
+    #[test]
+    fn test_mean_and_variance() {
+        let series = [1.0, 2.0, 3.0, 4.0];
+        assert_eq!(mean(&series), 2.5);
+        assert!((variance(&series) - 5.0 / 3.0).abs() < 1e-12);
+        assert_eq!(bin_series(&[1.0, 2.0, 3.0, 4.0, 5.0], 2), vec![1.5, 3.5]);
+    }
+
+    #[test]
+    fn test_uncorrelated_series() {
+        let series = ar1_series(0.0, 20000, 3);
+        let tau = integrated_autocorrelation_time(&series, 5.0).unwrap();
+        assert!(tau.converged);
+        assert!((tau.tau_int - 0.5).abs() < 0.05);
+        let levels = binning_analysis(&series, 16);
+        assert_eq!(levels[0].bin_size, 1);
+        assert_eq!(levels[1].bin_size, 2);
+        assert!((levels[0].error - (variance(&series) / 20000.0).sqrt()).abs() < 1e-12);
+        // Binning does not change the error of uncorrelated data (up to noise).
+        let last = levels.last().unwrap();
+        assert!(last.number_bins >= 16);
+        assert!((last.error / levels[0].error - 1.0).abs() < 0.3);
+    }
+
+    #[test]
+    fn test_correlated_series() {
+        let a = 0.8;
+        let exact_tau = (1.0 + a) / (2.0 * (1.0 - a));
+        let series = ar1_series(a, 200000, 5);
+        let tau = integrated_autocorrelation_time(&series, 6.0).unwrap();
+        assert!(tau.converged);
+        assert!((tau.tau_int - exact_tau).abs() < 3.0 * tau.error + 0.1 * exact_tau);
+        assert!(tau.window as f64 >= 6.0 * tau.tau_int);
+
+        let levels = binning_analysis(&series, 64);
+        let binned_tau = binning_tau(&levels).unwrap();
+        assert!((binned_tau - exact_tau).abs() < 0.15 * exact_tau);
+        // The naive error is too small by about sqrt(2 tau_int).
+        let corrected = levels[0].error * (2.0 * tau.tau_int).sqrt();
+        assert!((corrected / levels.last().unwrap().error - 1.0).abs() < 0.2);
+    }
+
+    #[test]
+    fn test_constant_series() {
+        assert!(integrated_autocorrelation_time(&[2.0; 100], 5.0).is_err());
+        assert!(integrated_autocorrelation_time(&[1.0], 5.0).is_err());
+    }
+
+    #[test]
+    fn test_read_csv_column() {
+        let path = env::temp_dir().join("z3stringnet_test_read_csv_column.csv");
+        {
+            let mut file = File::create(&path).unwrap();
+            file.write_all(b"link_count,winding_horizontal\n4,0\n6,-1\n\n8,1\n").unwrap();
+        }
+        let f_str = path.to_str().unwrap();
+        assert_eq!(read_csv_column(f_str, "winding_horizontal").unwrap(), vec![0.0, -1.0, 1.0]);
+        assert_eq!(read_csv_column(f_str, "link_count").unwrap(), vec![4.0, 6.0, 8.0]);
+        assert!(read_csv_column(f_str, "winding_vertical").is_err());
+    }
+}
+
+/// Average of `series`.
+pub fn mean(series: &[f64]) -> f64 {
+    series.iter().sum::<f64>() / series.len() as f64
+}
+
+/// Sample variance (with N - 1) of `series`.
+pub fn variance(series: &[f64]) -> f64 {
+    let average = mean(series);
+    series.iter().map(|x| (x - average).powi(2)).sum::<f64>() / (series.len() as f64 - 1.0)
+}
+
+/// Averages of consecutive blocks of `bin_size` values. A partial block at the end is dropped.
+pub fn bin_series(series: &[f64], bin_size: usize) -> Vec<f64> {
+    series.chunks(bin_size)
+        .filter(|chunk| chunk.len() == bin_size)
+        .map(mean)
+        .collect()
+}
+
+/// One level of a binning analysis.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BinningLevel {
+    /// Number of measurements per bin.
+    pub bin_size: usize,
+    pub number_bins: usize,
+    pub mean: f64,
+    /// Error of the mean assuming the bins are independent.
+    pub error: f64,
+}
+
+/// Error of the mean with bins of 1, 2, 4, ... measurements as long as there are at least
+/// `min_bins` bins.
+///
+/// For correlated measurements the error grows with the bin size until the bins are longer
+/// than the autocorrelation time and then stays flat. If it is still growing at the last level
+/// the bins (and probably the run) are too short. The plateau error is about `sqrt(2 tau_int)`
+/// times the naive error of the first level, see `binning_tau`.
+pub fn binning_analysis(series: &[f64], min_bins: usize) -> Vec<BinningLevel> {
+    let min_bins = min_bins.max(2);
+    let mut levels: Vec<BinningLevel> = Vec::new();
+    let mut bin_size: usize = 1;
+    while series.len() / bin_size >= min_bins {
+        let bins = bin_series(series, bin_size);
+        levels.push(BinningLevel {
+            bin_size,
+            number_bins: bins.len(),
+            mean: mean(&bins),
+            error: (variance(&bins) / bins.len() as f64).sqrt(),
+        });
+        bin_size *= 2;
+    }
+    levels
+}
+
+/// Integrated autocorrelation time from the last level of a binning analysis,
+/// `(error_last / error_first)^2 / 2`, in the convention of `integrated_autocorrelation_time`.
+/// `None` if there are fewer than two levels or the first error is 0.
+pub fn binning_tau(levels: &[BinningLevel]) -> Option<f64> {
+    if levels.len() < 2 || levels[0].error == 0.0 {
+        return None;
+    }
+    let ratio = levels[levels.len() - 1].error / levels[0].error;
+    Some(ratio * ratio / 2.0)
+}
+
+/// Deviations of `series` from its mean.
+fn deviations(series: &[f64]) -> Vec<f64> {
+    let average = mean(series);
+    series.iter().map(|x| x - average).collect()
+}
+
+/// Autocovariance of the deviations at `lag`.
+fn autocovariance(deviations: &[f64], lag: usize) -> f64 {
+    let length = deviations.len();
+    deviations[..length - lag].iter()
+        .zip(&deviations[lag..])
+        .map(|(a, b)| a * b)
+        .sum::<f64>() / (length - lag) as f64
+}
+
+/// Normalized autocorrelation function rho(t) of `series` for t = 0 to `max_lag`.
+pub fn autocorrelation_function(series: &[f64], max_lag: usize) -> Vec<f64> {
+    let deviations = deviations(series);
+    let c0 = autocovariance(&deviations, 0);
+    (0..(max_lag.min(series.len() - 1) + 1))
+        .map(|lag| autocovariance(&deviations, lag) / c0)
+        .collect()
+}
+
+/// Result of `integrated_autocorrelation_time`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct IntegratedAutocorrelationTime {
+    /// tau_int = 1/2 + sum of rho(t) for t = 1 to `window`. Uncorrelated measurements have 1/2
+    /// and the error of the mean is `sqrt(2 tau_int)` times the naive error.
+    pub tau_int: f64,
+    /// Statistical error of `tau_int` (Madras and Sokal).
+    pub error: f64,
+    /// Number of lags summed over.
+    pub window: usize,
+    /// False if no window satisfied the windowing rule, i.e. the series is too short for its
+    /// autocorrelation time and `tau_int` is a lower bound at best.
+    pub converged: bool,
+}
+
+/// Integrated autocorrelation time of `series` in units of measurements, with Sokal's
+/// automatic windowing: the sum over rho(t) stops at the first window W with
+/// W >= `window_factor` * tau_int(W). A `window_factor` of 4 to 10 is usual, 5 is a good
+/// start; it trades the bias of a short window against the noise of a long one.
+///
+/// Measurements are roughly independent when they are `2 tau_int` apart, so `nmeasure` should
+/// be many times `2 tau_int` for the bins to be independent; otherwise raise `nupdate`.
+pub fn integrated_autocorrelation_time(series: &[f64], window_factor: f64)
+    -> Result<IntegratedAutocorrelationTime, String> {
+    if series.len() < 2 {
+        return Err(String::from("Need at least two measurements"));
+    }
+    if variance(series) == 0.0 {
+        return Err(String::from("The series is constant, there is no autocorrelation time"));
+    }
+    let length = series.len();
+    let deviations = deviations(series);
+    let c0 = autocovariance(&deviations, 0);
+
+    // rho(t) is only computed up to the window, the full function would cost length^2.
+    let mut tau_int: f64 = 0.5;
+    let mut window: usize = 0;
+    let mut converged = false;
+    for lag in 1..(length / 2 + 1) {
+        tau_int += autocovariance(&deviations, lag) / c0;
+        window = lag;
+        if lag as f64 >= window_factor * tau_int {
+            converged = true;
+            break;
+        }
+    }
+    let error = tau_int * (2.0 * (2.0 * window as f64 + 1.0) / length as f64).sqrt();
+    Ok(IntegratedAutocorrelationTime {tau_int, error, window, converged})
+}
+
+/// Read the column named `column` of a CSV file with a header line, like the files written by
+/// the estimators. Empty lines are skipped.
+pub fn read_csv_column(f_str: &str, column: &str) -> Result<Vec<f64>, String> {
+    let file = match File::open(f_str) {
+        Ok(f) => f,
+        Err(e) => return Err(format!("Problem opening {}: {}", f_str, e)),
+    };
+    let mut lines = BufReader::new(file).lines();
+    let header = match lines.next() {
+        Some(Ok(header)) => header,
+        _ => return Err(format!("{} has no header", f_str)),
+    };
+    let column_index = match header.split(',').position(|name| name.trim() == column) {
+        Some(index) => index,
+        None => return Err(format!("{} has no column {} (it has {})", f_str, column, header)),
+    };
+
+    let mut values: Vec<f64> = Vec::new();
+    for (line_number, line) in lines.enumerate() {
+        let line = match line {
+            Ok(l) => l,
+            Err(e) => return Err(format!("Problem reading {}: {}", f_str, e)),
+        };
+        if line.trim().is_empty() {
+            continue;
+        }
+        match line.split(',').nth(column_index).map(|v| v.trim().parse::<f64>()) {
+            Some(Ok(value)) => values.push(value),
+            _ => return Err(format!("{} line {}: no number in column {}", f_str, line_number + 2, column)),
+        }
+    }
+    Ok(values)
+}
@@ -0,0 +1,122 @@
+use rand::prelude::*;
+use rand::rngs::StdRng;
+use super::mean;
+use super::variance;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn square_of_first(means: &[f64]) -> Result<f64, String> {
+        Ok(means[0] * means[0])
+    }
+
+    #[test]
+    fn test_jackknife_of_a_mean_is_the_standard_error() {
+        let bins = vec![1.0, 4.0, 2.0, 8.0, 5.0, 3.0];
+        let estimate = jackknife(&[&bins], &|means| Ok(means[0])).unwrap();
+        assert!((estimate.value - mean(&bins)).abs() < 1e-12);
+        assert!((estimate.error - (variance(&bins) / 6.0).sqrt()).abs() < 1e-12);
+    }
+
+    #[test]
+    fn test_nonlinear_function() {
+        // To first order the error of m^2 is 2 m times the error of m.
+        let bins: Vec<f64> = (0..1000).map(|i| 10.0 + ((i * 7919) % 101) as f64 / 100.0).collect();
+        let standard_error = (variance(&bins) / bins.len() as f64).sqrt();
+        let expected = 2.0 * mean(&bins) * standard_error;
+
+        let jackknife_estimate = jackknife(&[&bins], &square_of_first).unwrap();
+        assert!((jackknife_estimate.value - mean(&bins).powi(2)).abs() < 1e-9);
+        assert!((jackknife_estimate.error / expected - 1.0).abs() < 0.01);
+
+        let mut rng = StdRng::seed_from_u64(11);
+        let bootstrap_estimate = bootstrap(&[&bins], &square_of_first, 2000, &mut rng).unwrap();
+        assert_eq!(bootstrap_estimate.value, jackknife_estimate.value);
+        assert!((bootstrap_estimate.error / expected - 1.0).abs() < 0.1);
+    }
+
+    #[test]
+    fn test_bins_have_to_match() {
+        let short = vec![1.0, 2.0];
+        let long = vec![1.0, 2.0, 3.0];
+        assert!(jackknife(&[&short, &long], &|means| Ok(means[0] / means[1])).is_err());
+        assert!(jackknife(&[&[1.0][..]], &|means| Ok(means[0])).is_err());
+        let mut rng = StdRng::seed_from_u64(1);
+        assert!(bootstrap(&[&short], &|means| Ok(means[0]), 0, &mut rng).is_err());
+    }
+}
+
+/// A value with its statistical error.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Estimate {
+    pub value: f64,
+    pub error: f64,
+}
+
+/// Number of bins shared by all `columns`, which has to be at least two.
+fn number_bins(columns: &[&[f64]]) -> Result<usize, String> {
+    let number = columns.first().map_or(0, |column| column.len());
+    if columns.iter().any(|column| column.len() != number) {
+        return Err(String::from("All observables need the same number of bins"));
+    }
+    if number < 2 {
+        return Err(format!("Need at least two bins for an error, got {}", number));
+    }
+    Ok(number)
+}
+
+/// Means of `columns` over the bins in `selection` (indices, repeats allowed).
+fn selected_means(columns: &[&[f64]], selection: &[usize]) -> Vec<f64> {
+    columns.iter()
+        .map(|column| selection.iter().map(|&bin| column[bin]).sum::<f64>() / selection.len() as f64)
+        .collect()
+}
+
+/// Jackknife estimate of `function` of the means of `columns`, one column of bin averages per
+/// observable (in the order `function` expects them).
+///
+/// `function` is evaluated on the means with one bin left out at a time, and the spread of
+/// these N values, times (N - 1) / N, gives the error. For a plain mean this is the usual
+/// standard error; for nonlinear functions like ratios or cumulants it also accounts for the
+/// correlations between the observables. The value is `function` of the full means.
+pub fn jackknife(columns: &[&[f64]], function: &Fn(&[f64]) -> Result<f64, String>)
+    -> Result<Estimate, String> {
+    let number = number_bins(columns)?;
+    let value = function(&selected_means(columns, &(0..number).collect::<Vec<usize>>()))?;
+
+    let totals: Vec<f64> = columns.iter().map(|column| column.iter().sum()).collect();
+    let mut leave_one_out: Vec<f64> = Vec::with_capacity(number);
+    for bin in 0..number {
+        let means: Vec<f64> = totals.iter().zip(columns)
+            .map(|(total, column)| (total - column[bin]) / (number - 1) as f64)
+            .collect();
+        leave_one_out.push(function(&means)?);
+    }
+    let average = mean(&leave_one_out);
+    let spread: f64 = leave_one_out.iter().map(|x| (x - average).powi(2)).sum();
+    Ok(Estimate {value, error: (spread * (number - 1) as f64 / number as f64).sqrt()})
+}
+
+/// Bootstrap estimate of `function` of the means of `columns` (see `jackknife`).
+///
+/// The bins are drawn with replacement `resamples` times, the same bins for every observable,
+/// and the standard deviation of `function` over the resamples is the error.
+pub fn bootstrap(columns: &[&[f64]], function: &Fn(&[f64]) -> Result<f64, String>,
+                 resamples: usize, rng: &mut StdRng) -> Result<Estimate, String> {
+    let number = number_bins(columns)?;
+    if resamples < 2 {
+        return Err(format!("Need at least two bootstrap resamples, got {}", resamples));
+    }
+    let value = function(&selected_means(columns, &(0..number).collect::<Vec<usize>>()))?;
+
+    let mut values: Vec<f64> = Vec::with_capacity(resamples);
+    let mut selection: Vec<usize> = vec![0; number];
+    for _ in 0..resamples {
+        for bin in selection.iter_mut() {
+            *bin = rng.gen_range(0, number);
+        }
+        values.push(function(&selected_means(columns, &selection))?);
+    }
+    Ok(Estimate {value, error: variance(&values).sqrt()})
+}
@@ -0,0 +1,677 @@
+use std::fs::File;
+use std::io::prelude::*;
+use std::path::Path;
+use std::path::PathBuf;
+use yaml_rust::Yaml;
+use yaml_rust::YamlLoader;
+use super::datamodel::Point;
+use super::datamodel::lattice::check_lattice_size;
+use super::lattice_updates::UpdateType;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+
+    #[test]
+    fn test_defaults_from_empty_config() {
+        let config = RunConfig::from_yaml_str("").unwrap();
+        assert_eq!(config, RunConfig::default());
+        assert_eq!(config.equilibration_updates(), 2 * 4 * 4);
+    }
+
+    #[test]
+    fn test_read_config() {
+        let config = RunConfig::from_yaml_str("
+lattice:
+  lx: 8
+  ly: 6
+  modulus: 4
+  initial_state: file
+  initial_configuration: old/lattice_configurations.csv
+  initial_configuration_line: 3
+updates:
+  type: worm
+  weights: 0.5
+  nupdate: 7
+  seed: 42
+equilibration:
+  enabled: false
+binning:
+  nbins: 3
+  nmeasure: 20
+estimators: [density, winding_number_count]
+output:
+  directory: runs/a
+  write_measure_confs: true
+").unwrap();
+        assert_eq!(config.size, Point {x: 8, y: 6});
+        assert_eq!(config.modulus, 4);
+        assert_eq!(config.initial_state, InitialState::File {
+            path: String::from("old/lattice_configurations.csv"),
+            style: 2,
+            line: Some(3),
+        });
+        assert_eq!(config.update_type, UpdateType::Worm);
+        assert_eq!(config.weights, 0.5);
+        assert_eq!(config.nupdate, 7);
+        assert_eq!(config.seed, Some(42));
+        assert!(!config.equilibrate);
+        assert_eq!(config.nbins, 3);
+        assert_eq!(config.nmeasure, 20);
+        assert_eq!(config.estimators, vec![String::from("density"), String::from("winding_number_count")]);
+        assert!(config.uses_estimator("density"));
+        assert!(!config.uses_estimator("cluster_size"));
+        assert_eq!(config.output_dir, "runs/a");
+        assert!(config.write_measure_confs);
+        // Not in the file so still the default, which is kept in the output directory.
+        assert_eq!(config.checkpoint_file, "checkpoint.csv");
+        assert_eq!(config.checkpoint_path(), Path::new("runs/a").join("checkpoint.csv"));
+    }
+
+    #[test]
+    fn test_config_round_trip() {
+        let mut config = RunConfig::default();
+        config.size = Point {x: 6, y: 4};
+        config.initial_state = InitialState::Striped;
+        config.monomers = true;
+        config.update_type = UpdateType::Walk;
+        config.weights = 1.0;
+        config.equilibration_length = Some(100);
+        config.seed = Some(u64::max_value());
+        config.estimators = vec![String::from("cluster_size")];
+        config.output_dir = String::from("a \"quoted\": dir");
+        config.resume = Some(String::from("checkpoint.csv"));
+        assert_eq!(RunConfig::from_yaml_str(&config.to_yaml_string()).unwrap(), config);
+
+        let path = env::temp_dir().join("z3stringnet_test_run_config.yaml");
+        write_run_config(path.to_str().unwrap(), &config);
+        assert_eq!(read_run_config(path.to_str().unwrap()).unwrap(), config);
+    }
+
+    #[test]
+    fn test_monomers_leave_out_closed_net_estimators() {
+        let config = RunConfig::from_yaml_str("lattice:\n  monomers: true\n").unwrap();
+        assert!(config.monomers);
+        assert!(config.uses_estimator("density"));
+        assert!(config.uses_estimator("cluster_size"));
+        assert!(!config.uses_estimator("winding_number_count"));
+        assert!(!config.uses_estimator("winding_number_variance"));
+    }
+
+    #[test]
+    fn test_config_errors() {
+        assert!(RunConfig::from_yaml_str("lattice:\n  lz: 4\n").is_err());
+        assert!(RunConfig::from_yaml_str("binnig:\n  nbins: 4\n").is_err());
+        assert!(RunConfig::from_yaml_str("lattice:\n  lx: 5\n").is_err());
+        assert!(RunConfig::from_yaml_str("lattice:\n  lx: many\n").is_err());
+        assert!(RunConfig::from_yaml_str("updates:\n  type: teleport\n").is_err());
+        assert!(RunConfig::from_yaml_str("estimators: [density, magic]\n").is_err());
+        assert!(RunConfig::from_yaml_str("lattice:\n  initial_state: file\n").is_err());
+        // The Z3 starting states do not exist for other moduli.
+        assert!(RunConfig::from_yaml_str("lattice:\n  modulus: 2\n  initial_state: striped\n").is_err());
+        // Winding numbers only exist for closed string nets.
+        assert!(RunConfig::from_yaml_str("lattice:\n  monomers: true\nestimators: [winding_number_count]\n").is_err());
+        // Configuration files do not keep the monomers, so they could not be read back.
+        assert!(RunConfig::from_yaml_str("lattice:\n  monomers: true\noutput:\n  write_update_confs: true\n").is_err());
+        assert!(RunConfig::from_yaml_str("estimators: [monomer_correlation]\n").is_err());
+    }
+}
+
+/// Names of the estimators that can be selected in a run configuration.
+pub const ESTIMATOR_NAMES: [&'static str; 14] = [
+    "density",
+    "correlation_origin",
+    "correlation_function",
+    "total_link_count",
+    "winding_number_count",
+    "winding_number_variance",
+    "cluster_size",
+    "time_series",
+    "structure_factor",
+    "wilson_loop",
+    "monomer_correlation",
+    "vertex_type",
+    "winding_histogram",
+    "cluster_geometry",
+];
+
+/// Estimators that need a closed string net, so they can not be used with monomers.
+const CLOSED_NET_ESTIMATOR_NAMES: [&'static str; 4] = [
+    "winding_number_count",
+    "winding_number_variance",
+    "winding_histogram",
+    "time_series",
+];
+
+/// Estimators a run uses unless the configuration says otherwise. The others in
+/// `ESTIMATOR_NAMES` are slower or write a lot more, so they have to be asked for.
+pub const DEFAULT_ESTIMATOR_NAMES: [&'static str; 6] = [
+    "density",
+    "correlation_origin",
+    "total_link_count",
+    "winding_number_count",
+    "winding_number_variance",
+    "cluster_size",
+];
+
+/// The lattice a run starts from (unless it resumes from a checkpoint).
+#[derive(Debug, Clone, PartialEq)]
+pub enum InitialState {
+    Blank,
+    /// `build_z3_striped_lat`, Z3 only.
+    Striped,
+    /// `build_z3_striped_vertical_lat`, Z3 only.
+    StripedVertical,
+    /// `build_z3_fully_packed_lat`, Z3 only.
+    FullyPacked,
+    /// A configuration saved by an earlier run, see `oio::read_lattice_style_1` and
+    /// `oio::read_lattice_style_2`. `line` picks the configuration of a style 2 file, the last
+    /// one if it is `None`.
+    File { path: String, style: u8, line: Option<usize> },
+}
+impl InitialState {
+    /// The built in starting states by the name used in run configurations and on the command
+    /// line. `file` is not one of them, it needs a path.
+    pub fn from_name(name: &str) -> Result<InitialState, String> {
+        match name {
+            "blank" => Ok(InitialState::Blank),
+            "striped" => Ok(InitialState::Striped),
+            "striped_vertical" => Ok(InitialState::StripedVertical),
+            "fully_packed" => Ok(InitialState::FullyPacked),
+            _ => Err(format!("Unknown initial state {} (use blank, striped, striped_vertical, \
+                              fully_packed or file)", name)),
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match *self {
+            InitialState::Blank => "blank",
+            InitialState::Striped => "striped",
+            InitialState::StripedVertical => "striped_vertical",
+            InitialState::FullyPacked => "fully_packed",
+            InitialState::File { .. } => "file",
+        }
+    }
+}
+
+pub fn update_type_from_name(name: &str) -> Result<UpdateType, String> {
+    match name {
+        "local" => Ok(UpdateType::Local),
+        "walk" => Ok(UpdateType::Walk),
+        "worm" => Ok(UpdateType::Worm),
+        _ => Err(format!("Unknown update type {} (use local, walk or worm)", name)),
+    }
+}
+
+pub fn update_type_name(update_type: &UpdateType) -> &'static str {
+    match *update_type {
+        UpdateType::Local => "local",
+        UpdateType::Walk => "walk",
+        UpdateType::Worm => "worm",
+    }
+}
+
+/// Everything needed to set up and run a simulation.
+///
+/// A run configuration is read from a YAML file with `read_run_config`. Every entry is optional
+/// and falls back to the value in `RunConfig::default`. The file has the sections
+/// ```yaml
+/// lattice:
+///   lx: 4
+///   ly: 4
+///   modulus: 3
+///   initial_state: blank   # striped, striped_vertical, fully_packed or file
+///   initial_configuration: lattice_configurations.csv   # only for file
+///   initial_configuration_style: 2
+///   initial_configuration_line: 0   # leave out for the last configuration
+///   monomers: false   # true puts a pair of test charges on the lattice
+/// updates:
+///   type: local   # walk or worm
+///   weights: 1.0
+///   nupdate: 5
+///   seed: 1234   # leave out for a random seed
+///   paranoid: 0
+/// equilibration:
+///   enabled: true
+///   updates: 32   # leave out for 2 * lx * ly
+/// binning:
+///   nbins: 10
+///   nmeasure: 500
+/// estimators: [density, correlation_origin, total_link_count, winding_number_count,
+///              winding_number_variance, cluster_size]
+///              # not by default: time_series, correlation_function, structure_factor,
+///              # wilson_loop, monomer_correlation, vertex_type, winding_histogram,
+///              # cluster_geometry
+/// output:
+///   directory: .
+///   write_update_confs: false
+///   write_measure_confs: false
+///   write_bin_confs: false
+///   configuration_style: 2
+///   checkpoint_every: 0
+///   checkpoint_file: checkpoint.csv   # in directory unless absolute
+/// resume: checkpoint.csv   # leave out for a new run
+/// gui: false
+/// ```
+/// `RunConfig::to_yaml_string` writes a configuration in the same format, which is how every
+/// run records the configuration it actually used.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RunConfig {
+    pub size: Point,
+    pub modulus: u8,
+    pub initial_state: InitialState,
+    /// Start with a pair of monomers (test charges) next to each other at the origin. Without an
+    /// `estimators` entry the run then uses the default estimators that work with monomers.
+    pub monomers: bool,
+    pub update_type: UpdateType,
+    pub weights: f64,
+    /// Updates between measurements.
+    pub nupdate: u64,
+    /// `None` means a seed is generated when the run starts.
+    pub seed: Option<u64>,
+    /// Validate the lattice every `paranoid` updates, 0 never does.
+    pub paranoid: u64,
+    /// Whether to update before the first measurement. A resumed run never equilibrates.
+    pub equilibrate: bool,
+    /// Number of equilibration updates, `None` for `2 * lx * ly`.
+    pub equilibration_length: Option<u64>,
+    pub nbins: u64,
+    /// Measurements per bin.
+    pub nmeasure: u64,
+    /// Selected estimators, from `ESTIMATOR_NAMES`.
+    pub estimators: Vec<String>,
+    /// Where estimator files, configurations and the run parameters are written.
+    pub output_dir: String,
+    pub write_update_confs: bool,
+    pub write_measure_confs: bool,
+    pub write_bin_confs: bool,
+    pub write_configuration_style: u8,
+    /// Write a checkpoint every `checkpoint_every` bins, 0 never does.
+    pub checkpoint_every: u64,
+    /// Where checkpoints are written, relative to `output_dir` unless it is absolute (see
+    /// `checkpoint_path`).
+    pub checkpoint_file: String,
+    /// Checkpoint to continue from.
+    pub resume: Option<String>,
+    pub gui: bool,
+}
+
+impl Default for RunConfig {
+    fn default() -> RunConfig {
+        RunConfig {
+            size: Point {x: 4, y: 4},
+            modulus: 3,
+            initial_state: InitialState::Blank,
+            monomers: false,
+            update_type: UpdateType::Local,
+            weights: 1.0,
+            nupdate: 5,
+            seed: None,
+            paranoid: 0,
+            equilibrate: true,
+            equilibration_length: None,
+            nbins: 10,
+            nmeasure: 500,
+            estimators: DEFAULT_ESTIMATOR_NAMES.iter().map(|name| String::from(*name)).collect(),
+            output_dir: String::from("."),
+            write_update_confs: false,
+            write_measure_confs: false,
+            write_bin_confs: false,
+            write_configuration_style: 2,
+            checkpoint_every: 0,
+            checkpoint_file: String::from("checkpoint.csv"),
+            resume: None,
+            gui: false,
+        }
+    }
+}
+
+const TOP_LEVEL_KEYS: [&'static str; 8] = [
+    "lattice", "updates", "equilibration", "binning", "estimators", "output", "resume", "gui",
+];
+const LATTICE_KEYS: [&'static str; 8] = [
+    "lx", "ly", "modulus", "initial_state", "initial_configuration",
+    "initial_configuration_style", "initial_configuration_line", "monomers",
+];
+const UPDATES_KEYS: [&'static str; 5] = ["type", "weights", "nupdate", "seed", "paranoid"];
+const EQUILIBRATION_KEYS: [&'static str; 2] = ["enabled", "updates"];
+const BINNING_KEYS: [&'static str; 2] = ["nbins", "nmeasure"];
+const OUTPUT_KEYS: [&'static str; 7] = [
+    "directory", "write_update_confs", "write_measure_confs", "write_bin_confs",
+    "configuration_style", "checkpoint_every", "checkpoint_file",
+];
+
+/// Error for any key of `hash` (named `section` in messages) that is not in `keys`. Catches
+/// typos which would otherwise silently leave the default in place.
+fn check_keys(hash: &Yaml, section: &str, keys: &[&str]) -> Result<(), String> {
+    match *hash {
+        Yaml::Hash(ref entries) => {
+            for key in entries.keys() {
+                match key.as_str() {
+                    Some(name) if keys.contains(&name) => (),
+                    _ => return Err(format!("Unknown entry {:?} in {}", key, section)),
+                }
+            }
+            Ok(())
+        },
+        Yaml::BadValue | Yaml::Null => Ok(()),
+        _ => Err(format!("{} should be a map of entries", section)),
+    }
+}
+
+fn yaml_u64(value: &Yaml, name: &str) -> Result<Option<u64>, String> {
+    match *value {
+        Yaml::BadValue | Yaml::Null => Ok(None),
+        Yaml::Integer(i) if i >= 0 => Ok(Some(i as u64)),
+        // Integers too large for an i64, like most generated seeds, come out as reals.
+        Yaml::Real(ref s) if s.parse::<u64>().is_ok() => Ok(s.parse().ok()),
+        _ => Err(format!("{} should be a non-negative integer, got {:?}", name, value)),
+    }
+}
+
+fn yaml_f64(value: &Yaml, name: &str) -> Result<Option<f64>, String> {
+    match *value {
+        Yaml::BadValue | Yaml::Null => Ok(None),
+        Yaml::Integer(i) => Ok(Some(i as f64)),
+        Yaml::Real(_) => Ok(value.as_f64()),
+        _ => Err(format!("{} should be a number, got {:?}", name, value)),
+    }
+}
+
+fn yaml_bool(value: &Yaml, name: &str) -> Result<Option<bool>, String> {
+    match *value {
+        Yaml::BadValue | Yaml::Null => Ok(None),
+        Yaml::Boolean(b) => Ok(Some(b)),
+        _ => Err(format!("{} should be true or false, got {:?}", name, value)),
+    }
+}
+
+fn yaml_string(value: &Yaml, name: &str) -> Result<Option<String>, String> {
+    match *value {
+        Yaml::BadValue | Yaml::Null => Ok(None),
+        Yaml::String(ref s) => Ok(Some(s.clone())),
+        _ => Err(format!("{} should be a string, got {:?}", name, value)),
+    }
+}
+
+/// Unsigned integer entries that have to fit a smaller type, like the modulus.
+fn yaml_u8(value: &Yaml, name: &str) -> Result<Option<u8>, String> {
+    match yaml_u64(value, name)? {
+        Some(v) if v > u8::max_value() as u64 => Err(format!("{} is too large: {}", name, v)),
+        v => Ok(v.map(|v| v as u8)),
+    }
+}
+
+/// Quote a string for YAML so paths with `:` or `#` in them survive.
+fn yaml_quote(s: &str) -> String {
+    format!("\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+impl RunConfig {
+    /// Read a run configuration, see `RunConfig` for the format. Anything not in `contents` keeps
+    /// its default.
+    pub fn from_yaml_str(contents: &str) -> Result<RunConfig, String> {
+        let docs = match YamlLoader::load_from_str(contents) {
+            Ok(docs) => docs,
+            Err(e) => return Err(format!("Not valid YAML: {}", e)),
+        };
+        let mut config = RunConfig::default();
+        let doc = match docs.into_iter().next() {
+            Some(doc) => doc,
+            None => return Ok(config),
+        };
+        check_keys(&doc, "the run configuration", &TOP_LEVEL_KEYS)?;
+
+        let lattice = &doc["lattice"];
+        check_keys(lattice, "lattice", &LATTICE_KEYS)?;
+        if let Some(lx) = yaml_u64(&lattice["lx"], "lattice.lx")? {
+            config.size.x = lx as i64;
+        }
+        if let Some(ly) = yaml_u64(&lattice["ly"], "lattice.ly")? {
+            config.size.y = ly as i64;
+        }
+        if let Some(modulus) = yaml_u8(&lattice["modulus"], "lattice.modulus")? {
+            config.modulus = modulus;
+        }
+        let configuration_path = yaml_string(&lattice["initial_configuration"], "lattice.initial_configuration")?;
+        let configuration_style = yaml_u8(&lattice["initial_configuration_style"], "lattice.initial_configuration_style")?;
+        let configuration_line = yaml_u64(&lattice["initial_configuration_line"], "lattice.initial_configuration_line")?;
+        match yaml_string(&lattice["initial_state"], "lattice.initial_state")? {
+            Some(ref name) if name == "file" => match configuration_path {
+                Some(path) => config.initial_state = InitialState::File {
+                    path,
+                    style: configuration_style.unwrap_or(2),
+                    line: configuration_line.map(|l| l as usize),
+                },
+                None => return Err(String::from(
+                    "lattice.initial_state is file but there is no lattice.initial_configuration")),
+            },
+            Some(name) => config.initial_state = InitialState::from_name(&name)?,
+            None => (),
+        }
+        if let Some(monomers) = yaml_bool(&lattice["monomers"], "lattice.monomers")? {
+            config.monomers = monomers;
+        }
+
+        let updates = &doc["updates"];
+        check_keys(updates, "updates", &UPDATES_KEYS)?;
+        if let Some(name) = yaml_string(&updates["type"], "updates.type")? {
+            config.update_type = update_type_from_name(&name)?;
+        }
+        if let Some(weights) = yaml_f64(&updates["weights"], "updates.weights")? {
+            config.weights = weights;
+        }
+        if let Some(nupdate) = yaml_u64(&updates["nupdate"], "updates.nupdate")? {
+            config.nupdate = nupdate;
+        }
+        config.seed = yaml_u64(&updates["seed"], "updates.seed")?;
+        if let Some(paranoid) = yaml_u64(&updates["paranoid"], "updates.paranoid")? {
+            config.paranoid = paranoid;
+        }
+
+        let equilibration = &doc["equilibration"];
+        check_keys(equilibration, "equilibration", &EQUILIBRATION_KEYS)?;
+        if let Some(enabled) = yaml_bool(&equilibration["enabled"], "equilibration.enabled")? {
+            config.equilibrate = enabled;
+        }
+        config.equilibration_length = yaml_u64(&equilibration["updates"], "equilibration.updates")?;
+
+        let binning = &doc["binning"];
+        check_keys(binning, "binning", &BINNING_KEYS)?;
+        if let Some(nbins) = yaml_u64(&binning["nbins"], "binning.nbins")? {
+            config.nbins = nbins;
+        }
+        if let Some(nmeasure) = yaml_u64(&binning["nmeasure"], "binning.nmeasure")? {
+            config.nmeasure = nmeasure;
+        }
+
+        match doc["estimators"] {
+            // The defaults, less the ones a string net with monomers can not use.
+            Yaml::BadValue => if config.monomers {
+                config.estimators.retain(|name| !CLOSED_NET_ESTIMATOR_NAMES.contains(&name.as_str()));
+            },
+            Yaml::Array(ref names) => {
+                config.estimators = Vec::new();
+                for name in names {
+                    match yaml_string(name, "estimators")? {
+                        Some(name) => config.estimators.push(name),
+                        None => return Err(String::from("estimators should be a list of names")),
+                    }
+                }
+            },
+            Yaml::Null => config.estimators = Vec::new(),
+            ref other => return Err(format!("estimators should be a list of names, got {:?}", other)),
+        }
+
+        let output = &doc["output"];
+        check_keys(output, "output", &OUTPUT_KEYS)?;
+        if let Some(directory) = yaml_string(&output["directory"], "output.directory")? {
+            config.output_dir = directory;
+        }
+        if let Some(b) = yaml_bool(&output["write_update_confs"], "output.write_update_confs")? {
+            config.write_update_confs = b;
+        }
+        if let Some(b) = yaml_bool(&output["write_measure_confs"], "output.write_measure_confs")? {
+            config.write_measure_confs = b;
+        }
+        if let Some(b) = yaml_bool(&output["write_bin_confs"], "output.write_bin_confs")? {
+            config.write_bin_confs = b;
+        }
+        if let Some(style) = yaml_u8(&output["configuration_style"], "output.configuration_style")? {
+            config.write_configuration_style = style;
+        }
+        if let Some(every) = yaml_u64(&output["checkpoint_every"], "output.checkpoint_every")? {
+            config.checkpoint_every = every;
+        }
+        if let Some(file) = yaml_string(&output["checkpoint_file"], "output.checkpoint_file")? {
+            config.checkpoint_file = file;
+        }
+
+        config.resume = yaml_string(&doc["resume"], "resume")?;
+        if let Some(gui) = yaml_bool(&doc["gui"], "gui")? {
+            config.gui = gui;
+        }
+
+        config.check()?;
+        Ok(config)
+    }
+
+    /// Check that the configuration describes a run that can be done.
+    pub fn check(&self) -> Result<(), String> {
+        check_lattice_size(&self.size)?;
+        if self.modulus < 2 {
+            return Err(format!("The modulus has to be at least 2, got {}", self.modulus));
+        }
+        match self.initial_state {
+            InitialState::Blank | InitialState::File { .. } => (),
+            ref z3_only => if self.modulus != 3 {
+                return Err(format!("Initial state {} only exists for modulus 3", z3_only.name()));
+            },
+        }
+        if let InitialState::File { style, .. } = self.initial_state {
+            if style != 1 && style != 2 {
+                return Err(format!("Initial configuration style has to be 1 or 2, got {}", style));
+            }
+        }
+        if self.write_configuration_style > 2 {
+            return Err(format!("Configuration style has to be 0, 1 or 2, got {}",
+                               self.write_configuration_style));
+        }
+        for name in &self.estimators {
+            if !ESTIMATOR_NAMES.contains(&name.as_str()) {
+                return Err(format!("Unknown estimator {} (known: {})", name, ESTIMATOR_NAMES.join(", ")));
+            }
+            if self.monomers && CLOSED_NET_ESTIMATOR_NAMES.contains(&name.as_str()) {
+                return Err(format!("Estimator {} needs a closed string net and can not be used with monomers",
+                                   name));
+            }
+        }
+        if self.monomers && (self.write_update_confs || self.write_measure_confs || self.write_bin_confs) {
+            return Err(String::from("Configuration files do not store monomers, so they can not be \
+                                     written in a run with lattice.monomers"));
+        }
+        if !self.monomers && self.uses_estimator("monomer_correlation") {
+            return Err(String::from("Estimator monomer_correlation needs lattice.monomers"));
+        }
+        Ok(())
+    }
+
+    /// `checkpoint_file` resolved against the output directory, so runs writing to different
+    /// directories never share a checkpoint.
+    pub fn checkpoint_path(&self) -> PathBuf {
+        Path::new(&self.output_dir).join(&self.checkpoint_file)
+    }
+
+    pub fn uses_estimator(&self, name: &str) -> bool {
+        self.estimators.iter().any(|selected| selected == name)
+    }
+
+    /// Updates before the first measurement if the run equilibrates.
+    pub fn equilibration_updates(&self) -> u64 {
+        self.equilibration_length.unwrap_or((self.size.x * self.size.y * 2) as u64)
+    }
+
+    /// The configuration in the format `from_yaml_str` reads, with every entry written out.
+    pub fn to_yaml_string(&self) -> String {
+        let mut out_string = String::new();
+        out_string.push_str("lattice:\n");
+        out_string.push_str(&format!("  lx: {}\n", self.size.x));
+        out_string.push_str(&format!("  ly: {}\n", self.size.y));
+        out_string.push_str(&format!("  modulus: {}\n", self.modulus));
+        out_string.push_str(&format!("  initial_state: {}\n", self.initial_state.name()));
+        if let InitialState::File { ref path, style, line } = self.initial_state {
+            out_string.push_str(&format!("  initial_configuration: {}\n", yaml_quote(path)));
+            out_string.push_str(&format!("  initial_configuration_style: {}\n", style));
+            if let Some(line) = line {
+                out_string.push_str(&format!("  initial_configuration_line: {}\n", line));
+            }
+        }
+        out_string.push_str(&format!("  monomers: {}\n", self.monomers));
+        out_string.push_str("updates:\n");
+        out_string.push_str(&format!("  type: {}\n", update_type_name(&self.update_type)));
+        // Debug keeps the decimal point (and every digit) of the weight.
+        out_string.push_str(&format!("  weights: {:?}\n", self.weights));
+        out_string.push_str(&format!("  nupdate: {}\n", self.nupdate));
+        if let Some(seed) = self.seed {
+            out_string.push_str(&format!("  seed: {}\n", seed));
+        }
+        out_string.push_str(&format!("  paranoid: {}\n", self.paranoid));
+        out_string.push_str("equilibration:\n");
+        out_string.push_str(&format!("  enabled: {}\n", self.equilibrate));
+        if let Some(length) = self.equilibration_length {
+            out_string.push_str(&format!("  updates: {}\n", length));
+        }
+        out_string.push_str("binning:\n");
+        out_string.push_str(&format!("  nbins: {}\n", self.nbins));
+        out_string.push_str(&format!("  nmeasure: {}\n", self.nmeasure));
+        out_string.push_str(&format!("estimators: [{}]\n", self.estimators.join(", ")));
+        out_string.push_str("output:\n");
+        out_string.push_str(&format!("  directory: {}\n", yaml_quote(&self.output_dir)));
+        out_string.push_str(&format!("  write_update_confs: {}\n", self.write_update_confs));
+        out_string.push_str(&format!("  write_measure_confs: {}\n", self.write_measure_confs));
+        out_string.push_str(&format!("  write_bin_confs: {}\n", self.write_bin_confs));
+        out_string.push_str(&format!("  configuration_style: {}\n", self.write_configuration_style));
+        out_string.push_str(&format!("  checkpoint_every: {}\n", self.checkpoint_every));
+        out_string.push_str(&format!("  checkpoint_file: {}\n", yaml_quote(&self.checkpoint_file)));
+        if let Some(ref resume) = self.resume {
+            out_string.push_str(&format!("resume: {}\n", yaml_quote(resume)));
+        }
+        out_string.push_str(&format!("gui: {}\n", self.gui));
+        out_string
+    }
+}
+
+/// Read a run configuration file, see `RunConfig` for the format.
+pub fn read_run_config(f_str: &str) -> Result<RunConfig, String> {
+    let mut contents = String::new();
+    match File::open(f_str) {
+        Ok(mut file) => if let Err(e) = file.read_to_string(&mut contents) {
+            return Err(format!("Problem reading {}: {}", f_str, e));
+        },
+        Err(e) => return Err(format!("Problem opening {}: {}", f_str, e)),
+    }
+    match RunConfig::from_yaml_str(&contents) {
+        Ok(config) => Ok(config),
+        Err(e) => Err(format!("{}: {}", f_str, e)),
+    }
+}
+
+/// Write `config` so it can be read back with `read_run_config`.
+pub fn write_run_config(f_str: &str, config: &RunConfig) {
+    let path = Path::new(f_str);
+    let display = path.display();
+
+    let mut file = match File::create(&path){
+        Err(err) => panic!("could not create {}: {}",
+                           display,
+                           err),
+        Ok(good_file) => good_file,
+    };
+    match file.write_all(config.to_yaml_string().as_bytes()){
+        Err(err) => panic!("could not write {}: {}",
+                           display,
+                           err),
+        Ok(_) => println!("Wrote run configuration to {}", display),
+    }
+}
@@ -9,6 +9,86 @@ use std::collections::HashMap;
 #[cfg(test)]
 mod tests {
     use super::*;
+    use datamodel::lattice::build_blank_lat;
+    use datamodel::lattice::build_z3_striped_lat;
+    use datamodel::lattice::build_z3_fully_packed_lat;
+    use estimators::cluster_size_estimator::ClusterSizeEstimator;
+    use lattice_updates::Update;
+    use lattice_updates::UpdateType;
+
+    /// Cluster sizes, sorted, found by walking every cluster with `ClusterSizeEstimator`.
+    fn walked_cluster_sizes(lat: &mut Lattice) -> Vec<u64> {
+        let mut walker = ClusterSizeEstimator::new(lat);
+        for y in 0..lat.size.y {
+            for x in 0..lat.size.x {
+                let loc = BoundPoint {size: lat.size, location: Point {x, y}};
+                if walker.clustered.contains_key(&loc)
+                    || directions_of_filled_links(&lat.get_vertex_from_point(&loc)).is_none() {
+                    continue;
+                }
+                walker.init_calculation_location(Point {x, y}, lat);
+                while walker.is_initialized {
+                    walker.next();
+                }
+            }
+        }
+        let mut sizes: HashMap<u64, u64> = HashMap::new();
+        for label in walker.clustered.values() {
+            *sizes.entry(*label).or_insert(0) += 1;
+        }
+        let mut sizes: Vec<u64> = sizes.values().cloned().collect();
+        sizes.sort();
+        sizes
+    }
+
+    #[test]
+    fn test_label_clusters_of_simple_lattices() {
+        let blank = label_clusters(&build_blank_lat(Point{x: 4, y: 4}));
+        assert_eq!(blank.number_clusters(), 0);
+        assert!(blank.labels.iter().all(|label| label.is_none()));
+
+        let striped = label_clusters(&build_z3_striped_lat(Point{x: 4, y: 6}));
+        assert_eq!(striped.sizes, vec![4; 6]);
+        assert_eq!(striped.wrapping, vec![(true, false); 6]);
+        assert_eq!(striped.labels[4 * 2 + 3], Some(2));
+
+        let fully_packed = label_clusters(&build_z3_fully_packed_lat(Point{x: 4, y: 4}));
+        assert_eq!(fully_packed.sizes, vec![16]);
+        assert_eq!(fully_packed.wrapping, vec![(true, true)]);
+    }
+
+    #[test]
+    fn test_unwrapped_positions_follow_the_links() {
+        // A loop around the plaquette across the corner of the lattice.
+        let mut lat = build_blank_lat(Point{x: 4, y: 4});
+        lat.out_raise_link(&Point{x: 0, y: 0}, &Direction::W);
+        lat.out_lower_link(&Point{x: 3, y: 3}, &Direction::E);
+        lat.out_raise_link(&Point{x: 3, y: 3}, &Direction::N);
+        lat.out_lower_link(&Point{x: 0, y: 0}, &Direction::S);
+        let clusters = label_clusters(&lat);
+        assert_eq!(clusters.sizes, vec![4]);
+        assert_eq!(clusters.wrapping, vec![(false, false)]);
+        let corners = [Point{x: 0, y: 0}, Point{x: 3, y: 0}, Point{x: 0, y: 3}, Point{x: 3, y: 3}];
+        let unwrapped: Vec<Point> = corners.iter().map(|p| clusters.unwrapped[(p.y * 4 + p.x) as usize]).collect();
+        for (a, b) in [(0, 1), (0, 2), (1, 3), (2, 3)].iter() {
+            let distance = (unwrapped[*a].x - unwrapped[*b].x).abs() + (unwrapped[*a].y - unwrapped[*b].y).abs();
+            assert_eq!(distance, 1);
+        }
+    }
+
+    #[test]
+    fn test_label_clusters_agrees_with_the_walker() {
+        for &seed in [3, 8, 19].iter() {
+            let mut lat: Lattice = build_blank_lat(Point{x: 6, y: 6});
+            let mut updater = Update::new(lat.size, 0.8, seed);
+            for _ in 0..30 {
+                updater.main_update(&mut lat, &UpdateType::Worm);
+            }
+            let mut sizes = label_clusters(&lat).sizes;
+            sizes.sort();
+            assert_eq!(sizes, walked_cluster_sizes(&mut lat));
+        }
+    }
 
     #[test]
     fn test_increment_location() {
@@ -37,7 +117,7 @@ mod tests {
     #[test]
     fn test_add_to_direction_vec_if_filled_if_filled() {
         let direction = Direction::N;
-        let link = Link::In;
+        let link = Link::in_link(3);
         let mut direction_vec = Vec::new();
         add_to_direction_vec_if_filled(&mut direction_vec, &direction, &link);
         assert_eq!(vec![Direction::N], direction_vec);
@@ -45,7 +125,7 @@ mod tests {
     #[test]
     fn test_add_to_direction_vec_if_filled_if_empty() {
         let direction = Direction::N;
-        let link = Link::Blank;
+        let link = Link::blank(3);
         let mut direction_vec = Vec::new();
         add_to_direction_vec_if_filled(&mut direction_vec, &direction, &link);
         assert_eq!(direction_vec.len(), 0);
@@ -53,10 +133,10 @@ mod tests {
     #[test]
     fn test_directions_of_filled_links() {
         let test_vertex = Vertex{
-            n: Link::In,
-            e: Link::Blank,
-            s: Link::Out,
-            w: Link::Blank,
+            n: Link::in_link(3),
+            e: Link::blank(3),
+            s: Link::out_link(3),
+            w: Link::blank(3),
             xy: Point{x: 1, y: 1}
         };
         let dir_vec_option: Option<Vec<Direction>> = directions_of_filled_links(&test_vertex);
@@ -70,10 +150,10 @@ mod tests {
     #[test]
     fn test_directions_of_filled_links_when_empty() {
         let test_vertex = Vertex{
-            n: Link::Blank,
-            e: Link::Blank,
-            s: Link::Blank,
-            w: Link::Blank,
+            n: Link::blank(3),
+            e: Link::blank(3),
+            s: Link::blank(3),
+            w: Link::blank(3),
             xy: Point{x: 1, y: 1}
         };
         let dir_vec_option: Option<Vec<Direction>> = directions_of_filled_links(&test_vertex);
@@ -111,6 +191,16 @@ pub fn increment_location(location: BoundPoint, direction: &Direction) -> BoundP
     post_increment_bound_point
 }
 
+/// The location one step from `point` in `direction`, without wrapping around the lattice.
+pub fn unwrapped_step(point: Point, direction: &Direction) -> Point {
+    match *direction {
+        Direction::N => Point {x: point.x, y: point.y + 1},
+        Direction::E => Point {x: point.x + 1, y: point.y},
+        Direction::S => Point {x: point.x, y: point.y - 1},
+        Direction::W => Point {x: point.x - 1, y: point.y},
+    }
+}
+
 /// Reverse the input direction and increment the bound point in that
 /// reversed direction.
 pub fn decrement_location(location: BoundPoint, direction: &Direction) -> BoundPoint {
@@ -122,10 +212,8 @@ pub fn decrement_location(location: BoundPoint, direction: &Direction) -> BoundP
 pub fn add_to_direction_vec_if_filled(
     keep_vec: &mut Vec<Direction>, direction: &Direction, link: &Link
 ) {
-    match *link {
-        Link::In => (*keep_vec).push(direction.clone()),
-        Link::Out => (*keep_vec).push(direction.clone()),
-        Link::Blank => ()
+    if !link.is_blank() {
+        (*keep_vec).push(direction.clone());
     }
 }
 
@@ -147,3 +235,137 @@ pub fn directions_of_filled_links(vertex: &Vertex) -> Option<Vec<Direction>> {
         None
     }
 }
+
+/// Clusters of a configuration as found by `label_clusters`. Vertices are indexed by
+/// `y * lx + x`, for both sublattices.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ClusterLabels {
+    pub size: Point,
+    /// The cluster of every vertex, `None` for vertices without filled links.
+    pub labels: Vec<Option<usize>>,
+    /// Number of vertices in every cluster, by label.
+    pub sizes: Vec<u64>,
+    /// Whether every cluster wraps around the lattice in x and in y, by label.
+    pub wrapping: Vec<(bool, bool)>,
+    /// Every vertex moved by whole lattice lengths so that the vertices of a cluster are laid out
+    /// as the cluster is connected, without jumps across the boundary (for a cluster that
+    /// wraps there is no such layout and the choice is arbitrary).
+    pub unwrapped: Vec<Point>,
+}
+
+impl ClusterLabels {
+    pub fn number_clusters(&self) -> usize {
+        self.sizes.len()
+    }
+}
+
+/// Union-find forest over the vertices. `offsets[v]` is the unwrapped position of `v` minus
+/// the one of its parent.
+struct UnionFind {
+    parents: Vec<usize>,
+    offsets: Vec<Point>,
+    ranks: Vec<u8>,
+    wrapping: Vec<(bool, bool)>,
+}
+
+impl UnionFind {
+    /// The root of `v` and the position of `v` relative to it, compressing the path on the way.
+    fn find(&mut self, v: usize) -> (usize, Point) {
+        let parent = self.parents[v];
+        if parent == v {
+            return (v, Point {x: 0, y: 0});
+        }
+        let (root, parent_offset) = self.find(parent);
+        let offset = Point {x: self.offsets[v].x + parent_offset.x, y: self.offsets[v].y + parent_offset.y};
+        self.parents[v] = root;
+        self.offsets[v] = offset;
+        (root, offset)
+    }
+
+    /// Join `a` and `b`, where `b` sits at `step` from `a`. If they already are in the same
+    /// cluster but at a different relative position, the cluster wraps around.
+    fn union(&mut self, a: usize, b: usize, step: Point) {
+        let (root_a, offset_a) = self.find(a);
+        let (root_b, offset_b) = self.find(b);
+        // Position of root_b relative to root_a.
+        let between = Point {x: offset_a.x + step.x - offset_b.x, y: offset_a.y + step.y - offset_b.y};
+        if root_a == root_b {
+            self.wrapping[root_a].0 |= between.x != 0;
+            self.wrapping[root_a].1 |= between.y != 0;
+            return;
+        }
+        let wraps = (self.wrapping[root_a].0 || self.wrapping[root_b].0,
+                     self.wrapping[root_a].1 || self.wrapping[root_b].1);
+        if self.ranks[root_a] < self.ranks[root_b] {
+            self.parents[root_a] = root_b;
+            self.offsets[root_a] = Point {x: -between.x, y: -between.y};
+            self.wrapping[root_b] = wraps;
+        } else {
+            self.parents[root_b] = root_a;
+            self.offsets[root_b] = between;
+            self.wrapping[root_a] = wraps;
+            if self.ranks[root_a] == self.ranks[root_b] {
+                self.ranks[root_a] += 1;
+            }
+        }
+    }
+}
+
+/// Label the clusters of `lat`, the sets of vertices (of both sublattices) connected by filled
+/// links, with a union-find (Hoshen-Kopelman like) pass over every link. Each union also keeps
+/// track of where the vertices sit relative to each other, which tells whether a cluster
+/// wraps around the torus.
+///
+/// This visits every link once and does not copy the lattice, so it is what the estimators
+/// use. `ClusterSizeEstimator` walks a cluster one step at a time for the GUI instead.
+pub fn label_clusters(lat: &Lattice) -> ClusterLabels {
+    let size = lat.size;
+    let number_vertices = (size.x * size.y) as usize;
+    let index = |p: Point| (p.y * size.x + p.x) as usize;
+    let mut forest = UnionFind {
+        parents: (0..number_vertices).collect(),
+        offsets: vec![Point {x: 0, y: 0}; number_vertices],
+        ranks: vec![0; number_vertices],
+        wrapping: vec![(false, false); number_vertices],
+    };
+    let mut filled = vec![false; number_vertices];
+    for y in 0..size.y {
+        for x in 0..size.x {
+            let loc = Point {x, y};
+            for direction in [Direction::E, Direction::N].iter() {
+                if lat.link_flux(&loc, direction) == 0 {
+                    continue;
+                }
+                let neighbour = increment_location(BoundPoint {size, location: loc}, direction).location;
+                filled[index(loc)] = true;
+                filled[index(neighbour)] = true;
+                forest.union(index(loc), index(neighbour), unwrapped_step(Point {x: 0, y: 0}, direction));
+            }
+        }
+    }
+
+    let mut labels: Vec<Option<usize>> = vec![None; number_vertices];
+    let mut root_labels: HashMap<usize, usize> = HashMap::new();
+    let mut sizes: Vec<u64> = Vec::new();
+    let mut wrapping: Vec<(bool, bool)> = Vec::new();
+    let mut unwrapped: Vec<Point> = Vec::with_capacity(number_vertices);
+    for v in 0..number_vertices {
+        let location = Point {x: v as i64 % size.x, y: v as i64 / size.x};
+        if !filled[v] {
+            unwrapped.push(location);
+            continue;
+        }
+        let (root, offset) = forest.find(v);
+        let next_label = sizes.len();
+        let label = *root_labels.entry(root).or_insert(next_label);
+        if label == next_label {
+            sizes.push(0);
+            wrapping.push(forest.wrapping[root]);
+        }
+        sizes[label] += 1;
+        labels[v] = Some(label);
+        let root_location = Point {x: root as i64 % size.x, y: root as i64 / size.x};
+        unwrapped.push(Point {x: root_location.x + offset.x, y: root_location.y + offset.y});
+    }
+    ClusterLabels {size, labels, sizes, wrapping, unwrapped}
+}
@@ -1,14 +1,93 @@
 use super::Direction;
 use super::Link;
+use super::Monomer;
 use super::Point;
 use super::BoundPoint;
 use super::Vertex;
 use super::cluster::increment_location;
+use std::fmt;
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_check_lattice_size() {
+        assert!(check_lattice_size(&Point{x: 4, y: 4}).is_ok());
+        assert!(check_lattice_size(&Point{x: 8, y: 4}).is_ok());
+        assert!(check_lattice_size(&Point{x: 4, y: 10}).is_ok());
+        assert!(check_lattice_size(&Point{x: 5, y: 4}).is_err());
+        assert!(check_lattice_size(&Point{x: 4, y: 7}).is_err());
+        assert!(check_lattice_size(&Point{x: 2, y: 4}).is_err());
+        assert!(check_lattice_size(&Point{x: 6, y: 0}).is_err());
+    }
+    #[test]
+    fn test_vertex_positions_on_rectangular_lattice() {
+        let mut lat = build_blank_lat(Point{x: 8, y: 4});
+        assert_eq!(lat.vertices.len(), 16);
+        for i in 0..lat.vertices.len() {
+            let xy = lat.vertices[i].xy;
+            assert!(lat.point_real(&xy));
+            assert!(xy.x < 8 && xy.y < 4);
+            assert_eq!(lat.get_vector_location_of_vertex(&xy), i as i64);
+        }
+    }
+    #[test]
+    fn test_validate_accepts_closed_string_nets() {
+        assert!(build_blank_lat(Point{x: 4, y: 4}).validate().is_valid());
+        assert!(build_z3_striped_lat(Point{x: 6, y: 4}).validate().is_valid());
+        assert!(build_z3_fully_packed_lat(Point{x: 4, y: 4}).validate().is_valid());
+    }
+    #[test]
+    fn test_validate_reports_violations() {
+        let mut lat = build_blank_lat(Point{x: 4, y: 4});
+        // A single raised link leaves a charge on each of its ends.
+        lat.out_raise_link(&Point{x: 0, y: 0}, &Direction::E);
+        let report = lat.validate();
+        assert!(!report.is_valid());
+        assert_eq!(report.flux_violations, vec![
+            FluxViolation {location: Point{x: 0, y: 0}, net_flux: 1},
+            FluxViolation {location: Point{x: 1, y: 0}, net_flux: -1},
+        ]);
+        assert_eq!(report.link_count_mismatch, Some((0, 1)));
+    }
+    #[test]
+    fn test_link_flux() {
+        // Every horizontal link of the striped lattice points west.
+        let lat = build_z3_striped_lat(Point{x: 6, y: 4});
+        for y in 0..4 {
+            for x in 0..6 {
+                assert_eq!(lat.link_flux(&Point{x, y}, &Direction::E), -1);
+                assert_eq!(lat.link_flux(&Point{x, y}, &Direction::W), 1);
+                assert_eq!(lat.link_flux(&Point{x, y}, &Direction::N), 0);
+            }
+        }
+        let mut lat = build_blank_lat(Point{x: 4, y: 4});
+        lat.out_raise_link(&Point{x: 0, y: 0}, &Direction::S);
+        assert_eq!(lat.link_flux(&Point{x: 0, y: 0}, &Direction::S), 1);
+        assert_eq!(lat.link_flux(&Point{x: 0, y: 3}, &Direction::N), -1);
+    }
+    #[test]
+    fn test_monomers_carry_the_string_end() {
+        let mut lat = build_blank_lat(Point{x: 4, y: 4});
+        lat.insert_monomer_pair(&Point{x: 0, y: 0}, &Direction::E).unwrap();
+        assert!(lat.insert_monomer_pair(&Point{x: 2, y: 2}, &Direction::N).is_err());
+        assert!(lat.validate().is_valid());
+        assert_eq!(lat.number_filled_links, 1);
+        // Drag the -1 charge around a fake vertex and the +1 charge backwards across the wrap.
+        assert_eq!(lat.move_monomer(1, &Direction::N), 1);
+        assert_eq!(lat.move_monomer(0, &Direction::W), 1);
+        assert_eq!(lat.monomers[0].location, Point{x: 3, y: 0});
+        assert_eq!(lat.monomers[1].location, Point{x: 1, y: 1});
+        assert!(lat.validate().is_valid());
+        assert_eq!(lat.number_filled_links, 3);
+        assert_eq!(lat.move_monomer(0, &Direction::E), -1);
+        assert_eq!(lat.move_monomer(1, &Direction::S), -1);
+        assert_eq!(lat.move_monomer(1, &Direction::W), -1);
+        assert_eq!(lat.number_filled_links, 0);
+        assert_eq!(lat.monomers[0].location, lat.monomers[1].location);
+        assert!(lat.validate().is_valid());
+    }
     #[test]
     fn test_get_blank_vertex_from_real_point() {
         let mut lat: Lattice = build_blank_lat(Point{x: 4, y: 4});
@@ -17,10 +96,10 @@ mod tests {
             location: Point{x: 0, y: 0},
         };
         let vertex: Vertex = lat.get_vertex_from_point(&loc);
-        assert_eq!(vertex.n, Link::Blank);
-        assert_eq!(vertex.e, Link::Blank);
-        assert_eq!(vertex.s, Link::Blank);
-        assert_eq!(vertex.w, Link::Blank);
+        assert_eq!(vertex.n, Link::blank(3));
+        assert_eq!(vertex.e, Link::blank(3));
+        assert_eq!(vertex.s, Link::blank(3));
+        assert_eq!(vertex.w, Link::blank(3));
     }
     #[test]
     fn test_get_blank_vertex_from_fake_point() {
@@ -30,10 +109,10 @@ mod tests {
             location: Point{x: 1, y: 0},
         };
         let vertex: Vertex = lat.get_vertex_from_point(&loc);
-        assert_eq!(vertex.n, Link::Blank);
-        assert_eq!(vertex.e, Link::Blank);
-        assert_eq!(vertex.s, Link::Blank);
-        assert_eq!(vertex.w, Link::Blank);
+        assert_eq!(vertex.n, Link::blank(3));
+        assert_eq!(vertex.e, Link::blank(3));
+        assert_eq!(vertex.s, Link::blank(3));
+        assert_eq!(vertex.w, Link::blank(3));
     }
     #[test]
     fn test_get_in_out_vertext_from_real_point() {
@@ -43,10 +122,10 @@ mod tests {
             location: Point{x: 0, y: 0},
         };
         let vertex: Vertex = lat.get_vertex_from_point(&loc);
-        assert_eq!(vertex.w, Link::In);
-        assert_eq!(vertex.e, Link::Out);
-        assert_eq!(vertex.n, Link::Blank);
-        assert_eq!(vertex.s, Link::Blank);
+        assert_eq!(vertex.w, Link::in_link(3));
+        assert_eq!(vertex.e, Link::out_link(3));
+        assert_eq!(vertex.n, Link::blank(3));
+        assert_eq!(vertex.s, Link::blank(3));
     }
     #[test]
     fn test_get_in_out_vertext_from_fake_point() {
@@ -56,10 +135,10 @@ mod tests {
             location: Point{x: 1, y: 0},
         };
         let vertex: Vertex = lat.get_vertex_from_point(&loc);
-        assert_eq!(vertex.w, Link::In);
-        assert_eq!(vertex.e, Link::Out);
-        assert_eq!(vertex.n, Link::Blank);
-        assert_eq!(vertex.s, Link::Blank);
+        assert_eq!(vertex.w, Link::in_link(3));
+        assert_eq!(vertex.e, Link::out_link(3));
+        assert_eq!(vertex.n, Link::blank(3));
+        assert_eq!(vertex.s, Link::blank(3));
     }
 }
 
@@ -84,7 +163,11 @@ mod tests {
 pub struct Lattice {
     pub vertices: Vec<Vertex>,
     pub size: Point,
+    /// The N of the ZN string net. Every link of the lattice has this modulus.
+    pub modulus: u8,
     pub number_filled_links: i64,
+    /// Test charges the string net ends on, empty for a closed string net.
+    pub monomers: Vec<Monomer>,
 }
 impl Lattice {
     /// Only storing one sublattice so other vertices are implied.
@@ -96,7 +179,7 @@ impl Lattice {
     }
 
     /// The location of vertex in the vector. This works becuase integers division rounds down.
-    pub fn get_vector_location_of_vertex(&mut self, loc: &Point) -> i64 {
+    pub fn get_vector_location_of_vertex(&self, loc: &Point) -> i64 {
         loc.y * (self.size.x/2) + loc.x/2
     }
 
@@ -110,17 +193,16 @@ impl Lattice {
     /// because it may not belong to the sublattice that `Lattice` is made out of and most
     /// importantly changes to the links will not be reflected anywhere else. The returned
     /// `Vertex` does not (&) reference any "real" information of the lattice.
-    pub fn get_vertex_from_point(&mut self, loc: &BoundPoint) -> Vertex {
+    pub fn get_vertex_from_point(&self, loc: &BoundPoint) -> Vertex {
         let point_from_bound = Point{x: loc.location.x, y: loc.location.y};
         let mut to_return_vertex: Vertex;
         let is_real = self.point_real(&point_from_bound);
         if is_real {
-            let vloc = self.get_vector_location_of_vertex(&point_from_bound);
             to_return_vertex = Vertex {
-                n: self.get_link_from_point(&point_from_bound, &Direction::N).clone(),
-                e: self.get_link_from_point(&point_from_bound, &Direction::E).clone(),
-                s: self.get_link_from_point(&point_from_bound, &Direction::S).clone(),
-                w: self.get_link_from_point(&point_from_bound, &Direction::W).clone(),
+                n: self.safe_get_link_from_point(&point_from_bound, &Direction::N).clone(),
+                e: self.safe_get_link_from_point(&point_from_bound, &Direction::E).clone(),
+                s: self.safe_get_link_from_point(&point_from_bound, &Direction::S).clone(),
+                w: self.safe_get_link_from_point(&point_from_bound, &Direction::W).clone(),
                 xy: Point {x: loc.location.x, y: loc.location.y},
             }
         }
@@ -136,25 +218,25 @@ impl Lattice {
             // Start with E direction like above
             let _e_new_loc: BoundPoint = increment_location(*loc, &Direction::E);
             let e_new_point_from_bound = Point{x: _e_new_loc.location.x, y: _e_new_loc.location.y};
-            let east_link: Link = self.get_link_from_point(
+            let east_link: Link = self.safe_get_link_from_point(
                 &e_new_point_from_bound, &Direction::W).clone().flip();
 
             // W direction 
             let _w_new_loc: BoundPoint = increment_location(*loc, &Direction::W);
             let w_new_point_from_bound = Point{x: _w_new_loc.location.x, y: _w_new_loc.location.y};
-            let west_link: Link = self.get_link_from_point(
+            let west_link: Link = self.safe_get_link_from_point(
                 &w_new_point_from_bound, &Direction::E).clone().flip();
 
             // N direction 
             let _n_new_loc: BoundPoint = increment_location(*loc, &Direction::N);
             let n_new_point_from_bound = Point{x: _n_new_loc.location.x, y: _n_new_loc.location.y};
-            let north_link: Link = self.get_link_from_point(
+            let north_link: Link = self.safe_get_link_from_point(
                 &n_new_point_from_bound, &Direction::S).clone().flip();
 
             // S direction 
             let _s_new_loc: BoundPoint = increment_location(*loc, &Direction::S);
             let s_new_point_from_bound = Point{x: _s_new_loc.location.x, y: _s_new_loc.location.y};
-            let south_link: Link = self.get_link_from_point(
+            let south_link: Link = self.safe_get_link_from_point(
                 &s_new_point_from_bound, &Direction::N).clone().flip();
 
             // TODO
@@ -231,14 +313,8 @@ impl Lattice {
         // This function, because of get_link_from_point(), will only will only work
         // on real verticies. Thats the way we want it
         let link: &mut Link = self.get_link_from_point(loc, direction);
-        match *link {
-            Link::In    => {*link = Link::Blank;
-                            Link::Blank},
-            Link::Out   => {*link = Link::In;
-                            Link::In},
-            Link::Blank => {*link = Link::Out;
-                            Link::Out},
-        }
+        *link = link.raise();
+        *link
     }
     pub fn out_lower_link(&mut self, loc: &Point, direction: &Direction) -> Link{
         // Lower a link traveling outward from the specified vertex. Also see raise 
@@ -246,53 +322,197 @@ impl Lattice {
         //println!("in out_lower_link. ---> location is: {:?}", loc);
         //println!("in out_lower_link. ---> directio is: {:?}", direction);
         let link: &mut Link = self.get_link_from_point(loc, direction);
-        match *link {
-            Link::In    => {*link = Link::Out;
-                            Link::Out},
-            Link::Out   => {*link = Link::Blank;
-                            Link::Blank},
-            Link::Blank => {*link = Link::In;
-                            Link::In},
+        *link = link.lower();
+        *link
+    }
+
+    /// Flux of the link leaving `loc` in `direction`, positive if it points away from `loc`.
+    /// Works for vertices of both sublattices.
+    pub fn link_flux(&self, loc: &Point, direction: &Direction) -> i64 {
+        let vertex = self.get_vertex_from_point(&BoundPoint {size: self.size, location: *loc});
+        match *direction {
+            Direction::N => vertex.n.signed_value(),
+            Direction::E => vertex.e.signed_value(),
+            Direction::S => vertex.s.signed_value(),
+            Direction::W => vertex.w.signed_value(),
         }
     }
 
-    pub fn count_non_blank_links(&mut self) -> u64{
+    pub fn count_non_blank_links(&self) -> u64{
         let mut count: u64 = 0;
         for (_, cur_vertex) in self.vertices.iter().enumerate(){
-            match cur_vertex.n {
-                Link::In  => {count += 1},
-                Link::Out => {count += 1},
-                Link::Blank => (),
-            }
-            match cur_vertex.e {
-                Link::In  => {count += 1},
-                Link::Out => {count += 1},
-                Link::Blank => (),
-            }
-            match cur_vertex.s {
-                Link::In  => {count += 1},
-                Link::Out => {count += 1},
-                Link::Blank => (),
-            }
-            match cur_vertex.w {
-                Link::In  => {count += 1},
-                Link::Out => {count += 1},
-                Link::Blank => (),
-            }
+            if !cur_vertex.n.is_blank() {count += 1}
+            if !cur_vertex.e.is_blank() {count += 1}
+            if !cur_vertex.s.is_blank() {count += 1}
+            if !cur_vertex.w.is_blank() {count += 1}
         }
         count
     }
+
+    /// Raise the link leaving `loc` in `direction` (works for vertices of both sublattices) and
+    /// return the change in the number of filled links.
+    fn raise_outward_link(&mut self, loc: &Point, direction: &Direction) -> i64 {
+        let neighbour = increment_location(BoundPoint {size: self.size, location: *loc}, direction).location;
+        let (before, after) = if self.point_real(loc) {
+            let before = *self.get_link_from_point(loc, direction);
+            (before, self.out_raise_link(loc, direction))
+        } else {
+            let before = *self.get_link_from_point(&neighbour, &direction.flip());
+            (before, self.out_lower_link(&neighbour, &direction.flip()))
+        };
+        let change = match (before.is_blank(), after.is_blank()) {
+            (true, false) => 1,
+            (false, true) => -1,
+            _ => 0,
+        };
+        self.number_filled_links += change;
+        change
+    }
+
+    /// Sum of the charges of the monomers at `loc`.
+    pub fn charge_at(&self, loc: &Point) -> i64 {
+        self.monomers.iter().filter(|m| m.location == *loc).map(|m| m.charge).sum()
+    }
+
+    /// Put a pair of test charges on the string net by raising the link leaving `loc` in
+    /// `direction`: a monomer of charge +1 at `loc` and one of charge -1 at its neighbour.
+    /// Only one pair is supported.
+    pub fn insert_monomer_pair(&mut self, loc: &Point, direction: &Direction) -> Result<(), String> {
+        if !self.monomers.is_empty() {
+            return Err(String::from("The lattice already has a pair of monomers"));
+        }
+        self.raise_outward_link(loc, direction);
+        let neighbour = increment_location(BoundPoint {size: self.size, location: *loc}, direction).location;
+        self.monomers.push(Monomer {location: *loc, charge: 1});
+        self.monomers.push(Monomer {location: neighbour, charge: -1});
+        Ok(())
+    }
+
+    /// Move monomer `index` one step in `direction`, changing the link it crosses so the string
+    /// net still ends on it. Returns the change in the number of filled links. Moving it back
+    /// in the opposite direction restores the lattice exactly.
+    pub fn move_monomer(&mut self, index: usize, direction: &Direction) -> i64 {
+        let Monomer {location, charge} = self.monomers[index];
+        let neighbour = increment_location(BoundPoint {size: self.size, location}, direction).location;
+        // The unit of flux the monomer sources (+1) or sinks (-1) has to end up on the
+        // neighbour, so the link between them takes one unit towards it.
+        let change = if charge > 0 {
+            self.raise_outward_link(&neighbour, &direction.flip())
+        } else {
+            self.raise_outward_link(&location, direction)
+        };
+        self.monomers[index].location = neighbour;
+        change
+    }
+
+    /// Check that the lattice is a ZN string net that is closed except at its monomers.
+    ///
+    /// Every vertex, of both sublattices, has to have a net flux equal to the charge of the
+    /// monomers on it (zero for most) modulo `modulus` and `number_filled_links` has to agree
+    /// with `count_non_blank_links`. Nothing is changed, all problems found are collected in
+    /// the returned report.
+    pub fn validate(&self) -> ValidationReport {
+        let mut report = ValidationReport {
+            flux_violations: Vec::new(),
+            link_count_mismatch: None,
+        };
+        for y in 0..self.size.y {
+            for x in 0..self.size.x {
+                let vertex: Vertex = self.get_vertex_from_point(
+                    &BoundPoint {size: self.size, location: Point {x, y}}
+                );
+                let net_flux: i64 = vertex.n.signed_value() + vertex.e.signed_value()
+                    + vertex.s.signed_value() + vertex.w.signed_value();
+                if (net_flux - self.charge_at(&Point {x, y})) % (self.modulus as i64) != 0 {
+                    report.flux_violations.push(FluxViolation {
+                        location: Point {x, y},
+                        net_flux,
+                    });
+                }
+            }
+        }
+        let counted_links = self.count_non_blank_links();
+        if self.number_filled_links < 0 || self.number_filled_links as u64 != counted_links {
+            report.link_count_mismatch = Some((self.number_filled_links, counted_links));
+        }
+        report
+    }
 }
 
+/// A vertex where the flux into the vertex does not match the flux out of it.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FluxViolation {
+    pub location: Point,
+    /// Net flux out of the vertex, `In` links counting as negative.
+    pub net_flux: i64,
+}
 
+/// Everything `Lattice::validate` found wrong with a lattice.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ValidationReport {
+    pub flux_violations: Vec<FluxViolation>,
+    /// `(number_filled_links, count_non_blank_links())` if the two disagree.
+    pub link_count_mismatch: Option<(i64, u64)>,
+}
+impl ValidationReport {
+    pub fn is_valid(&self) -> bool {
+        self.flux_violations.is_empty() && self.link_count_mismatch.is_none()
+    }
+}
+impl fmt::Display for ValidationReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.is_valid() {
+            return write!(f, "Lattice is valid");
+        }
+        if let Some((stored, counted)) = self.link_count_mismatch {
+            writeln!(f, "number_filled_links is {} but {} links are filled", stored, counted)?;
+        }
+        for violation in &self.flux_violations {
+            writeln!(f, "Net flux {} at vertex x: {}, y: {}",
+                     violation.net_flux, violation.location.x, violation.location.y)?;
+        }
+        Ok(())
+    }
+}
+
+/// The smallest edge length we support. Edges are even, and along an edge of 2 the neighbours
+/// on either side of a vertex would be one and the same vertex.
+pub const MIN_LATTICE_EDGE: i64 = 4;
+
+/// Checks that `size` can be used to build a `Lattice`.
+///
+/// Both edges have to be even, otherwise the two sublattices do not close up under the
+/// periodic boundary conditions, and at least `MIN_LATTICE_EDGE` long. The edges do not have to
+/// be equal.
+pub fn check_lattice_size(size: &Point) -> Result<(), String> {
+    for &(name, edge) in [("x", size.x), ("y", size.y)].iter() {
+        if edge < MIN_LATTICE_EDGE {
+            return Err(format!("Lattice size in {} is {} but it has to be at least {}",
+                               name, edge, MIN_LATTICE_EDGE));
+        }
+        if edge % 2 != 0 {
+            return Err(format!("Lattice size in {} is {} but it has to be even", name, edge));
+        }
+    }
+    Ok(())
+}
+
+/// Blank Z3 lattice. See `build_blank_zn_lat` for other N.
 pub fn build_blank_lat(size: Point) -> Lattice {
-    println!("Building blank lattice of size x {}, y {}",
-             size.x, size.y);
+    build_blank_zn_lat(size, 3)
+}
+
+/// Blank lattice of a ZN string net with N = `modulus`.
+pub fn build_blank_zn_lat(size: Point, modulus: u8) -> Lattice {
+    println!("Building blank Z{} lattice of size x {}, y {}",
+             modulus, size.x, size.y);
 
     let mut lat: Lattice = Lattice {
         vertices: Vec::new(),
         size,
-        number_filled_links: 0
+        modulus,
+        number_filled_links: 0,
+        monomers: Vec::new(),
     };
 
     let half_n = (lat.size.x * lat.size.y)/2;
@@ -302,10 +522,10 @@ pub fn build_blank_lat(size: Point) -> Lattice {
     println!("Filling vertex array:");
     for i in 0..half_n {
         let cur_vertex: Vertex = Vertex{
-            n: Link::Blank,
-            e: Link::Blank,
-            s: Link::Blank,
-            w: Link::Blank,
+            n: Link::blank(modulus),
+            e: Link::blank(modulus),
+            s: Link::blank(modulus),
+            w: Link::blank(modulus),
             xy: Point{
                 x: x_from_vertex_vec_position(i, &lat.size),
                 y: y_from_vertex_vec_position(i, &lat.size),
@@ -335,7 +555,9 @@ pub fn build_z3_striped_lat(size: Point) -> Lattice {
     let mut lat: Lattice = Lattice {
         vertices: Vec::new(),
         size,
-        number_filled_links: (size.y / 2 * size.x) as i64
+        modulus: 3,
+        number_filled_links: (size.y * size.x) as i64,
+        monomers: Vec::new(),
     };
 
     let half_n = (lat.size.x * lat.size.y)/2;
@@ -345,10 +567,10 @@ pub fn build_z3_striped_lat(size: Point) -> Lattice {
     println!("Filling vertex array:");
     for i in 0..half_n {
         let cur_vertex: Vertex = Vertex{
-            n: Link::Blank,
-            e: Link::In,
-            s: Link::Blank,
-            w: Link::Out,
+            n: Link::blank(3),
+            e: Link::in_link(3),
+            s: Link::blank(3),
+            w: Link::out_link(3),
             xy: Point{
                 x: x_from_vertex_vec_position(i, &lat.size),
                 y: y_from_vertex_vec_position(i, &lat.size),
@@ -367,7 +589,9 @@ pub fn build_z3_striped_vertical_lat(size: Point) -> Lattice {
     let mut lat: Lattice = Lattice {
         vertices: Vec::new(),
         size,
-        number_filled_links: (size.y * size.x) as i64
+        modulus: 3,
+        number_filled_links: (size.y * size.x) as i64,
+        monomers: Vec::new(),
     };
 
     let half_n = (lat.size.x * lat.size.y)/2;
@@ -377,10 +601,10 @@ pub fn build_z3_striped_vertical_lat(size: Point) -> Lattice {
     println!("Filling vertex array:");
     for i in 0..half_n {
         let cur_vertex: Vertex = Vertex{
-            n: Link::Out,
-            e: Link::Blank,
-            s: Link::In,
-            w: Link::Blank,
+            n: Link::out_link(3),
+            e: Link::blank(3),
+            s: Link::in_link(3),
+            w: Link::blank(3),
             xy: Point{
                 x: x_from_vertex_vec_position(i, &lat.size),
                 y: y_from_vertex_vec_position(i, &lat.size),
@@ -399,7 +623,9 @@ pub fn build_z3_fully_packed_lat(size: Point) -> Lattice {
     let mut lat: Lattice = Lattice {
         vertices: Vec::new(),
         size,
-        number_filled_links: (size.y * size.x * 2) as i64
+        modulus: 3,
+        number_filled_links: (size.y * size.x * 2) as i64,
+        monomers: Vec::new(),
     };
 
     let half_n = (lat.size.x * lat.size.y)/2;
@@ -409,10 +635,10 @@ pub fn build_z3_fully_packed_lat(size: Point) -> Lattice {
     println!("Filling vertex array:");
     for i in 0..half_n {
         let cur_vertex: Vertex = Vertex{
-            n: Link::Out,
-            e: Link::Out,
-            s: Link::In,
-            w: Link::In,
+            n: Link::out_link(3),
+            e: Link::out_link(3),
+            s: Link::in_link(3),
+            w: Link::in_link(3),
             xy: Point{
                 x: x_from_vertex_vec_position(i, &lat.size),
                 y: y_from_vertex_vec_position(i, &lat.size),
@@ -431,7 +657,9 @@ pub fn build_z3_messy_lat(size: Point) -> Lattice {
     let mut lat: Lattice = Lattice {
         vertices: Vec::new(),
         size,
-        number_filled_links: (size.y / 2 * size.x) as i64
+        modulus: 3,
+        number_filled_links: (size.y / 2 * size.x) as i64,
+        monomers: Vec::new(),
     };
 
     let half_n = (lat.size.x * lat.size.y)/2;
@@ -442,10 +670,10 @@ pub fn build_z3_messy_lat(size: Point) -> Lattice {
     for i in 0..half_n {
         if ((i % 2 == 0) & (i < 4)) {
             let cur_vertex: Vertex = Vertex{
-                n: Link::Blank,
-                e: Link::Blank,
-                s: Link::In,
-                w: Link::Out,
+                n: Link::blank(3),
+                e: Link::blank(3),
+                s: Link::in_link(3),
+                w: Link::out_link(3),
                 xy: Point{
                     x: x_from_vertex_vec_position(i, &lat.size),
                     y: y_from_vertex_vec_position(i, &lat.size),
@@ -455,10 +683,10 @@ pub fn build_z3_messy_lat(size: Point) -> Lattice {
         }
         else {
             let cur_vertex: Vertex = Vertex{
-                n: Link::Blank,
-                e: Link::Blank,
-                s: Link::Blank,
-                w: Link::Blank,
+                n: Link::blank(3),
+                e: Link::blank(3),
+                s: Link::blank(3),
+                w: Link::blank(3),
                 xy: Point{
                     x: x_from_vertex_vec_position(i, &lat.size),
                     y: y_from_vertex_vec_position(i, &lat.size),
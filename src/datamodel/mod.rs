@@ -1,4 +1,3 @@
-use rand;
 use rand::Rng;
 pub mod lattice;
 pub mod cluster;
@@ -7,6 +6,46 @@ use self::lattice::y_from_vertex_vec_position;
 use std::ops::Add;
 use std::slice::Iter;
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_z3_links_match_in_out_blank() {
+        let blank = Link::blank(3);
+        let out_link = Link::out_link(3);
+        let in_link = Link::in_link(3);
+        assert_eq!(blank.orientation(), Orientation::Blank);
+        assert_eq!(out_link.orientation(), Orientation::Out);
+        assert_eq!(in_link.orientation(), Orientation::In);
+        assert_eq!(out_link.flip(), in_link);
+        assert_eq!(blank.flip(), blank);
+        // Raising goes Blank -> Out -> In -> Blank
+        assert_eq!(blank.raise(), out_link);
+        assert_eq!(out_link.raise(), in_link);
+        assert_eq!(in_link.raise(), blank);
+        assert_eq!(in_link.lower(), out_link);
+    }
+    #[test]
+    fn test_zn_link_arithmetic() {
+        // Z2: a filled link is its own reverse.
+        let z2 = Link::out_link(2);
+        assert_eq!(z2.flip(), z2);
+        assert_eq!(z2.raise(), Link::blank(2));
+        // Z4: two units of flux point both ways.
+        let z4 = Link::new(2, 4);
+        assert_eq!(z4.flip(), z4);
+        assert_eq!(z4.signed_value(), 2);
+        assert_eq!(Link::new(3, 4).signed_value(), -1);
+        assert_eq!(Link::new(7, 4), Link::new(3, 4));
+        // Large moduli go past u8 on the way.
+        let z255 = Link::blank(255);
+        assert_eq!(z255.lower(), Link::new(254, 255));
+        assert_eq!(z255.lower().raise(), z255);
+        assert_eq!(Link::new(200, 200).lower(), Link::new(199, 200));
+    }
+}
+
 
 //#[derive(Debug, Clone, Copy, Eq, PartialEq)]
 ///// An AbsolutePlaquett is a plaquett view of the surounding links where the links are specified
@@ -34,27 +73,88 @@ use std::slice::Iter;
 //    Blank
 //}
 
-//#[derive(Debug, Clone, Copy)]
+/// Which way a link points as seen from the vertex it is stored on (or looked at from).
 #[derive(Debug, Clone, Copy, Eq, PartialEq)]
-pub enum Link {
+pub enum Orientation {
     In,
     Out,
     Blank,
 }
+
+/// A link of a ZN string net.
+///
+/// `value` is the flux carried out of the vertex the link is looked at from, modulo `modulus`
+/// (the N of ZN). Looking at the same link from the vertex on the other end negates the value,
+/// see `flip`. For Z3 the values 0, 1 and 2 are the familiar `Blank`, `Out` and `In` links.
+/// For Z2 (loop gas) a filled link points both ways so it is always `Out`.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub struct Link {
+    value: u8,
+    modulus: u8,
+}
 impl Link {
-    pub fn flip(&self) -> Link {
-        match *self{
-            Link::In => {Link::Out}
-            Link::Out => {Link::In}
-            Link::Blank => {Link::Blank}
+    pub fn new(value: u8, modulus: u8) -> Link {
+        assert!(modulus >= 2, "Links need a modulus of at least 2, got {}", modulus);
+        Link {value: value % modulus, modulus}
+    }
+    pub fn blank(modulus: u8) -> Link {
+        Link::new(0, modulus)
+    }
+    /// One unit of flux out of the vertex.
+    pub fn out_link(modulus: u8) -> Link {
+        Link::new(1, modulus)
+    }
+    /// One unit of flux into the vertex.
+    pub fn in_link(modulus: u8) -> Link {
+        Link::new(modulus - 1, modulus)
+    }
+    pub fn value(&self) -> u8 {
+        self.value
+    }
+    pub fn modulus(&self) -> u8 {
+        self.modulus
+    }
+    pub fn is_blank(&self) -> bool {
+        self.value == 0
+    }
+    /// The value as a flux in `(-N/2, N/2]`, so `In` for Z3 is -1.
+    pub fn signed_value(&self) -> i64 {
+        if 2 * (self.value as i64) <= self.modulus as i64 {
+            self.value as i64
+        }
+        else {
+            self.value as i64 - self.modulus as i64
         }
     }
-    pub fn soft_flip(link: &Link) -> Link {
-        match link{
-            Link::In => {Link::Out}
-            Link::Out => {Link::In}
-            Link::Blank => {Link::Blank}
+    pub fn orientation(&self) -> Orientation {
+        let signed_value = self.signed_value();
+        if signed_value > 0 {
+            Orientation::Out
         }
+        else if signed_value < 0 {
+            Orientation::In
+        }
+        else {
+            Orientation::Blank
+        }
+    }
+    /// The same link looked at from the vertex on the other end.
+    pub fn flip(&self) -> Link {
+        Link {value: (self.modulus - self.value) % self.modulus, modulus: self.modulus}
+    }
+    /// The value a link, measured outward from a vertex, takes after a raise step leaves
+    /// that vertex across it: one more unit of flux out of the vertex.
+    pub fn raise(&self) -> Link {
+        Link {value: ((self.value as u16 + 1) % self.modulus as u16) as u8, modulus: self.modulus}
+    }
+    /// Inverse of `raise`. Worked out in u16 since value + modulus does not fit a u8 for
+    /// moduli above 128.
+    pub fn lower(&self) -> Link {
+        let value = (self.value as u16 + self.modulus as u16 - 1) % self.modulus as u16;
+        Link {value: value as u8, modulus: self.modulus}
+    }
+    pub fn soft_flip(link: &Link) -> Link {
+        link.flip()
     }
 }
 
@@ -74,8 +174,10 @@ impl Direction {
             Direction::W => { Direction::E }
         }
     }
-    pub fn get_random_direction() -> Direction {
-        let direction_int = rand::thread_rng().gen_range(0, 4);
+    /// Draw a direction uniformly at random from the passed in generator so that runs
+    /// seeded with the same value walk the same way.
+    pub fn get_random_direction<R: Rng>(rng: &mut R) -> Direction {
+        let direction_int = rng.gen_range(0, 4);
         assert!((direction_int < 4) && (direction_int >= 0));
         match direction_int {
             0 => { Direction::N }
@@ -142,6 +244,14 @@ pub struct Vertex {
     pub xy: Point,
 }
 
+/// A test charge: a vertex where the net flux out of the vertex is `charge` instead of 0
+/// (modulo N). See `Lattice::insert_monomer_pair`.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct Monomer {
+    pub location: Point,
+    pub charge: i64,
+}
+
 #[derive(Debug, Copy, Clone, Hash, Eq, PartialEq)]
 pub struct Point {
     pub x: i64,
@@ -0,0 +1,243 @@
+use std::collections::BTreeMap;
+use std::io::prelude::*;
+use super::Measurable;
+use super::EstimatorOutput;
+use super::ResultSink;
+use super::flush_result_file;
+use super::vertex_type_estimator::VertexType;
+use super::super::datamodel::BoundPoint;
+use super::super::datamodel::Direction;
+use super::super::datamodel::Point;
+use super::super::datamodel::cluster::ClusterLabels;
+use super::super::datamodel::cluster::increment_location;
+use super::super::datamodel::cluster::label_clusters;
+use super::super::datamodel::lattice::Lattice;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use estimators::MemoryOutput;
+    use datamodel::lattice::build_blank_lat;
+    use datamodel::lattice::build_z3_fully_packed_lat;
+
+    #[test]
+    fn test_plaquette_and_fully_packed() {
+        let memory = MemoryOutput::new();
+        let output = EstimatorOutput::Memory(memory.clone());
+        let size = Point {x: 4, y: 4};
+        let mut estimator = ClusterGeometryEstimator::new(&size, &output);
+        // A loop around the plaquette across the corner of the lattice, unwrapped a unit square.
+        let mut plaquette = build_blank_lat(size);
+        plaquette.out_raise_link(&Point {x: 0, y: 0}, &Direction::W);
+        plaquette.out_lower_link(&Point {x: 3, y: 3}, &Direction::E);
+        plaquette.out_raise_link(&Point {x: 3, y: 3}, &Direction::N);
+        plaquette.out_lower_link(&Point {x: 0, y: 0}, &Direction::S);
+        estimator.measure(&mut plaquette);
+        // One cluster wrapping both ways, full of junctions.
+        estimator.measure(&mut build_z3_fully_packed_lat(size));
+        estimator.finalize_bin_and_write(2);
+        estimator.flush();
+
+        let geometry = memory.contents("cluster_geometry_estimator.csv").unwrap();
+        let lines: Vec<&str> = geometry.lines().collect();
+        assert_eq!(lines[0], "size,clusters_per_vertex,radius_of_gyration,perimeter");
+        let values: Vec<f64> = lines[1].split(',').map(|v| v.parse().unwrap()).collect();
+        assert_eq!(values[0], 4.0);
+        assert_eq!(values[1], 1.0 / 32.0);
+        assert!((values[2] - 0.5f64.sqrt()).abs() < 1e-12);
+        // Two blank links leave every corner.
+        assert_eq!(values[3], 8.0);
+        assert_eq!(lines[2], "");
+        assert_eq!(lines.len(), 3);
+
+        // Only the plaquette has no junctions.
+        let loops = memory.contents("loop_length_estimator.csv").unwrap();
+        assert_eq!(loops.lines().collect::<Vec<&str>>(),
+                   vec!["length,loops_per_vertex", "4,0.0625", ""]);
+    }
+}
+
+/// Sums over the clusters of one size.
+#[derive(Debug, Clone, Copy, Default)]
+struct SizeSums {
+    clusters: u64,
+    radius_of_gyration: f64,
+    perimeter: f64,
+}
+
+/// Geometry of the string clusters, to fit the fractal dimension from the cluster size against
+/// the radius of gyration, R_g ~ size^(1 / d_f).
+///
+/// Clusters are labelled with `label_clusters`. The radius of gyration of a cluster is taken
+/// over its unwrapped vertices, so a cluster across the boundary is measured in one piece;
+/// clusters that wrap around the torus have no such layout and are left out. The perimeter is
+/// the number of blank links touching the cluster. Every bin writes a block of lines, one per
+/// cluster size, with the number of such clusters per vertex and their mean radius of gyration
+/// and perimeter.
+///
+/// A configuration without junctions (every vertex empty, straight or a corner) is a set of
+/// closed loops, and for those the distribution of loop lengths goes to
+/// `loop_length_estimator.csv`, per vertex of the junction free configurations of the bin.
+#[derive(Debug)]
+pub struct ClusterGeometryEstimator {
+    number_vertices: u64,
+    size_sums: BTreeMap<u64, SizeSums>,
+    /// Number of loops of every length, summed over the junction free configurations.
+    loop_counts: BTreeMap<u64, u64>,
+    number_loop_configurations: u64,
+    geometry_file_buffer: ResultSink,
+    loop_file_buffer: ResultSink,
+}
+
+impl ClusterGeometryEstimator {
+
+    /// Results go to `cluster_geometry_estimator.csv` and `loop_length_estimator.csv` in
+    /// `output`, one block of lines per bin (no new headers if it appends).
+    pub fn new(size: &Point, output: &EstimatorOutput) -> ClusterGeometryEstimator {
+        println!("Initializing ClusterGeometryEstimator");
+
+        let mut cluster_geometry_estimator = ClusterGeometryEstimator {
+            number_vertices: (size.x * size.y) as u64,
+            size_sums: BTreeMap::new(),
+            loop_counts: BTreeMap::new(),
+            number_loop_configurations: 0,
+            geometry_file_buffer: output.open("cluster_geometry_estimator.csv"),
+            loop_file_buffer: output.open("loop_length_estimator.csv"),
+        };
+
+        if !output.appends() {
+            match cluster_geometry_estimator.geometry_file_buffer
+                .write(b"size,clusters_per_vertex,radius_of_gyration,perimeter\n"){
+                Err(_err) => panic!("Can not write cluster geometry header."),
+                Ok(_) => println!("Wrote cluster geometry header."),
+            };
+            match cluster_geometry_estimator.loop_file_buffer.write(b"length,loops_per_vertex\n"){
+                Err(_err) => panic!("Can not write loop length header."),
+                Ok(_) => println!("Wrote loop length header."),
+            };
+        }
+
+        cluster_geometry_estimator
+    }
+}
+
+/// Radius of gyration of every cluster, from the unwrapped positions of its vertices.
+fn radii_of_gyration(clusters: &ClusterLabels) -> Vec<f64> {
+    // Sums of x, y and x^2 + y^2 over the vertices of every cluster.
+    let mut sums = vec![(0.0, 0.0, 0.0); clusters.number_clusters()];
+    for (label, position) in clusters.labels.iter().zip(clusters.unwrapped.iter()) {
+        if let Some(label) = *label {
+            let (x, y) = (position.x as f64, position.y as f64);
+            sums[label].0 += x;
+            sums[label].1 += y;
+            sums[label].2 += x * x + y * y;
+        }
+    }
+    sums.iter().zip(clusters.sizes.iter()).map(|(&(x, y, squared), &size)| {
+        let size = size as f64;
+        let (mean_x, mean_y) = (x / size, y / size);
+        (squared / size - mean_x * mean_x - mean_y * mean_y).max(0.0).sqrt()
+    }).collect()
+}
+
+/// Number of blank links touching every cluster.
+fn perimeters(lat: &Lattice, clusters: &ClusterLabels) -> Vec<u64> {
+    let mut perimeters = vec![0; clusters.number_clusters()];
+    let index = |p: Point| (p.y * lat.size.x + p.x) as usize;
+    for y in 0..lat.size.y {
+        for x in 0..lat.size.x {
+            let loc = Point {x, y};
+            for direction in [Direction::E, Direction::N].iter() {
+                if lat.link_flux(&loc, direction) != 0 {
+                    continue;
+                }
+                let neighbour = increment_location(BoundPoint {size: lat.size, location: loc}, direction).location;
+                let here = clusters.labels[index(loc)];
+                let there = clusters.labels[index(neighbour)];
+                if let Some(label) = here {
+                    perimeters[label] += 1;
+                }
+                if let Some(label) = there {
+                    if here != there {
+                        perimeters[label] += 1;
+                    }
+                }
+            }
+        }
+    }
+    perimeters
+}
+
+impl Measurable for ClusterGeometryEstimator {
+    fn flush(&mut self) {
+        flush_result_file(&mut self.geometry_file_buffer);
+        flush_result_file(&mut self.loop_file_buffer);
+    }
+
+    fn clear(&mut self) {
+        self.size_sums.clear();
+        self.loop_counts.clear();
+        self.number_loop_configurations = 0;
+    }
+
+    /// Divide the cluster counts by the vertices of `denominator` measurements and the loop
+    /// counts by the vertices of the junction free ones.
+    fn finalize_bin_and_write(&mut self, denominator: u64) {
+        let per_vertex_denominator = (denominator * self.number_vertices) as f64;
+        let mut geometry_string = String::new();
+        for (size, sums) in &self.size_sums {
+            let clusters = sums.clusters as f64;
+            geometry_string.push_str(&format!("{},{},{},{}\n", size, clusters / per_vertex_denominator,
+                                              sums.radius_of_gyration / clusters, sums.perimeter / clusters));
+        }
+        geometry_string.push_str("\n");
+        match self.geometry_file_buffer.write(geometry_string.as_bytes()){
+            Err(err) => panic!("Can not write to cluster geometry estimator buffer {}", err),
+            Ok(_) => (),
+        }
+
+        let loop_denominator = (self.number_loop_configurations * self.number_vertices) as f64;
+        let mut loop_string = String::new();
+        for (length, count) in &self.loop_counts {
+            loop_string.push_str(&format!("{},{}\n", length, *count as f64 / loop_denominator));
+        }
+        loop_string.push_str("\n");
+        match self.loop_file_buffer.write(loop_string.as_bytes()){
+            Err(err) => panic!("Can not write to loop length estimator buffer {}", err),
+            Ok(_) => (),
+        }
+    }
+
+    fn measure(&mut self, lat: &mut Lattice) {
+        let clusters = label_clusters(lat);
+        let radii = radii_of_gyration(&clusters);
+        let perimeters = perimeters(lat, &clusters);
+        for label in 0..clusters.number_clusters() {
+            if clusters.wrapping[label].0 || clusters.wrapping[label].1 {
+                continue;
+            }
+            let sums = self.size_sums.entry(clusters.sizes[label]).or_default();
+            sums.clusters += 1;
+            sums.radius_of_gyration += radii[label];
+            sums.perimeter += perimeters[label] as f64;
+        }
+
+        let mut junction_free = true;
+        for y in 0..lat.size.y {
+            for x in 0..lat.size.x {
+                let vertex = lat.get_vertex_from_point(&BoundPoint {size: lat.size, location: Point {x, y}});
+                match VertexType::of(&vertex) {
+                    VertexType::Empty | VertexType::Straight | VertexType::Corner => (),
+                    _ => junction_free = false,
+                }
+            }
+        }
+        if junction_free {
+            // Every vertex of a loop has two links, so the loop is as long as it has vertices.
+            for &length in clusters.sizes.iter() {
+                *self.loop_counts.entry(length).or_insert(0) += 1;
+            }
+            self.number_loop_configurations += 1;
+        }
+    }
+}
@@ -1,8 +1,8 @@
 use std::io::prelude::*;
-use std::fs::File;
-use std::io::BufWriter;
-use std::path::Path;
 use super::Measurable;
+use super::EstimatorOutput;
+use super::ResultSink;
+use super::flush_result_file;
 use super::super::datamodel::Point;
 use super::super::datamodel::Direction;
 use super::super::datamodel::Vertex;
@@ -11,13 +11,16 @@ use super::super::datamodel::lattice::Lattice;
 use super::super::datamodel::cluster::directions_of_filled_links;
 use super::super::datamodel::cluster::decrement_location;
 use super::super::datamodel::cluster::increment_location;
+use super::super::datamodel::cluster::label_clusters;
 use super::super::datamodel::lattice::build_blank_lat;
+use std::collections::BTreeMap;
 use std::collections::HashMap;
 
 #[cfg(test)]
 mod tests {
     use super::*;
     use datamodel::lattice::build_z3_striped_lat;
+    use estimators::MemoryOutput;
 
     #[test]
     fn test_cluster_size_estimator_constructor() {
@@ -43,6 +46,38 @@ mod tests {
             );
         }
     }
+    #[test]
+    fn test_full_cluster_size_estimator() {
+        let memory = MemoryOutput::new();
+        let output = EstimatorOutput::Memory(memory.clone());
+        let mut striped: Lattice = build_z3_striped_lat(Point { x: 4, y: 4 });
+        // A single loop around the plaquette with corners (0, 0) and (1, 1).
+        let mut plaquette: Lattice = build_blank_lat(Point { x: 4, y: 4 });
+        plaquette.out_raise_link(&Point {x: 0, y: 0}, &Direction::E);
+        plaquette.out_lower_link(&Point {x: 1, y: 1}, &Direction::S);
+        plaquette.out_raise_link(&Point {x: 1, y: 1}, &Direction::W);
+        plaquette.out_lower_link(&Point {x: 0, y: 0}, &Direction::N);
+        let mut estimator = FullClusterSizeEstimator::new(&striped, &output);
+        estimator.measure(&mut striped);
+        estimator.measure(&mut striped);
+        estimator.finalize_bin_and_write(2);
+        estimator.clear();
+        estimator.measure(&mut plaquette);
+        estimator.measure(&mut striped);
+        estimator.finalize_bin_and_write(2);
+        estimator.flush();
+
+        // Four strings of four vertices, each wrapping around in x.
+        let sizes = memory.contents("cluster_size_estimator.csv").unwrap();
+        assert_eq!(sizes.lines().collect::<Vec<&str>>(), vec!["4", "4"]);
+        let distribution = memory.contents("cluster_size_distribution_estimator.csv").unwrap();
+        assert_eq!(distribution.lines().collect::<Vec<&str>>(),
+                   vec!["size,clusters_per_vertex", "4,0.25", "", "4,0.15625", ""]);
+        let percolation = memory.contents("cluster_percolation_estimator.csv").unwrap();
+        assert_eq!(percolation.lines().collect::<Vec<&str>>(),
+                   vec!["largest_cluster,wrapping_horizontal,wrapping_vertical,wrapping_either,wrapping_both",
+                        "4,1,0,1,0", "4,0.5,0,0.5,0"]);
+    }
 }
 
 
@@ -54,35 +89,58 @@ pub struct ClusterSizeEstimatorDisplay {
 }
 
 pub struct FullClusterSizeEstimator {
-    result_file_buffer: BufWriter<File>,
-    cluster_size_estimator: ClusterSizeEstimator,
-    current_num_vertex_per_cluster_avg: f64
+    result_file_buffer: ResultSink,
+    distribution_file_buffer: ResultSink,
+    percolation_file_buffer: ResultSink,
+    current_num_vertex_per_cluster_avg: f64,
+    /// Number of clusters of every size, summed over the measurements of the bin.
+    size_counts: BTreeMap<u64, u64>,
+    /// Sums over the measurements of the largest cluster size and of the wrapping indicators
+    /// (horizontal, vertical, either, both).
+    largest_cluster_sum: f64,
+    wrapping_sums: [f64; 4],
+    number_vertices: u64,
 }
 
 impl FullClusterSizeEstimator {
-    pub fn new(lat: &Lattice) -> FullClusterSizeEstimator {
+    /// Results are written to `output`, after anything already there if it appends: the mean
+    /// cluster size to `cluster_size_estimator.csv`, the size distribution (a block of lines
+    /// per bin) to `cluster_size_distribution_estimator.csv` and the largest cluster and
+    /// wrapping probabilities to `cluster_percolation_estimator.csv`.
+    pub fn new(lat: &Lattice, output: &EstimatorOutput) -> FullClusterSizeEstimator {
         println!("Initializing FullClusterSizeEstimator");
         println!("Opening FullClusterSizeEstimator file");
-        let path = Path::new("cluster_size_estimator.csv");
-        let display = path.display();
-        let file = match File::create(&path) {
-            Err(err) => panic!("could not create {}: {}",
-                               display,
-                               err),
-            Ok(good_file) => good_file,
-        };
-        let result_file_buffer = BufWriter::new(file);
-        FullClusterSizeEstimator {
+        let result_file_buffer = output.open("cluster_size_estimator.csv");
+        let mut full_cluster_size_estimator = FullClusterSizeEstimator {
             result_file_buffer,
-            cluster_size_estimator: ClusterSizeEstimator::new(lat),
-            current_num_vertex_per_cluster_avg: 0.0
+            distribution_file_buffer: output.open("cluster_size_distribution_estimator.csv"),
+            percolation_file_buffer: output.open("cluster_percolation_estimator.csv"),
+            current_num_vertex_per_cluster_avg: 0.0,
+            size_counts: BTreeMap::new(),
+            largest_cluster_sum: 0.0,
+            wrapping_sums: [0.0; 4],
+            number_vertices: (lat.size.x * lat.size.y) as u64,
+        };
+        if !output.appends() {
+            match full_cluster_size_estimator.distribution_file_buffer.write(b"size,clusters_per_vertex\n") {
+                Err(_err) => panic!("Can not write cluster size distribution header."),
+                Ok(_) => println!("Wrote cluster size distribution header."),
+            };
+            let header_string = "largest_cluster,wrapping_horizontal,wrapping_vertical,wrapping_either,\
+                                 wrapping_both\n";
+            match full_cluster_size_estimator.percolation_file_buffer.write(header_string.as_bytes()) {
+                Err(_err) => panic!("Can not write cluster percolation header."),
+                Ok(_) => println!("Wrote cluster percolation header."),
+            };
         }
+        full_cluster_size_estimator
     }
 }
 
 #[derive(Debug, Clone)]
-/// Full cluster size estimator is what is actually used in the runs but the smaller
-/// version has to be cloneable for the GUI
+/// Walks one cluster a step at a time so the GUI can show the walk, which is why it is
+/// cloneable and keeps its own copy of the lattice. The runs use `FullClusterSizeEstimator`,
+/// which labels every cluster at once with `label_clusters`.
 pub struct ClusterSizeEstimator{
     pub cluster_sizes: Vec<i64>,
     pub clustered: HashMap<BoundPoint, u64>,
@@ -99,44 +157,30 @@ pub struct ClusterSizeEstimator{
 }
 
 impl Measurable for FullClusterSizeEstimator {
+    fn flush(&mut self) {
+        flush_result_file(&mut self.result_file_buffer);
+        flush_result_file(&mut self.distribution_file_buffer);
+        flush_result_file(&mut self.percolation_file_buffer);
+    }
     fn measure(&mut self, lat: &mut Lattice) {
-        for i in 0..lat.size.x{
-            for j in 0..lat.size.y{
-                //println!("Looking at vertex x: {}, y: {}", i, j);
-                let cur_point = Point::new(i, j);
-                if self.cluster_size_estimator.clustered.contains_key(&BoundPoint{size: lat.size, location: cur_point}) {
-                    continue
-                } else {
-                    match directions_of_filled_links(&lat.get_vertex_from_point(&BoundPoint { size: lat.size, location: cur_point })) {
-                        Some(_)=> {
-                            self.cluster_size_estimator.init_calculation_location(cur_point, lat);
-                            while self.cluster_size_estimator.is_initialized {
-                                self.cluster_size_estimator.next();
-                            }
-                        }
-                        None => continue
-                    }
-                }
-            }
-        }
-        let mut label_to_vertex_size:HashMap<u64, u64> = HashMap::new();
-        for i in 0..lat.size.x {
-            for j in 0..lat.size.y {
-                let cur_point = Point::new(i, j);
-                match self.cluster_size_estimator.clustered.get(&BoundPoint{size: lat.size, location: cur_point}){
-                    Some(cur_label_num) => {
-                        let addition = label_to_vertex_size.entry(*cur_label_num).or_insert(0);
-                        *addition += 1;
-                    },
-                    None => ()
-                }
-            }
-        }
-        let mut count: i64 = 0;
+        let clusters = label_clusters(lat);
+        let count = clusters.number_clusters();
         let mut avg: f64 = 0.0;
-        for (k, v) in label_to_vertex_size {
-            count += 1;
+        let mut largest: u64 = 0;
+        for &v in clusters.sizes.iter() {
             avg += v as f64;
+            largest = largest.max(v);
+            *self.size_counts.entry(v).or_insert(0) += 1;
+        }
+        self.largest_cluster_sum += largest as f64;
+        let wraps_horizontal = clusters.wrapping.iter().any(|w| w.0);
+        let wraps_vertical = clusters.wrapping.iter().any(|w| w.1);
+        let indicators = [wraps_horizontal, wraps_vertical,
+                          wraps_horizontal || wraps_vertical, wraps_horizontal && wraps_vertical];
+        for (sum, &indicator) in self.wrapping_sums.iter_mut().zip(indicators.iter()) {
+            if indicator {
+                *sum += 1.0;
+            }
         }
         if count == 0 {
             self.current_num_vertex_per_cluster_avg += 0.0;
@@ -157,10 +201,31 @@ impl Measurable for FullClusterSizeEstimator {
             ),
             Ok(_) => (),
         }
+
+        let per_vertex_denominator = (denominator * self.number_vertices) as f64;
+        let mut distribution_string = String::new();
+        for (size, count) in &self.size_counts {
+            distribution_string.push_str(&format!("{},{}\n", size, *count as f64 / per_vertex_denominator));
+        }
+        distribution_string.push_str("\n");
+        match self.distribution_file_buffer.write(distribution_string.as_bytes()) {
+            Err(err) => panic!("Can not write to cluster size distribution file {}", err),
+            Ok(_) => (),
+        }
+
+        let mut values: Vec<String> = vec![(self.largest_cluster_sum / denominator as f64).to_string()];
+        values.extend(self.wrapping_sums.iter().map(|sum| (sum / denominator as f64).to_string()));
+        let percolation_string = format!("{}\n", values.join(","));
+        match self.percolation_file_buffer.write(percolation_string.as_bytes()) {
+            Err(err) => panic!("Can not write to cluster percolation file {}", err),
+            Ok(_) => (),
+        }
     }
     fn clear(&mut self) {
         self.current_num_vertex_per_cluster_avg = 0.0;
-        self.cluster_size_estimator = ClusterSizeEstimator::new(&self.cluster_size_estimator.lat);
+        self.size_counts.clear();
+        self.largest_cluster_sum = 0.0;
+        self.wrapping_sums = [0.0; 4];
     }
 }
 
@@ -199,6 +264,8 @@ impl Iterator for ClusterSizeEstimator {
                     }
                     else {
                         self.is_initialized = false;
+                        // The next cluster needs a number of its own.
+                        self.available_cluster_num += 1;
                         return Some(
                             ClusterSizeEstimatorDisplay {
                                 local_text: "Completed sizing of this cluster!".to_string(),
@@ -0,0 +1,190 @@
+use std::io::prelude::*;
+use super::Measurable;
+use super::EstimatorOutput;
+use super::ResultSink;
+use super::flush_result_file;
+use super::super::datamodel::Direction;
+use super::super::datamodel::Point;
+use super::super::datamodel::lattice::Lattice;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use estimators::MemoryOutput;
+    use datamodel::lattice::build_z3_striped_lat;
+    use datamodel::lattice::build_blank_lat;
+
+    #[test]
+    fn test_striped_lattice() {
+        let memory = MemoryOutput::new();
+        let output = EstimatorOutput::Memory(memory.clone());
+        let size = Point {x: 4, y: 4};
+        let mut estimator = CorrelationFunctionEstimator::new(&size, &output);
+        let mut lat = build_z3_striped_lat(size);
+        estimator.measure(&mut lat);
+        estimator.measure(&mut lat);
+        estimator.finalize_bin_and_write(2);
+        estimator.flush();
+
+        // Every horizontal link points west: the in channel is 1 at every distance and
+        // nothing is connected.
+        let horizontal_in = memory.contents("horizontal_correlation_function_in_estimator.csv").unwrap();
+        let lines: Vec<&str> = horizontal_in.lines().collect();
+        assert_eq!(lines[0], "dx,dy,correlation,connected");
+        assert_eq!(lines.len(), 1 + 16 + 1);
+        assert_eq!(lines[1], "0,0,1,0");
+        assert_eq!(lines[16], "3,3,1,0");
+        let horizontal_out = memory.contents("horizontal_correlation_function_out_estimator.csv").unwrap();
+        assert_eq!(horizontal_out.lines().nth(7).unwrap(), "2,1,0,0");
+    }
+
+    #[test]
+    fn test_two_links() {
+        let memory = MemoryOutput::new();
+        let output = EstimatorOutput::Memory(memory.clone());
+        let size = Point {x: 4, y: 4};
+        let mut estimator = CorrelationFunctionEstimator::new(&size, &output);
+        // Two east pointing links, two apart in x.
+        let mut lat = build_blank_lat(size);
+        lat.out_raise_link(&Point {x: 0, y: 0}, &Direction::E);
+        lat.out_raise_link(&Point {x: 2, y: 0}, &Direction::E);
+        estimator.measure(&mut lat);
+        estimator.finalize_bin_and_write(1);
+        estimator.flush();
+
+        let horizontal_out = memory.contents("horizontal_correlation_function_out_estimator.csv").unwrap();
+        let lines: Vec<&str> = horizontal_out.lines().collect();
+        let density: f64 = 2.0 / 16.0;
+        let connected_zero = density - density * density;
+        assert_eq!(lines[1], format!("0,0,{},{}", density, connected_zero));
+        assert_eq!(lines[2], format!("1,0,0,{}", -density * density));
+        assert_eq!(lines[3], format!("2,0,{},{}", density, connected_zero));
+        let vertical_in = memory.contents("vertical_correlation_function_in_estimator.csv").unwrap();
+        assert_eq!(vertical_in.lines().nth(1).unwrap(), "0,0,0,0");
+    }
+}
+
+/// The channels of the correlation function: the direction of the links and the sign of the
+/// flux along the x or y axis.
+const CHANNELS: [(Direction, &'static str, &'static str, i64); 4] = [
+    (Direction::E, "horizontal", "out", 1),
+    (Direction::E, "horizontal", "in", -1),
+    (Direction::N, "vertical", "out", 1),
+    (Direction::N, "vertical", "in", -1),
+];
+
+/// Translation averaged two point correlation function of the links.
+///
+/// Where `CorrelationOriginEstimator` correlates every link with the links of the origin,
+/// this one averages over every pair of links, so it sees N times the statistics. For each
+/// channel (horizontal or vertical links, flux pointing along the axis, `out`, or against it,
+/// `in`) with n(r) = 1 if the link leaving vertex r in the positive x (y) direction is in the
+/// channel, it writes C(dx, dy) = 1/N sum_r <n(r) n(r + (dx, dy))> and the connected
+/// correlation C(dx, dy) - rho^2, with rho the density of the channel averaged over the same
+/// bin. Only pairs of occupied links are visited, so a measurement costs the square of the
+/// number of links in the channel.
+#[derive(Debug)]
+pub struct CorrelationFunctionEstimator {
+    size: Point,
+    /// Per channel the number of occupied pairs at every separation, index dy * lx + dx.
+    pair_counts: Vec<Vec<u64>>,
+    /// Per channel the number of occupied links, summed over the measurements.
+    link_counts: Vec<u64>,
+    result_file_buffers: Vec<ResultSink>,
+}
+
+impl CorrelationFunctionEstimator {
+
+    /// Results go to `{horizontal,vertical}_correlation_function_{out,in}_estimator.csv` in
+    /// `output`, one block of lines per bin (no new header if it appends).
+    pub fn new(size: &Point, output: &EstimatorOutput) -> CorrelationFunctionEstimator {
+        println!("Initializing CorrelationFunctionEstimator");
+
+        let number_vertices = (size.x * size.y) as usize;
+        let mut correlation_function_estimator = CorrelationFunctionEstimator {
+            size: *size,
+            pair_counts: vec![vec![0; number_vertices]; CHANNELS.len()],
+            link_counts: vec![0; CHANNELS.len()],
+            result_file_buffers: CHANNELS.iter()
+                .map(|&(_, direction, orientation, _)| output.open(
+                    &format!("{}_correlation_function_{}_estimator.csv", direction, orientation)))
+                .collect(),
+        };
+
+        if !output.appends() {
+            for buffer in correlation_function_estimator.result_file_buffers.iter_mut() {
+                match buffer.write(b"dx,dy,correlation,connected\n"){
+                    Err(_err) => panic!("Can not write correlation function header."),
+                    Ok(_) => println!("Wrote correlation function header."),
+                };
+            }
+        }
+
+        correlation_function_estimator
+    }
+}
+
+impl Measurable for CorrelationFunctionEstimator {
+    fn flush(&mut self) {
+        for buffer in self.result_file_buffers.iter_mut() {
+            flush_result_file(buffer);
+        }
+    }
+
+    fn clear(&mut self) {
+        for counts in self.pair_counts.iter_mut() {
+            for count in counts.iter_mut() {
+                *count = 0;
+            }
+        }
+        for count in self.link_counts.iter_mut() {
+            *count = 0;
+        }
+    }
+
+    /// Divide the pair counts by `denominator`, the number of measurements per bin, and the
+    /// number of vertices and write a line per separation.
+    fn finalize_bin_and_write(&mut self, denominator: u64) {
+        let float_denominator = (denominator as f64) * ((self.size.x * self.size.y) as f64);
+        for channel in 0..CHANNELS.len() {
+            let density = self.link_counts[channel] as f64 / float_denominator;
+            let mut out_string = String::new();
+            for dy in 0..self.size.y {
+                for dx in 0..self.size.x {
+                    let correlation = self.pair_counts[channel][(dy * self.size.x + dx) as usize] as f64
+                        / float_denominator;
+                    out_string.push_str(&format!("{},{},{},{}\n", dx, dy, correlation,
+                                                 correlation - density * density));
+                }
+            }
+            out_string.push_str("\n");
+            match self.result_file_buffers[channel].write(out_string.as_bytes()){
+                Err(err) => panic!("Can not write to correlation function estimator buffer {}", err),
+                Ok(_) => (),
+            }
+        }
+    }
+
+    fn measure(&mut self, lat: &mut Lattice) {
+        for (channel, &(ref direction, _, _, sign)) in CHANNELS.iter().enumerate() {
+            let mut occupied: Vec<Point> = Vec::new();
+            for y in 0..self.size.y {
+                for x in 0..self.size.x {
+                    let loc = Point {x, y};
+                    if lat.link_flux(&loc, direction) * sign > 0 {
+                        occupied.push(loc);
+                    }
+                }
+            }
+            self.link_counts[channel] += occupied.len() as u64;
+            let counts = &mut self.pair_counts[channel];
+            for first in &occupied {
+                for second in &occupied {
+                    let dx = (second.x - first.x).rem_euclid(self.size.x);
+                    let dy = (second.y - first.y).rem_euclid(self.size.y);
+                    counts[(dy * self.size.x + dx) as usize] += 1;
+                }
+            }
+        }
+    }
+}
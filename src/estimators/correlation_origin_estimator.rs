@@ -2,29 +2,25 @@ use super::Measurable;
 use super::super::datamodel::VertexLinkCount;
 use super::super::datamodel::lattice::Lattice;
 use super::super::datamodel::Link;
+use super::super::datamodel::Orientation;
 use super::super::datamodel::Vertex;
 use super::write_standard_header;
+use super::EstimatorOutput;
+use super::ResultSink;
+use super::flush_result_file;
 use super::super::datamodel::Point;
-use std::io::BufWriter;
-use std::fs::File;
-use std::path::Path;
 use std::io::prelude::*;
 
 fn simple_file_make_helper_function(direction_string: &str,
-                                    orientation_string: &str) -> BufWriter<File> {
+                                    orientation_string: &str,
+                                    output: &EstimatorOutput) -> ResultSink {
     println!("Opening {orientation} {direction} corrilation estimator file",
                 orientation=orientation_string,
                 direction=direction_string);
     let file_name_string = format!("{orientation}_correlation_origin_{direction}_estimator.csv",
                                     orientation=orientation_string,
                                     direction=direction_string);
-    let path = Path::new(&file_name_string);
-    let display = path.display();
-    let file = match File::create(&path){
-        Err(err) => panic!("could not create {}: {}", display, err),
-        Ok(good_file) => good_file,
-    };
-    return BufWriter::new(file);
+    return output.open(&file_name_string);
 }
 
 /// Measures the string correlation function from the horizontal 
@@ -42,25 +38,26 @@ pub struct CorrelationOriginEstimator {
     cur_binary_vertical_out_correlation: Vec<VertexLinkCount>,
     cur_binary_vertical_in_correlation: Vec<VertexLinkCount>,
 
-    result_file_buffer_horizontal_out: BufWriter<File>,
-    result_file_buffer_horizontal_in: BufWriter<File>,
-    result_file_buffer_vertical_out: BufWriter<File>,
-    result_file_buffer_vertical_in: BufWriter<File>,
+    result_file_buffer_horizontal_out: ResultSink,
+    result_file_buffer_horizontal_in: ResultSink,
+    result_file_buffer_vertical_out: ResultSink,
+    result_file_buffer_vertical_in: ResultSink,
     vector_size: u64,
 }
 
 impl CorrelationOriginEstimator {
 
-    pub fn new(size: &Point) -> CorrelationOriginEstimator {
+    /// The four result files are opened from `output`; nothing new gets a header if it appends.
+    pub fn new(size: &Point, output: &EstimatorOutput) -> CorrelationOriginEstimator {
         println!("Initializing HorizontalCorrelationOriginEstimator");
         let result_file_buffer_horizontal_out = 
-            simple_file_make_helper_function("out", "horizontal");
+            simple_file_make_helper_function("out", "horizontal", output);
         let result_file_buffer_horizontal_in = 
-            simple_file_make_helper_function("in", "horizontal");
+            simple_file_make_helper_function("in", "horizontal", output);
         let result_file_buffer_vertical_out = 
-            simple_file_make_helper_function("out", "vertical");
+            simple_file_make_helper_function("out", "vertical", output);
         let result_file_buffer_vertical_in = 
-            simple_file_make_helper_function("in", "vertical");
+            simple_file_make_helper_function("in", "vertical", output);
 
 
         let mut correlation_origin_estimator = CorrelationOriginEstimator {
@@ -87,14 +84,16 @@ impl CorrelationOriginEstimator {
             correlation_origin_estimator.cur_binary_vertical_out_correlation.push(cur_vertex_link_count);
         }
 
-        write_standard_header(
-            &mut correlation_origin_estimator.result_file_buffer_horizontal_in);
-        write_standard_header(
-            &mut correlation_origin_estimator.result_file_buffer_horizontal_out);
-        write_standard_header(
-            &mut correlation_origin_estimator.result_file_buffer_vertical_in);
-        write_standard_header(
-            &mut correlation_origin_estimator.result_file_buffer_vertical_out);
+        if !output.appends() {
+            write_standard_header(
+                &mut correlation_origin_estimator.result_file_buffer_horizontal_in);
+            write_standard_header(
+                &mut correlation_origin_estimator.result_file_buffer_horizontal_out);
+            write_standard_header(
+                &mut correlation_origin_estimator.result_file_buffer_vertical_in);
+            write_standard_header(
+                &mut correlation_origin_estimator.result_file_buffer_vertical_out);
+        }
 
         println!("Done initilizing origin correlation estimator.");
 
@@ -104,6 +103,13 @@ impl CorrelationOriginEstimator {
 }
 
 impl Measurable for CorrelationOriginEstimator {
+    fn flush(&mut self) {
+        flush_result_file(&mut self.result_file_buffer_horizontal_out);
+        flush_result_file(&mut self.result_file_buffer_horizontal_in);
+        flush_result_file(&mut self.result_file_buffer_vertical_out);
+        flush_result_file(&mut self.result_file_buffer_vertical_in);
+    }
+
     fn clear(&mut self) {
         for i in 0..self.vector_size {
             let cur_index = i as usize;
@@ -194,14 +200,14 @@ impl Measurable for CorrelationOriginEstimator {
 
         let mut measure_horizontal = true;
         let mut measure_vertical = true;
-        match *origin_horizontal_link {
-            Link::Blank => {
+        match origin_horizontal_link.orientation() {
+            Orientation::Blank => {
                 measure_horizontal = false;
             },
             _ => () 
         };
-        match *origin_vertical_link {
-            Link::Blank => {
+        match origin_vertical_link.orientation() {
+            Orientation::Blank => {
                 measure_vertical = false;
             },
             _ => ()
@@ -215,70 +221,70 @@ impl Measurable for CorrelationOriginEstimator {
 
         for (i, cur_vertex) in lat.vertices.iter().enumerate(){
             if measure_horizontal {
-                match *origin_horizontal_link {
-                    Link::In => {
-                        match cur_vertex.e {
-                            Link::In => {
+                match origin_horizontal_link.orientation() {
+                    Orientation::In => {
+                        match cur_vertex.e.orientation() {
+                            Orientation::In => {
                                 self.cur_binary_horizontal_in_correlation[i].e += 1;
                             },
                             _ => (),
                         }
-                        match cur_vertex.w {
-                            Link::Out => {
+                        match cur_vertex.w.orientation() {
+                            Orientation::Out => {
                                 self.cur_binary_horizontal_in_correlation[i].w += 1;
                             },
                             _ => (),
                         }
                     },
-                    Link::Out => {
-                        match cur_vertex.e {
-                            Link::Out => {
+                    Orientation::Out => {
+                        match cur_vertex.e.orientation() {
+                            Orientation::Out => {
                                 self.cur_binary_horizontal_out_correlation[i].e += 1;
                             },
                             _ => (),
                         }
-                        match cur_vertex.w {
-                            Link::In => {
+                        match cur_vertex.w.orientation() {
+                            Orientation::In => {
                                 self.cur_binary_horizontal_out_correlation[i].w += 1;
                             },
                             _ => (),
                         }
                     },
-                    Link::Blank => ()
+                    Orientation::Blank => ()
                 }
             }
 
             if measure_vertical {
-                match *origin_vertical_link {
-                    Link::In => {
-                        match cur_vertex.n {
-                            Link::In => {
+                match origin_vertical_link.orientation() {
+                    Orientation::In => {
+                        match cur_vertex.n.orientation() {
+                            Orientation::In => {
                                 self.cur_binary_vertical_in_correlation[i].n += 1;
                             },
                             _ => (),
                         }
-                        match cur_vertex.s {
-                            Link::Out => {
+                        match cur_vertex.s.orientation() {
+                            Orientation::Out => {
                                 self.cur_binary_vertical_in_correlation[i].s += 1;
                             },
                             _ => (),
                         }
                     },
-                    Link::Out => {
-                        match cur_vertex.n {
-                            Link::Out => {
+                    Orientation::Out => {
+                        match cur_vertex.n.orientation() {
+                            Orientation::Out => {
                                 self.cur_binary_vertical_out_correlation[i].n += 1;
                             },
                             _ => (),
                         }
-                        match cur_vertex.s {
-                            Link::In => {
+                        match cur_vertex.s.orientation() {
+                            Orientation::In => {
                                 self.cur_binary_vertical_out_correlation[i].s += 1;
                             },
                             _ => (),
                         }
                     },
-                    Link::Blank => ()
+                    Orientation::Blank => ()
                 }
             }
         }
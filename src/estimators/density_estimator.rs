@@ -1,13 +1,15 @@
 use super::Measurable;
 use super::write_standard_header;
+use super::EstimatorOutput;
+use super::ResultSink;
+use super::flush_result_file;
 use super::super::datamodel::VertexLinkCount;
-use super::super::datamodel::Link;
+use super::super::datamodel::Orientation;
 use super::super::datamodel::Point;
 use super::super::datamodel::lattice::Lattice;
 use std::io::prelude::*;
 use std::fs::File;
 use std::path::Path;
-use std::io::BufWriter;
 
 /// Measures the string density
 /// 
@@ -18,26 +20,19 @@ pub struct DensityEstimator {
     cur_link_in_count: Vec<VertexLinkCount>,
     cur_link_out_count: Vec<VertexLinkCount>,
     cur_total_count: Vec<VertexLinkCount>,
-    result_file_buffer: BufWriter<File>,
+    result_file_buffer: ResultSink,
     vector_size: u64,
 }
 impl DensityEstimator {
 
     /// static "constructor" method.
-    pub fn new(size: &Point) -> DensityEstimator{
+    ///
+    /// Results go to `density_estimator.csv` in `output` (no new header if it appends).
+    pub fn new(size: &Point, output: &EstimatorOutput) -> DensityEstimator{
         println!("Initializing DensityEstimator");
         
         println!("Opening density estimator file;");
-        let path = Path::new("density_estimator.csv");
-        let display = path.display();
-        let file = match File::create(&path){
-            Err(err) => panic!("could not create {}: {}",
-                            display,
-                            err),
-            Ok(good_file) => good_file,
-        };
-
-        let result_file_buffer = BufWriter::new(file);
+        let result_file_buffer = output.open("density_estimator.csv");
 
         let mut density_estimator = DensityEstimator{
             cur_link_in_count: Vec::new(),
@@ -58,7 +53,9 @@ impl DensityEstimator {
             density_estimator.cur_total_count.push(cur_vertex_link_count);
         }
 
-        write_standard_header(&mut density_estimator.result_file_buffer);
+        if !output.appends() {
+            write_standard_header(&mut density_estimator.result_file_buffer);
+        }
 
         println!("Done initializing density estimator.");
 
@@ -104,6 +101,10 @@ impl DensityEstimator {
 }
 
 impl Measurable for DensityEstimator {
+    fn flush(&mut self) {
+        flush_result_file(&mut self.result_file_buffer);
+    }
+
     fn clear(&mut self) {
         for i in 0..self.vector_size {
             let cur_index = i as usize;
@@ -139,49 +140,49 @@ impl Measurable for DensityEstimator {
         // vectors if you find those directions.
         // loop over real vertices
         for (i, cur_vertex) in lat.vertices.iter().enumerate(){
-            match cur_vertex.n {
-                Link::In  => {
+            match cur_vertex.n.orientation() {
+                Orientation::In  => {
                     self.cur_link_in_count[i].n += 1;
                     self.cur_total_count[i].n += 1;
                 },
-                Link::Out => {
+                Orientation::Out => {
                     self.cur_link_out_count[i].n += 1;
                     self.cur_total_count[i].n += 1;
                 },
-                Link::Blank => (),
+                Orientation::Blank => (),
             }
-            match cur_vertex.e {
-                Link::In  => {
+            match cur_vertex.e.orientation() {
+                Orientation::In  => {
                     self.cur_link_in_count[i].e += 1;
                     self.cur_total_count[i].e += 1;
                 },
-                Link::Out => {
+                Orientation::Out => {
                     self.cur_link_out_count[i].e += 1;
                     self.cur_total_count[i].e += 1;
                 },
-                Link::Blank => (),
+                Orientation::Blank => (),
             }
-            match cur_vertex.s {
-                Link::In  => {
+            match cur_vertex.s.orientation() {
+                Orientation::In  => {
                     self.cur_link_in_count[i].s += 1;
                     self.cur_total_count[i].s += 1;
                 },
-                Link::Out => {
+                Orientation::Out => {
                     self.cur_link_out_count[i].s += 1;
                     self.cur_total_count[i].s += 1;
                 },
-                Link::Blank => (),
+                Orientation::Blank => (),
             }
-            match cur_vertex.w {
-                Link::In  => {
+            match cur_vertex.w.orientation() {
+                Orientation::In  => {
                     self.cur_link_in_count[i].w += 1;
                     self.cur_total_count[i].w += 1;
                 },
-                Link::Out => {
+                Orientation::Out => {
                     self.cur_link_out_count[i].w += 1;
                     self.cur_total_count[i].w += 1;
                 },
-                Link::Blank => (),
+                Orientation::Blank => (),
             }
         }
     }
@@ -4,17 +4,238 @@ pub mod total_link_count_estimator;
 pub mod winding_number_estimator;
 pub mod winding_variance_estimator;
 pub mod cluster_size_estimator;
+pub mod time_series_estimator;
+pub mod correlation_function_estimator;
+pub mod structure_factor_estimator;
+pub mod wilson_loop_estimator;
+pub mod monomer_correlation_estimator;
+pub mod vertex_type_estimator;
+pub mod winding_histogram_estimator;
+pub mod cluster_geometry_estimator;
 
 use super::datamodel::lattice::Lattice;
+use std::io;
 use std::io::BufWriter;
+use std::fs;
 use std::fs::File;
+use std::fs::OpenOptions;
+use std::path::PathBuf;
+use std::fmt;
+use std::sync::{Arc, Mutex};
+use std::collections::BTreeMap;
+use std::collections::BTreeSet;
 use super::datamodel::VertexLinkCount;
 use std::io::prelude::*;
 
-    
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use datamodel::Point;
+    use datamodel::lattice::build_blank_lat;
+    use estimators::total_link_count_estimator::TotalLinkCountEstimator;
+    use estimators::density_estimator::DensityEstimator;
+
+    #[test]
+    fn test_memory_output_keeps_results() {
+        let memory = MemoryOutput::new();
+        let output = EstimatorOutput::Memory(memory.clone());
+        let mut lat = build_blank_lat(Point{x: 4, y: 4});
+        let mut estimator = TotalLinkCountEstimator::new(&output);
+        estimator.measure(&mut lat);
+        estimator.finalize_bin_and_write(1);
+        estimator.flush();
+        assert_eq!(memory.contents("total_link_count_estimator.csv").unwrap(),
+                   "Average Total Link Counts\n0\n");
+        assert!(memory.contents("density_estimator.csv").is_none());
+    }
+
+    #[test]
+    fn test_directory_output_appends() {
+        let dir = env::temp_dir().join("z3stringnet_test_directory_output");
+        let _ = fs::remove_dir_all(&dir);
+        let size = Point{x: 4, y: 4};
+        let read_lines = || {
+            let mut contents = String::new();
+            File::open(dir.join("density_estimator.csv")).unwrap().read_to_string(&mut contents).unwrap();
+            contents.lines().map(String::from).collect::<Vec<String>>()
+        };
+        {
+            let mut estimator = DensityEstimator::new(&size, &EstimatorOutput::directory(&dir, false));
+            estimator.finalize_bin_and_write(1);
+            estimator.flush();
+        }
+        let first_run = read_lines();
+        {
+            let mut estimator = DensityEstimator::new(&size, &EstimatorOutput::directory(&dir, true));
+            estimator.finalize_bin_and_write(1);
+            estimator.flush();
+        }
+        let both_runs = read_lines();
+        // The second bin goes after the first one, without a second header.
+        assert_eq!(both_runs.len(), 2 * first_run.len() - 1);
+        assert_eq!(both_runs.iter().filter(|l| l.starts_with("x,y")).count(), 1);
+        assert_eq!(both_runs[..first_run.len()].to_vec(), first_run);
+    }
+}
+
+/// Where estimators write their results.
+///
+/// Every estimator asks for its result file(s) by name, e.g. `density_estimator.csv`, and gets a
+/// `ResultSink` back. What is behind the sink depends on the variant, so several runs can live
+/// side by side in one process as long as each gets its own output.
+#[derive(Clone)]
+pub enum EstimatorOutput {
+    /// Files with the usual names in `path`, which is created if needed. When `append` is true,
+    /// e.g. when resuming from a checkpoint, new bins go to the end of the existing files and
+    /// estimators do not write their headers again. `opened` has the name of every file opened
+    /// so far, shared between clones.
+    Directory { path: PathBuf, append: bool, opened: Arc<Mutex<BTreeSet<String>>> },
+    /// Everything is kept in memory, see `MemoryOutput`.
+    Memory(MemoryOutput),
+    /// Any writer. The function is called with the file name the estimator asks for.
+    Custom(Arc<Fn(&str) -> Box<Write + Send> + Send + Sync>),
+}
+impl EstimatorOutput {
+    pub fn directory<P: Into<PathBuf>>(path: P, append: bool) -> EstimatorOutput {
+        EstimatorOutput::Directory { path: path.into(), append, opened: Arc::new(Mutex::new(BTreeSet::new())) }
+    }
+
+    /// Names of the files opened so far in a directory, which a checkpoint needs to know how
+    /// far each of them got. Empty for the other outputs.
+    pub fn opened_file_names(&self) -> Vec<String> {
+        match *self {
+            EstimatorOutput::Directory { ref opened, .. } => opened.lock().unwrap().iter().cloned().collect(),
+            _ => Vec::new(),
+        }
+    }
+
+    /// True if the results are added to earlier results, in which case no header is written.
+    pub fn appends(&self) -> bool {
+        match *self {
+            EstimatorOutput::Directory { append, .. } => append,
+            _ => false,
+        }
+    }
+
+    /// Open the result file `file_name`. Panics if a file can not be opened.
+    pub fn open(&self, file_name: &str) -> ResultSink {
+        let writer: Box<Write + Send> = match *self {
+            EstimatorOutput::Directory { ref path, append, ref opened } => {
+                opened.lock().unwrap().insert(String::from(file_name));
+                match fs::create_dir_all(path) {
+                    Err(err) => panic!("could not create output directory {}: {}",
+                                       path.display(),
+                                       err),
+                    Ok(_) => (),
+                }
+                Box::new(open_result_file(&path.join(file_name), append))
+            },
+            EstimatorOutput::Memory(ref memory) => Box::new(memory.open(file_name)),
+            EstimatorOutput::Custom(ref make_writer) => make_writer(file_name),
+        };
+        ResultSink {
+            file_name: String::from(file_name),
+            writer: BufWriter::new(writer),
+        }
+    }
+}
+impl fmt::Debug for EstimatorOutput {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            EstimatorOutput::Directory { ref path, append, .. } =>
+                write!(f, "Directory {{ path: {:?}, append: {} }}", path, append),
+            EstimatorOutput::Memory(ref memory) => write!(f, "Memory({:?})", memory),
+            EstimatorOutput::Custom(_) => write!(f, "Custom"),
+        }
+    }
+}
+
+/// In memory result files, shared between all clones.
+#[derive(Clone, Debug, Default)]
+pub struct MemoryOutput {
+    files: Arc<Mutex<BTreeMap<String, Arc<Mutex<Vec<u8>>>>>>,
+}
+impl MemoryOutput {
+    pub fn new() -> MemoryOutput {
+        MemoryOutput::default()
+    }
+
+    /// Everything written (and flushed) to `file_name` so far, if anything opened it.
+    pub fn contents(&self, file_name: &str) -> Option<String> {
+        let files = self.files.lock().unwrap();
+        files.get(file_name).map(|buffer| String::from_utf8_lossy(&buffer.lock().unwrap()).into_owned())
+    }
+
+    fn open(&self, file_name: &str) -> MemoryFile {
+        let mut files = self.files.lock().unwrap();
+        let buffer = files.entry(String::from(file_name)).or_insert_with(|| Arc::new(Mutex::new(Vec::new())));
+        MemoryFile { buffer: buffer.clone() }
+    }
+}
+
+struct MemoryFile {
+    buffer: Arc<Mutex<Vec<u8>>>,
+}
+impl Write for MemoryFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.buffer.lock().unwrap().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// A buffered result file of an estimator, opened with `EstimatorOutput::open`.
+pub struct ResultSink {
+    file_name: String,
+    writer: BufWriter<Box<Write + Send>>,
+}
+impl Write for ResultSink {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.writer.write(buf)
+    }
+    fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+}
+impl fmt::Debug for ResultSink {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "ResultSink {{ file_name: {:?} }}", self.file_name)
+    }
+}
+
+/// Open a result file on disk.
+///
+/// When `append` is false the file is created (truncating anything already there). When it is
+/// true new bins are added to the end of the existing file.
+fn open_result_file(path: &PathBuf, append: bool) -> File {
+    let display = path.display();
+    let file = if append {
+        OpenOptions::new().create(true).append(true).open(&path)
+    } else {
+        File::create(&path)
+    };
+    match file {
+        Err(err) => panic!("could not open {}: {}",
+                           display,
+                           err),
+        Ok(good_file) => good_file,
+    }
+}
+
+/// Flush a result sink so everything finalized so far is written out.
+pub fn flush_result_file(writer: &mut ResultSink) {
+    match writer.flush() {
+        Err(err) => panic!("Can not flush estimator buffer {}: {}", writer.file_name, err),
+        Ok(_) => (),
+    }
+}
+
 /// Write what should be the header for all 
 /// estimator files.
-pub fn write_standard_header(writer: &mut BufWriter<File>) {
+pub fn write_standard_header(writer: &mut ResultSink) {
     let mut out_string = String::new();
     out_string.push_str("x,y,N,E,S,W\n");
     match writer.write(out_string.as_bytes()){
@@ -33,9 +254,12 @@ pub trait Measurable {
     /// Clear out counts before taking a series of measurements to 
     /// be bined.
     fn clear(&mut self);
+    /// Push everything written so far out to the result file(s). Called before a checkpoint
+    /// is written so the files on disk agree with the checkpoint.
+    fn flush(&mut self);
 
     fn line_out_string_from_vertex_link_count(vertex: &VertexLinkCount,
-                                                  denominator: &f64) -> String {
+                                                  denominator: &f64) -> String where Self: Sized {
                                                   
         let formatted_line = format!("{},{},{},{},{},{}\n",
                 vertex.xy.x,
@@ -0,0 +1,130 @@
+use std::io::prelude::*;
+use super::Measurable;
+use super::EstimatorOutput;
+use super::ResultSink;
+use super::flush_result_file;
+use super::super::datamodel::Point;
+use super::super::datamodel::lattice::Lattice;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use estimators::MemoryOutput;
+    use datamodel::Direction;
+    use datamodel::lattice::build_blank_lat;
+
+    #[test]
+    fn test_separation_histogram() {
+        let memory = MemoryOutput::new();
+        let output = EstimatorOutput::Memory(memory.clone());
+        let size = Point {x: 4, y: 4};
+        let mut estimator = MonomerCorrelationEstimator::new(&size, &output);
+        let mut lat = build_blank_lat(size);
+        lat.insert_monomer_pair(&Point {x: 0, y: 0}, &Direction::E).unwrap();
+        estimator.measure(&mut lat);
+        // The -1 charge one step west of the +1 charge, across the boundary.
+        lat.move_monomer(1, &Direction::W);
+        lat.move_monomer(1, &Direction::W);
+        estimator.measure(&mut lat);
+        estimator.finalize_bin_and_write(2);
+        estimator.flush();
+
+        let contents = memory.contents("monomer_correlation_estimator.csv").unwrap();
+        let lines: Vec<&str> = contents.lines().collect();
+        assert_eq!(lines[0], "dx,dy,distance,probability");
+        assert_eq!(lines.len(), 1 + 16 + 1);
+        assert_eq!(lines[1], "0,0,0,0");
+        assert_eq!(lines[2], "1,0,1,0.5");
+        assert_eq!(lines[4], "3,0,1,0.5");
+        assert_eq!(lines[16], "3,3,1.4142135623730951,0");
+    }
+}
+
+/// Monomer correlation function G(dx, dy): the probability that the -1 monomer sits at
+/// (dx, dy) from the +1 monomer, both taken modulo the lattice size.
+///
+/// A string net with a pair of test charges has a string running between them, so G falls off
+/// exponentially with the distance if the strings are confined (they cost a tension per
+/// length) and goes to a constant if they are deconfined. Every separation gets a line with
+/// the shortest distance it stands for on the torus, so the lines can be grouped by r.
+#[derive(Debug)]
+pub struct MonomerCorrelationEstimator {
+    size: Point,
+    /// Number of (+1, -1) pairs seen at every separation, index dy * lx + dx.
+    pair_counts: Vec<u64>,
+    /// Number of (+1, -1) pairs seen in total.
+    number_pairs: u64,
+    result_file_buffer: ResultSink,
+}
+
+impl MonomerCorrelationEstimator {
+
+    /// Results go to `monomer_correlation_estimator.csv` in `output`, one block of lines per
+    /// bin (no new header if it appends).
+    pub fn new(size: &Point, output: &EstimatorOutput) -> MonomerCorrelationEstimator {
+        println!("Initializing MonomerCorrelationEstimator");
+
+        let mut monomer_correlation_estimator = MonomerCorrelationEstimator {
+            size: *size,
+            pair_counts: vec![0; (size.x * size.y) as usize],
+            number_pairs: 0,
+            result_file_buffer: output.open("monomer_correlation_estimator.csv"),
+        };
+
+        if !output.appends() {
+            match monomer_correlation_estimator.result_file_buffer.write(b"dx,dy,distance,probability\n"){
+                Err(_err) => panic!("Can not write monomer correlation header."),
+                Ok(_) => println!("Wrote monomer correlation header."),
+            };
+        }
+
+        monomer_correlation_estimator
+    }
+}
+
+impl Measurable for MonomerCorrelationEstimator {
+    fn flush(&mut self) {
+        flush_result_file(&mut self.result_file_buffer);
+    }
+
+    fn clear(&mut self) {
+        for count in self.pair_counts.iter_mut() {
+            *count = 0;
+        }
+        self.number_pairs = 0;
+    }
+
+    /// Normalize by the number of pairs seen in the bin, which is `denominator` times the
+    /// pairs per configuration, and write a line per separation.
+    fn finalize_bin_and_write(&mut self, _denominator: u64) {
+        let mut out_string = String::new();
+        for dy in 0..self.size.y {
+            for dx in 0..self.size.x {
+                let x = dx.min(self.size.x - dx) as f64;
+                let y = dy.min(self.size.y - dy) as f64;
+                let probability = if self.number_pairs == 0 {
+                    0.0
+                } else {
+                    self.pair_counts[(dy * self.size.x + dx) as usize] as f64 / self.number_pairs as f64
+                };
+                out_string.push_str(&format!("{},{},{},{}\n", dx, dy, (x * x + y * y).sqrt(), probability));
+            }
+        }
+        out_string.push_str("\n");
+        match self.result_file_buffer.write(out_string.as_bytes()){
+            Err(err) => panic!("Can not write to monomer correlation estimator buffer {}", err),
+            Ok(_) => (),
+        }
+    }
+
+    fn measure(&mut self, lat: &mut Lattice) {
+        for source in lat.monomers.iter().filter(|m| m.charge > 0) {
+            for sink in lat.monomers.iter().filter(|m| m.charge < 0) {
+                let dx = (sink.location.x - source.location.x).rem_euclid(self.size.x);
+                let dy = (sink.location.y - source.location.y).rem_euclid(self.size.y);
+                self.pair_counts[(dy * self.size.x + dx) as usize] += 1;
+                self.number_pairs += 1;
+            }
+        }
+    }
+}
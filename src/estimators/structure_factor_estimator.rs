@@ -0,0 +1,214 @@
+use std::f64::consts::PI;
+use std::io::prelude::*;
+use num_complex::Complex64;
+use super::Measurable;
+use super::EstimatorOutput;
+use super::ResultSink;
+use super::flush_result_file;
+use super::super::datamodel::Direction;
+use super::super::datamodel::Point;
+use super::super::datamodel::lattice::Lattice;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use estimators::MemoryOutput;
+    use datamodel::lattice::build_blank_lat;
+    use datamodel::lattice::build_z3_striped_lat;
+
+    fn measure_once(lat: &mut Lattice) -> Vec<Vec<f64>> {
+        let memory = MemoryOutput::new();
+        let output = EstimatorOutput::Memory(memory.clone());
+        let mut estimator = StructureFactorEstimator::new(&lat.size, lat.modulus, &output);
+        estimator.measure(lat);
+        estimator.finalize_bin_and_write(1);
+        estimator.flush();
+        let contents = memory.contents("structure_factor_estimator.csv").unwrap();
+        let mut lines = contents.lines();
+        assert_eq!(lines.next().unwrap(),
+                   "qx_index,qy_index,qx,qy,horizontal_orientation,vertical_orientation,\
+                    horizontal_occupancy,vertical_occupancy");
+        lines.filter(|line| !line.is_empty())
+            .map(|line| line.split(',').map(|v| v.parse().unwrap()).collect())
+            .collect()
+    }
+
+    #[test]
+    fn test_uniform_lattice_only_has_q_zero() {
+        let mut lat = build_z3_striped_lat(Point {x: 4, y: 4});
+        let rows = measure_once(&mut lat);
+        assert_eq!(rows.len(), 16);
+        for row in &rows {
+            let q_zero = row[0] == 0.0 && row[1] == 0.0;
+            // Horizontal links are all omega^2, vertical all blank (omega^0); only the
+            // horizontal links are occupied.
+            let expected = if q_zero { [16.0, 16.0, 16.0, 0.0] } else { [0.0; 4] };
+            for field in 0..4 {
+                assert!((row[4 + field] - expected[field]).abs() < 1e-9, "{:?}", row);
+            }
+        }
+    }
+
+    #[test]
+    fn test_single_link_is_flat() {
+        let mut lat = build_blank_lat(Point {x: 4, y: 6});
+        lat.out_raise_link(&Point {x: 2, y: 2}, &Direction::N);
+        let rows = measure_once(&mut lat);
+        assert_eq!(rows.len(), 24);
+        assert_eq!(&rows[5][..4], &[1.0, 1.0, PI / 2.0, PI / 3.0]);
+        for row in &rows {
+            assert!((row[7] - 1.0 / 24.0).abs() < 1e-12);
+            assert_eq!(row[6], 0.0);
+        }
+    }
+}
+
+/// Twiddle factors exp(-2 pi i m x / length) for m and x from 0 to length - 1, index m * length + x.
+fn twiddle_factors(length: i64) -> Vec<Complex64> {
+    let mut factors = Vec::with_capacity((length * length) as usize);
+    for m in 0..length {
+        for x in 0..length {
+            factors.push(Complex64::from_polar(1.0, -2.0 * PI * ((m * x) % length) as f64 / length as f64));
+        }
+    }
+    factors
+}
+
+/// Static structure factor of the link fields.
+///
+/// Every link, taken along the positive x (horizontal) or y (vertical) axis, is mapped to
+/// omega^k with omega = exp(2 pi i / N) and k its ZN value (the orientation field) and to 1 if
+/// it is filled, 0 if it is blank (the occupancy field). For each of the four fields f
+/// S(q) = |sum_r f(r) exp(-i q r)|^2 / (Lx Ly) is averaged over the bin, for all
+/// q = (2 pi m / Lx, 2 pi n / Ly) with r the vertex the link leaves. Mean values show up at
+/// q = 0 only, Bragg peaks elsewhere mark order.
+#[derive(Debug)]
+pub struct StructureFactorEstimator {
+    size: Point,
+    modulus: u8,
+    twiddles_x: Vec<Complex64>,
+    twiddles_y: Vec<Complex64>,
+    /// Per field |f(q)|^2 summed over the measurements, index n * lx + m.
+    sums: Vec<Vec<f64>>,
+    result_file_buffer: ResultSink,
+}
+
+impl StructureFactorEstimator {
+
+    /// Results go to `structure_factor_estimator.csv` in `output`, one block of lines per bin
+    /// (no new header if it appends).
+    pub fn new(size: &Point, modulus: u8, output: &EstimatorOutput) -> StructureFactorEstimator {
+        println!("Initializing StructureFactorEstimator");
+
+        let mut structure_factor_estimator = StructureFactorEstimator {
+            size: *size,
+            modulus,
+            twiddles_x: twiddle_factors(size.x),
+            twiddles_y: twiddle_factors(size.y),
+            sums: vec![vec![0.0; (size.x * size.y) as usize]; 4],
+            result_file_buffer: output.open("structure_factor_estimator.csv"),
+        };
+
+        if !output.appends() {
+            let header_string = "qx_index,qy_index,qx,qy,horizontal_orientation,vertical_orientation,\
+                                 horizontal_occupancy,vertical_occupancy\n";
+            match structure_factor_estimator.result_file_buffer.write(header_string.as_bytes()){
+                Err(_err) => panic!("Can not write structure factor header."),
+                Ok(_) => println!("Wrote structure factor header."),
+            };
+        }
+
+        structure_factor_estimator
+    }
+
+    /// Two dimensional DFT of `field` (index y * lx + x), one axis after the other.
+    fn transform(&self, field: &[Complex64]) -> Vec<Complex64> {
+        let (lx, ly) = (self.size.x as usize, self.size.y as usize);
+        let mut along_x = vec![Complex64::new(0.0, 0.0); lx * ly];
+        for y in 0..ly {
+            for m in 0..lx {
+                along_x[y * lx + m] = (0..lx)
+                    .map(|x| field[y * lx + x] * self.twiddles_x[m * lx + x])
+                    .sum();
+            }
+        }
+        let mut transformed = vec![Complex64::new(0.0, 0.0); lx * ly];
+        for n in 0..ly {
+            for m in 0..lx {
+                transformed[n * lx + m] = (0..ly)
+                    .map(|y| along_x[y * lx + m] * self.twiddles_y[n * ly + y])
+                    .sum();
+            }
+        }
+        transformed
+    }
+}
+
+impl Measurable for StructureFactorEstimator {
+    fn flush(&mut self) {
+        flush_result_file(&mut self.result_file_buffer);
+    }
+
+    fn clear(&mut self) {
+        for sums in self.sums.iter_mut() {
+            for sum in sums.iter_mut() {
+                *sum = 0.0;
+            }
+        }
+    }
+
+    /// Divide by `denominator`, the number of measurements per bin, and write a line per q.
+    fn finalize_bin_and_write(&mut self, denominator: u64) {
+        let mut out_string = String::new();
+        for n in 0..self.size.y {
+            for m in 0..self.size.x {
+                let index = (n * self.size.x + m) as usize;
+                out_string.push_str(&format!("{},{},{},{}", m, n,
+                                             2.0 * PI * m as f64 / self.size.x as f64,
+                                             2.0 * PI * n as f64 / self.size.y as f64));
+                for sums in &self.sums {
+                    out_string.push_str(&format!(",{}", sums[index] / denominator as f64));
+                }
+                out_string.push_str("\n");
+            }
+        }
+        out_string.push_str("\n");
+        match self.result_file_buffer.write(out_string.as_bytes()){
+            Err(err) => panic!("Can not write to structure factor estimator buffer {}", err),
+            Ok(_) => (),
+        }
+    }
+
+    fn measure(&mut self, lat: &mut Lattice) {
+        let number_vertices = (self.size.x * self.size.y) as f64;
+        let omega = Complex64::from_polar(1.0, 2.0 * PI / self.modulus as f64);
+        let mut fields: Vec<Vec<Complex64>> = Vec::with_capacity(4);
+        for direction in &[Direction::E, Direction::N] {
+            let mut orientation: Vec<Complex64> = Vec::new();
+            for y in 0..self.size.y {
+                for x in 0..self.size.x {
+                    let k = lat.link_flux(&Point {x, y}, direction).rem_euclid(self.modulus as i64);
+                    orientation.push(omega.powi(k as i32));
+                }
+            }
+            fields.push(orientation);
+        }
+        for direction in &[Direction::E, Direction::N] {
+            let mut occupancy: Vec<Complex64> = Vec::new();
+            for y in 0..self.size.y {
+                for x in 0..self.size.x {
+                    let filled = lat.link_flux(&Point {x, y}, direction) != 0;
+                    occupancy.push(Complex64::new(if filled { 1.0 } else { 0.0 }, 0.0));
+                }
+            }
+            fields.push(occupancy);
+        }
+
+        for (index, field) in fields.iter().enumerate() {
+            let transformed = self.transform(field);
+            for (sum, value) in self.sums[index].iter_mut().zip(&transformed) {
+                *sum += value.norm_sqr() / number_vertices;
+            }
+        }
+    }
+}
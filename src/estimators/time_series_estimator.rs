@@ -0,0 +1,61 @@
+use std::io::prelude::*;
+use super::Measurable;
+use super::EstimatorOutput;
+use super::ResultSink;
+use super::flush_result_file;
+use super::winding_variance_estimator::WindingCuts;
+use super::super::datamodel::lattice::Lattice;
+
+/// Writes the link count and winding numbers (averaged over every cut, see `WindingCuts`) of
+/// every measured configuration, one line each, instead of bin averages. This is the input for
+/// `analysis`, e.g. to find the integrated autocorrelation time of the link count.
+#[derive(Debug)]
+pub struct TimeSeriesEstimator {
+    result_file_buffer: ResultSink,
+}
+
+impl TimeSeriesEstimator {
+
+    /// Results go to `time_series_estimator.csv` in `output` (no new header if it appends).
+    pub fn new(output: &EstimatorOutput) -> TimeSeriesEstimator {
+        println!("Initializing TimeSeriesEstimator");
+
+        let mut time_series_estimator = TimeSeriesEstimator {
+            result_file_buffer: output.open("time_series_estimator.csv"),
+        };
+
+        if !output.appends() {
+            let mut header_string = String::new();
+            header_string.push_str("link_count,winding_horizontal,winding_vertical\n");
+            match time_series_estimator.result_file_buffer.write(header_string.as_bytes()){
+                Err(_err) => panic!("Can not write time series header."),
+                Ok(_) => println!("Wrote time series header."),
+            };
+        }
+
+        time_series_estimator
+    }
+}
+
+impl Measurable for TimeSeriesEstimator {
+    fn flush(&mut self) {
+        flush_result_file(&mut self.result_file_buffer);
+    }
+
+    /// Nothing is kept between measurements.
+    fn clear(&mut self) {}
+
+    /// Every measurement was already written.
+    fn finalize_bin_and_write(&mut self, _denominator: u64) {}
+
+    fn measure(&mut self, lat: &mut Lattice) {
+        let cuts = WindingCuts::of(lat);
+        let out_string = format!("{},{},{}\n", lat.number_filled_links, cuts.average_horizontal(),
+                                 cuts.average_vertical());
+        match self.result_file_buffer.write(out_string.as_bytes()){
+            Err(err) => panic!("Can not write to time series estimator buffer {}",
+                err),
+            Ok(_) => (),
+        }
+    }
+}
@@ -1,46 +1,38 @@
-use std::fs::File;
-use std::path::Path;
-use std::error::Error;
-use std::io::BufWriter;
 use std::io::prelude::*;
 use super::Measurable;
-use super::super::datamodel::Link;
+use super::EstimatorOutput;
+use super::ResultSink;
+use super::flush_result_file;
 use super::super::datamodel::lattice::Lattice;
 
 #[derive(Debug)]
 pub struct TotalLinkCountEstimator {
     count: u64,
-    result_file_buffer: BufWriter<File>,
+    result_file_buffer: ResultSink,
 }
 
 impl TotalLinkCountEstimator {
 
-    pub fn new() -> TotalLinkCountEstimator {
+    /// Results go to `total_link_count_estimator.csv` in `output` (no new header if it appends).
+    pub fn new(output: &EstimatorOutput) -> TotalLinkCountEstimator {
         println!("Initializing TotalLinkCountEstimator");
 
         println!("Opening density estimator file");
-        let path = Path::new("total_link_count_estimator.csv");
-        let display = path.display();
-        let file = match File::create(&path) {
-            Err(err) => panic!("could not create {}: {}",
-                display,
-                err),
-            Ok(good_file) => good_file,
-        };
-
-        let result_file_buffer = BufWriter::new(file);
+        let result_file_buffer = output.open("total_link_count_estimator.csv");
 
         let mut total_link_count_estimator = TotalLinkCountEstimator{
             count: 0,
             result_file_buffer,
         };
 
-        let mut header_string = String::new();
-        header_string.push_str("Average Total Link Counts\n");
-        match total_link_count_estimator.result_file_buffer.write(header_string.as_bytes()){
-            Err(_err) => panic!("Can not write total link count header."),
-            Ok(_) => println!("Wrote total link count header."),
-        };
+        if !output.appends() {
+            let mut header_string = String::new();
+            header_string.push_str("Average Total Link Counts\n");
+            match total_link_count_estimator.result_file_buffer.write(header_string.as_bytes()){
+                Err(_err) => panic!("Can not write total link count header."),
+                Ok(_) => println!("Wrote total link count header."),
+            };
+        }
 
         println!("Done initializing total link count estimator.");
 
@@ -49,6 +41,10 @@ impl TotalLinkCountEstimator {
 }
 
 impl Measurable for TotalLinkCountEstimator {
+    fn flush(&mut self) {
+        flush_result_file(&mut self.result_file_buffer);
+    }
+
     fn clear(&mut self){
         self.count = 0;
     }
@@ -67,41 +63,17 @@ impl Measurable for TotalLinkCountEstimator {
 
     fn measure(&mut self, lat: &mut Lattice){
         for (_i, cur_vertex) in lat.vertices.iter().enumerate(){
-            match cur_vertex.n {
-                Link::In => {
-                    self.count += 1;
-                }
-                Link::Out => {
-                    self.count += 1;
-                }
-                Link::Blank => ()
+            if !cur_vertex.n.is_blank() {
+                self.count += 1;
             }
-            match cur_vertex.e {
-                Link::In => {
-                    self.count += 1;
-                }
-                Link::Out => {
-                    self.count += 1;
-                }
-                Link::Blank => ()
+            if !cur_vertex.e.is_blank() {
+                self.count += 1;
             }
-            match cur_vertex.s {
-                Link::In => {
-                    self.count += 1;
-                }
-                Link::Out => {
-                    self.count += 1;
-                }
-                Link::Blank => ()
+            if !cur_vertex.s.is_blank() {
+                self.count += 1;
             }
-            match cur_vertex.w {
-                Link::In => {
-                    self.count += 1;
-                }
-                Link::Out => {
-                    self.count += 1;
-                }
-                Link::Blank => ()
+            if !cur_vertex.w.is_blank() {
+                self.count += 1;
             }
         }
     }
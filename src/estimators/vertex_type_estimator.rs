@@ -0,0 +1,202 @@
+use std::io::prelude::*;
+use super::Measurable;
+use super::EstimatorOutput;
+use super::ResultSink;
+use super::flush_result_file;
+use super::super::datamodel::BoundPoint;
+use super::super::datamodel::Orientation;
+use super::super::datamodel::Point;
+use super::super::datamodel::Vertex;
+use super::super::datamodel::lattice::Lattice;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use estimators::MemoryOutput;
+    use datamodel::Direction;
+    use datamodel::lattice::build_blank_lat;
+    use datamodel::lattice::build_z3_striped_lat;
+    use datamodel::lattice::build_z3_fully_packed_lat;
+
+    fn measure_once(lat: &mut Lattice) -> Vec<f64> {
+        let memory = MemoryOutput::new();
+        let output = EstimatorOutput::Memory(memory.clone());
+        let mut estimator = VertexTypeEstimator::new(&output);
+        estimator.measure(lat);
+        estimator.finalize_bin_and_write(1);
+        estimator.flush();
+        let contents = memory.contents("vertex_type_estimator.csv").unwrap();
+        let lines: Vec<&str> = contents.lines().collect();
+        assert_eq!(lines[0], "vertex_empty,vertex_end,vertex_straight,vertex_corner,\
+                              vertex_junction_out,vertex_junction_in,vertex_junction_mixed,vertex_four_way");
+        assert_eq!(lines.len(), 2);
+        lines[1].split(',').map(|v| v.parse().unwrap()).collect()
+    }
+
+    #[test]
+    fn test_uniform_lattices() {
+        assert_eq!(measure_once(&mut build_blank_lat(Point {x: 4, y: 4})),
+                   vec![1.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0]);
+        assert_eq!(measure_once(&mut build_z3_striped_lat(Point {x: 4, y: 4})),
+                   vec![0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 0.0, 0.0]);
+        // Real vertices have three outward links, the fake ones three inward.
+        let fully_packed = measure_once(&mut build_z3_fully_packed_lat(Point {x: 4, y: 4}));
+        assert_eq!(fully_packed.iter().sum::<f64>(), 1.0);
+        assert_eq!(fully_packed[VertexType::JunctionOut as usize], fully_packed[VertexType::JunctionIn as usize]);
+    }
+
+    #[test]
+    fn test_plaquette_loop() {
+        let mut lat = build_blank_lat(Point {x: 4, y: 4});
+        lat.out_raise_link(&Point {x: 0, y: 0}, &Direction::E);
+        lat.out_lower_link(&Point {x: 1, y: 1}, &Direction::S);
+        lat.out_raise_link(&Point {x: 1, y: 1}, &Direction::W);
+        lat.out_lower_link(&Point {x: 0, y: 0}, &Direction::N);
+        let fractions = measure_once(&mut lat);
+        assert_eq!(fractions[VertexType::Corner as usize], 4.0 / 16.0);
+        assert_eq!(fractions[VertexType::Empty as usize], 12.0 / 16.0);
+        let vertex = lat.get_vertex_from_point(&BoundPoint {size: lat.size, location: Point {x: 1, y: 0}});
+        assert_eq!(VertexType::of(&vertex), VertexType::Corner);
+    }
+}
+
+/// The arrangements of filled links around a vertex, named after their shape.
+///
+/// A closed Z3 string net only has empty vertices, straight strings, corners, three way
+/// junctions with all three links pointing out or all in, and four way vertices (two in, two
+/// out). Vertices with a single link are string ends, which only exist at monomers, and mixed
+/// junctions only for other moduli.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum VertexType {
+    Empty,
+    End,
+    Straight,
+    Corner,
+    JunctionOut,
+    JunctionIn,
+    JunctionMixed,
+    FourWay,
+}
+
+/// Every vertex type, in the order of the columns.
+pub const VERTEX_TYPES: [VertexType; 8] = [
+    VertexType::Empty,
+    VertexType::End,
+    VertexType::Straight,
+    VertexType::Corner,
+    VertexType::JunctionOut,
+    VertexType::JunctionIn,
+    VertexType::JunctionMixed,
+    VertexType::FourWay,
+];
+
+impl VertexType {
+    pub fn name(&self) -> &'static str {
+        match *self {
+            VertexType::Empty => "empty",
+            VertexType::End => "end",
+            VertexType::Straight => "straight",
+            VertexType::Corner => "corner",
+            VertexType::JunctionOut => "junction_out",
+            VertexType::JunctionIn => "junction_in",
+            VertexType::JunctionMixed => "junction_mixed",
+            VertexType::FourWay => "four_way",
+        }
+    }
+
+    /// Type of `vertex`, with its links looked at from the vertex (as `get_vertex_from_point`
+    /// returns them).
+    pub fn of(vertex: &Vertex) -> VertexType {
+        let orientations = [vertex.n.orientation(), vertex.e.orientation(),
+                            vertex.s.orientation(), vertex.w.orientation()];
+        let filled: Vec<bool> = orientations.iter().map(|o| *o != Orientation::Blank).collect();
+        let number_out = orientations.iter().filter(|o| **o == Orientation::Out).count();
+        let number_in = orientations.iter().filter(|o| **o == Orientation::In).count();
+        match number_out + number_in {
+            0 => VertexType::Empty,
+            1 => VertexType::End,
+            // N and S (index 0 and 2) or E and W (1 and 3) are opposite.
+            2 => if filled[0] == filled[2] { VertexType::Straight } else { VertexType::Corner },
+            3 => if number_in == 0 {
+                VertexType::JunctionOut
+            } else if number_out == 0 {
+                VertexType::JunctionIn
+            } else {
+                VertexType::JunctionMixed
+            },
+            _ => VertexType::FourWay,
+        }
+    }
+}
+
+/// Fractions of the vertices, of both sublattices, of every `VertexType`.
+///
+/// Many junctions mean a branching string net, mostly straight strings and corners a net of
+/// loops. One line per bin with a column `vertex_<type>` per type.
+#[derive(Debug)]
+pub struct VertexTypeEstimator {
+    /// Vertices of every type summed over the measurements, in the order of `VERTEX_TYPES`.
+    counts: Vec<u64>,
+    result_file_buffer: ResultSink,
+}
+
+impl VertexTypeEstimator {
+
+    /// Results go to `vertex_type_estimator.csv` in `output` (no new header if it appends).
+    pub fn new(output: &EstimatorOutput) -> VertexTypeEstimator {
+        println!("Initializing VertexTypeEstimator");
+
+        let mut vertex_type_estimator = VertexTypeEstimator {
+            counts: vec![0; VERTEX_TYPES.len()],
+            result_file_buffer: output.open("vertex_type_estimator.csv"),
+        };
+
+        if !output.appends() {
+            let names: Vec<String> = VERTEX_TYPES.iter()
+                .map(|vertex_type| format!("vertex_{}", vertex_type.name()))
+                .collect();
+            let header_string = format!("{}\n", names.join(","));
+            match vertex_type_estimator.result_file_buffer.write(header_string.as_bytes()){
+                Err(_err) => panic!("Can not write vertex type header."),
+                Ok(_) => println!("Wrote vertex type header."),
+            };
+        }
+
+        vertex_type_estimator
+    }
+}
+
+impl Measurable for VertexTypeEstimator {
+    fn flush(&mut self) {
+        flush_result_file(&mut self.result_file_buffer);
+    }
+
+    fn clear(&mut self) {
+        for count in self.counts.iter_mut() {
+            *count = 0;
+        }
+    }
+
+    /// Divide by the number of vertices counted, `denominator` measurements of the whole
+    /// lattice.
+    fn finalize_bin_and_write(&mut self, _denominator: u64) {
+        let total: u64 = self.counts.iter().sum();
+        let values: Vec<String> = self.counts.iter()
+            .map(|&count| (count as f64 / total as f64).to_string())
+            .collect();
+        let out_string = format!("{}\n", values.join(","));
+        match self.result_file_buffer.write(out_string.as_bytes()){
+            Err(err) => panic!("Can not write to vertex type estimator buffer {}", err),
+            Ok(_) => (),
+        }
+    }
+
+    fn measure(&mut self, lat: &mut Lattice) {
+        for y in 0..lat.size.y {
+            for x in 0..lat.size.x {
+                let vertex = lat.get_vertex_from_point(&BoundPoint {size: lat.size, location: Point {x, y}});
+                self.counts[VertexType::of(&vertex) as usize] += 1;
+            }
+        }
+    }
+}
@@ -0,0 +1,186 @@
+use std::f64::consts::PI;
+use std::io::prelude::*;
+use super::Measurable;
+use super::EstimatorOutput;
+use super::ResultSink;
+use super::flush_result_file;
+use super::super::datamodel::Direction;
+use super::super::datamodel::Point;
+use super::super::datamodel::lattice::Lattice;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use estimators::MemoryOutput;
+    use datamodel::lattice::build_blank_lat;
+    use datamodel::lattice::build_z3_striped_lat;
+
+    fn measure_once(lat: &mut Lattice) -> (Vec<String>, Vec<f64>) {
+        let memory = MemoryOutput::new();
+        let output = EstimatorOutput::Memory(memory.clone());
+        let mut estimator = WilsonLoopEstimator::new(&lat.size, lat.modulus, &output);
+        estimator.measure(lat);
+        estimator.finalize_bin_and_write(1);
+        estimator.flush();
+        let contents = memory.contents("wilson_loop_estimator.csv").unwrap();
+        let lines: Vec<&str> = contents.lines().collect();
+        assert_eq!(lines.len(), 2);
+        (lines[0].split(',').map(String::from).collect(),
+         lines[1].split(',').map(|v| v.parse().unwrap()).collect())
+    }
+
+    #[test]
+    fn test_loops_without_enclosed_flux() {
+        let (names, values) = measure_once(&mut build_blank_lat(Point {x: 4, y: 6}));
+        assert_eq!(names, vec!["wilson_1_1", "wilson_1_2", "wilson_1_3",
+                               "wilson_2_1", "wilson_2_2", "wilson_2_3"]);
+        assert_eq!(values, vec![1.0; 6]);
+        // Straight strings run along the bottom and top of every loop in opposite directions.
+        let (_, values) = measure_once(&mut build_z3_striped_lat(Point {x: 4, y: 4}));
+        assert_eq!(values, vec![1.0; 4]);
+    }
+
+    #[test]
+    fn test_single_plaquette_loop() {
+        // A counterclockwise loop around the plaquette with corners (0, 0) and (1, 1).
+        let mut lat = build_blank_lat(Point {x: 4, y: 4});
+        lat.out_raise_link(&Point {x: 0, y: 0}, &Direction::E);
+        lat.out_lower_link(&Point {x: 1, y: 1}, &Direction::S);
+        lat.out_raise_link(&Point {x: 1, y: 1}, &Direction::W);
+        lat.out_lower_link(&Point {x: 0, y: 0}, &Direction::N);
+        assert!(lat.validate().flux_violations.is_empty());
+        let (_, values) = measure_once(&mut lat);
+        // The plaquette itself and its four neighbours see one unit of flux, cos(2 pi / 3).
+        let expected = (11.0 - 5.0 * 0.5) / 16.0;
+        assert!((values[0] - expected).abs() < 1e-12);
+    }
+}
+
+/// Wilson loops of every size R x T with R up to Lx / 2 and T up to Ly / 2.
+///
+/// A loop is the rectangle of R links in x and T links in y with its lower left corner on a
+/// vertex. Its value is omega^k with omega = exp(2 pi i / N) and k the net flux carried along
+/// its boundary links, counted positive where it runs counterclockwise. The estimator
+/// averages the real part over all translations (the imaginary part vanishes on average) and
+/// writes one line per bin with a column `wilson_R_T` per size, which `analysis::bins` reads.
+///
+/// Area law, -ln W ~ sigma R T, means confined strings with string tension sigma; perimeter
+/// law, -ln W ~ 2 (R + T), a deconfined phase. The Creutz ratio
+/// -ln(W(R, T) W(R - 1, T - 1) / (W(R, T - 1) W(R - 1, T))) estimates sigma, e.g. with
+/// `analyze --derived`.
+#[derive(Debug)]
+pub struct WilsonLoopEstimator {
+    size: Point,
+    modulus: u8,
+    max_r: i64,
+    max_t: i64,
+    /// Sum over measurements and translations, index (r - 1) * max_t + (t - 1).
+    sums: Vec<f64>,
+    result_file_buffer: ResultSink,
+}
+
+impl WilsonLoopEstimator {
+
+    /// Results go to `wilson_loop_estimator.csv` in `output` (no new header if it appends).
+    pub fn new(size: &Point, modulus: u8, output: &EstimatorOutput) -> WilsonLoopEstimator {
+        println!("Initializing WilsonLoopEstimator");
+
+        let max_r = size.x / 2;
+        let max_t = size.y / 2;
+        let mut wilson_loop_estimator = WilsonLoopEstimator {
+            size: *size,
+            modulus,
+            max_r,
+            max_t,
+            sums: vec![0.0; (max_r * max_t) as usize],
+            result_file_buffer: output.open("wilson_loop_estimator.csv"),
+        };
+
+        if !output.appends() {
+            let mut names: Vec<String> = Vec::new();
+            for r in 1..(max_r + 1) {
+                for t in 1..(max_t + 1) {
+                    names.push(format!("wilson_{}_{}", r, t));
+                }
+            }
+            let header_string = format!("{}\n", names.join(","));
+            match wilson_loop_estimator.result_file_buffer.write(header_string.as_bytes()){
+                Err(_err) => panic!("Can not write Wilson loop header."),
+                Ok(_) => println!("Wrote Wilson loop header."),
+            };
+        }
+
+        wilson_loop_estimator
+    }
+}
+
+impl Measurable for WilsonLoopEstimator {
+    fn flush(&mut self) {
+        flush_result_file(&mut self.result_file_buffer);
+    }
+
+    fn clear(&mut self) {
+        for sum in self.sums.iter_mut() {
+            *sum = 0.0;
+        }
+    }
+
+    /// Divide by `denominator`, the number of measurements per bin, and by the number of
+    /// translations.
+    fn finalize_bin_and_write(&mut self, denominator: u64) {
+        let float_denominator = (denominator as f64) * ((self.size.x * self.size.y) as f64);
+        let values: Vec<String> = self.sums.iter()
+            .map(|sum| (sum / float_denominator).to_string())
+            .collect();
+        let out_string = format!("{}\n", values.join(","));
+        match self.result_file_buffer.write(out_string.as_bytes()){
+            Err(err) => panic!("Can not write to Wilson loop estimator buffer {}", err),
+            Ok(_) => (),
+        }
+    }
+
+    fn measure(&mut self, lat: &mut Lattice) {
+        let (lx, ly) = (self.size.x, self.size.y);
+        let index = |x: i64, y: i64| (y.rem_euclid(ly) * lx + x.rem_euclid(lx)) as usize;
+        let mut horizontal_flux: Vec<i64> = Vec::with_capacity((lx * ly) as usize);
+        let mut vertical_flux: Vec<i64> = Vec::with_capacity((lx * ly) as usize);
+        for y in 0..ly {
+            for x in 0..lx {
+                horizontal_flux.push(lat.link_flux(&Point {x, y}, &Direction::E));
+                vertical_flux.push(lat.link_flux(&Point {x, y}, &Direction::N));
+            }
+        }
+
+        // Flux along R links in x (T links in y) starting at every vertex, one length at a time.
+        let mut horizontal_lines: Vec<Vec<i64>> = vec![vec![0; (lx * ly) as usize]];
+        for r in 1..(self.max_r + 1) {
+            let line = (0..ly).flat_map(|y| (0..lx).map(move |x| (x, y)))
+                .map(|(x, y)| horizontal_lines[(r - 1) as usize][index(x, y)] + horizontal_flux[index(x + r - 1, y)])
+                .collect();
+            horizontal_lines.push(line);
+        }
+        let mut vertical_lines: Vec<Vec<i64>> = vec![vec![0; (lx * ly) as usize]];
+        for t in 1..(self.max_t + 1) {
+            let line = (0..ly).flat_map(|y| (0..lx).map(move |x| (x, y)))
+                .map(|(x, y)| vertical_lines[(t - 1) as usize][index(x, y)] + vertical_flux[index(x, y + t - 1)])
+                .collect();
+            vertical_lines.push(line);
+        }
+
+        for r in 1..(self.max_r + 1) {
+            let bottom_and_top = &horizontal_lines[r as usize];
+            for t in 1..(self.max_t + 1) {
+                let sides = &vertical_lines[t as usize];
+                let mut sum = 0.0;
+                for y in 0..ly {
+                    for x in 0..lx {
+                        let net_flux = bottom_and_top[index(x, y)] + sides[index(x + r, y)]
+                            - bottom_and_top[index(x, y + t)] - sides[index(x, y)];
+                        sum += (2.0 * PI * net_flux as f64 / self.modulus as f64).cos();
+                    }
+                }
+                self.sums[((r - 1) * self.max_t + (t - 1)) as usize] += sum;
+            }
+        }
+    }
+}
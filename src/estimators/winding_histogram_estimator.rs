@@ -0,0 +1,186 @@
+use std::collections::BTreeMap;
+use std::io::prelude::*;
+use super::Measurable;
+use super::EstimatorOutput;
+use super::ResultSink;
+use super::flush_result_file;
+use super::winding_variance_estimator::WindingCuts;
+use super::super::datamodel::Point;
+use super::super::datamodel::lattice::Lattice;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use estimators::MemoryOutput;
+    use datamodel::lattice::build_blank_lat;
+    use datamodel::lattice::build_z3_striped_lat;
+    use estimators::winding_variance_estimator::winding_numbers;
+
+    #[test]
+    fn test_histogram_and_sectors() {
+        let memory = MemoryOutput::new();
+        let output = EstimatorOutput::Memory(memory.clone());
+        let size = Point {x: 4, y: 6};
+        let mut estimator = WindingHistogramEstimator::new(&size, 3, &output);
+        // Six strings along x, so a column counts a winding of -6 and a row none.
+        let mut striped = build_z3_striped_lat(size);
+        let (horizontal, vertical) = winding_numbers(&striped);
+        assert_eq!((horizontal, vertical.abs()), (0, 6));
+        estimator.measure(&mut striped);
+        estimator.measure(&mut build_blank_lat(size));
+        estimator.measure(&mut build_blank_lat(size));
+        estimator.measure(&mut build_blank_lat(size));
+        estimator.finalize_bin_and_write(4);
+        estimator.flush();
+
+        let histogram = memory.contents("winding_histogram_estimator.csv").unwrap();
+        let lines: Vec<&str> = histogram.lines().collect();
+        assert_eq!(lines, vec!["horizontal,vertical,probability",
+                               "0,0,0.75", &format!("0,{},0.25", vertical)[..], ""]);
+
+        let sectors = memory.contents("winding_sector_estimator.csv").unwrap();
+        let lines: Vec<&str> = sectors.lines().collect();
+        assert_eq!(lines[0], "sector_0_0,sector_0_1,sector_0_2,sector_1_0,sector_1_1,sector_1_2,\
+                              sector_2_0,sector_2_1,sector_2_2,winding_squared_horizontal,\
+                              winding_squared_vertical,winding_stiffness");
+        let values: Vec<f64> = lines[1].split(',').map(|v| v.parse().unwrap()).collect();
+        // -6 is 0 modulo 3, so every configuration is in the trivial sector.
+        assert_eq!(&values[..9], &[1.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0]);
+        assert_eq!(values[9], 0.0);
+        assert_eq!(values[10], 9.0);
+        assert_eq!(values[11], 9.0 * 4.0 / 6.0 / 2.0);
+    }
+}
+
+/// Joint distribution of the horizontal and vertical winding numbers, measured every
+/// measurement. A histogram needs integers, so these are the windings of the row and column
+/// through the origin (`winding_numbers`); every other cut is checked to agree with them modulo
+/// N, and `flush` prints on stderr how many configurations failed that (`winding_number_count`
+/// writes the details).
+///
+/// Every bin writes the histogram of the integer pairs to `winding_histogram_estimator.csv`,
+/// and to `winding_sector_estimator.csv` one line with the probability of every topological
+/// sector (both windings modulo N, column `sector_h_v`), the mean squared windings and the
+/// winding number stiffness (<W_v^2> Lx / Ly + <W_h^2> Ly / Lx) / 2. Equal sector
+/// probabilities that do not depend on the size mean topological degeneracy.
+#[derive(Debug)]
+pub struct WindingHistogramEstimator {
+    size: Point,
+    modulus: u8,
+    /// How often every (horizontal, vertical) pair was seen in the bin.
+    counts: BTreeMap<(i64, i64), u64>,
+    number_measurements: u64,
+    /// Configurations where some cut disagrees with the ones through the origin.
+    number_inconsistent_configurations: u64,
+    /// Inconsistent configurations already counted in a summary on stderr by `flush`.
+    number_reported_configurations: u64,
+    histogram_file_buffer: ResultSink,
+    sector_file_buffer: ResultSink,
+}
+
+impl WindingHistogramEstimator {
+
+    /// Results go to `winding_histogram_estimator.csv`, one block of lines per bin, and
+    /// `winding_sector_estimator.csv` in `output` (no new headers if it appends).
+    pub fn new(size: &Point, modulus: u8, output: &EstimatorOutput) -> WindingHistogramEstimator {
+        println!("Initializing WindingHistogramEstimator");
+
+        let mut winding_histogram_estimator = WindingHistogramEstimator {
+            size: *size,
+            modulus,
+            counts: BTreeMap::new(),
+            number_measurements: 0,
+            number_inconsistent_configurations: 0,
+            number_reported_configurations: 0,
+            histogram_file_buffer: output.open("winding_histogram_estimator.csv"),
+            sector_file_buffer: output.open("winding_sector_estimator.csv"),
+        };
+
+        if !output.appends() {
+            match winding_histogram_estimator.histogram_file_buffer.write(b"horizontal,vertical,probability\n"){
+                Err(_err) => panic!("Can not write winding histogram header."),
+                Ok(_) => println!("Wrote winding histogram header."),
+            };
+            let mut names: Vec<String> = Vec::new();
+            for horizontal in 0..modulus {
+                for vertical in 0..modulus {
+                    names.push(format!("sector_{}_{}", horizontal, vertical));
+                }
+            }
+            names.push(String::from("winding_squared_horizontal"));
+            names.push(String::from("winding_squared_vertical"));
+            names.push(String::from("winding_stiffness"));
+            let header_string = format!("{}\n", names.join(","));
+            match winding_histogram_estimator.sector_file_buffer.write(header_string.as_bytes()){
+                Err(_err) => panic!("Can not write winding sector header."),
+                Ok(_) => println!("Wrote winding sector header."),
+            };
+        }
+
+        winding_histogram_estimator
+    }
+}
+
+impl Measurable for WindingHistogramEstimator {
+    fn flush(&mut self) {
+        flush_result_file(&mut self.histogram_file_buffer);
+        flush_result_file(&mut self.sector_file_buffer);
+        if self.number_inconsistent_configurations > self.number_reported_configurations {
+            eprintln!("Winding numbers of some cuts disagree with the cuts through the origin in {} \
+                       of {} measured configurations, the winding histogram counts the latter",
+                      self.number_inconsistent_configurations, self.number_measurements);
+            self.number_reported_configurations = self.number_inconsistent_configurations;
+        }
+    }
+
+    fn clear(&mut self) {
+        self.counts.clear();
+    }
+
+    /// Divide by `denominator`, the number of measurements per bin.
+    fn finalize_bin_and_write(&mut self, denominator: u64) {
+        let float_denominator = denominator as f64;
+        let modulus = self.modulus as i64;
+        let mut histogram_string = String::new();
+        let mut sectors = vec![0.0; (modulus * modulus) as usize];
+        let mut squared_horizontal = 0.0;
+        let mut squared_vertical = 0.0;
+        // Most likely first, so the common windings are at the top of every block.
+        let mut pairs: Vec<(&(i64, i64), &u64)> = self.counts.iter().collect();
+        pairs.sort_by(|a, b| b.1.cmp(a.1));
+        for (&(horizontal, vertical), &count) in pairs {
+            let probability = count as f64 / float_denominator;
+            histogram_string.push_str(&format!("{},{},{}\n", horizontal, vertical, probability));
+            let sector = horizontal.rem_euclid(modulus) * modulus + vertical.rem_euclid(modulus);
+            sectors[sector as usize] += probability;
+            squared_horizontal += probability * (horizontal * horizontal) as f64;
+            squared_vertical += probability * (vertical * vertical) as f64;
+        }
+        histogram_string.push_str("\n");
+        match self.histogram_file_buffer.write(histogram_string.as_bytes()){
+            Err(err) => panic!("Can not write to winding histogram estimator buffer {}", err),
+            Ok(_) => (),
+        }
+
+        let aspect_ratio = self.size.x as f64 / self.size.y as f64;
+        let stiffness = (squared_vertical * aspect_ratio + squared_horizontal / aspect_ratio) / 2.0;
+        let mut values: Vec<String> = sectors.iter().map(|p| p.to_string()).collect();
+        values.push(squared_horizontal.to_string());
+        values.push(squared_vertical.to_string());
+        values.push(stiffness.to_string());
+        let sector_string = format!("{}\n", values.join(","));
+        match self.sector_file_buffer.write(sector_string.as_bytes()){
+            Err(err) => panic!("Can not write to winding sector estimator buffer {}", err),
+            Ok(_) => (),
+        }
+    }
+
+    fn measure(&mut self, lat: &mut Lattice) {
+        self.number_measurements += 1;
+        let cuts = WindingCuts::of(lat);
+        if !cuts.inconsistencies(lat.modulus).is_empty() {
+            self.number_inconsistent_configurations += 1;
+        }
+        *self.counts.entry((cuts.rows[0], cuts.columns[0])).or_insert(0) += 1;
+    }
+}
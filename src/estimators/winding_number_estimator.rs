@@ -1,12 +1,62 @@
-use std::fs::File;
-use std::path::Path;
-use std::io::BufWriter;
 use super::Measurable;
+use super::EstimatorOutput;
+use super::ResultSink;
+use super::flush_result_file;
 use std::io::prelude::*;
 use super::super::datamodel::Link;
 use super::super::datamodel::Point;
 use super::super::datamodel::lattice::Lattice;
 use super::super::datamodel::Direction;
+use super::winding_variance_estimator::WindingCuts;
+use super::winding_variance_estimator::WindingInconsistency;
+use super::super::oio::lattice_to_style_2_line;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use estimators::MemoryOutput;
+    use datamodel::lattice::build_blank_lat;
+    use datamodel::lattice::build_z3_fully_packed_lat;
+
+    #[test]
+    fn test_closed_string_net_has_no_diagnostics() {
+        let memory = MemoryOutput::new();
+        let output = EstimatorOutput::Memory(memory.clone());
+        let mut lat = build_z3_fully_packed_lat(Point {x: 4, y: 4});
+        let cuts = WindingCuts::of(&lat);
+        assert!(cuts.inconsistencies(3).is_empty());
+        let mut estimator = WindingNumberCountEstimator::new(lat.clone(), &output);
+        estimator.measure(&mut lat);
+        estimator.finalize_bin_and_write(1);
+        estimator.flush();
+        let contents = memory.contents("winding_number_count_estimator.csv").unwrap();
+        assert_eq!(contents.lines().nth(1).unwrap(),
+                   format!("{},{}", cuts.average_horizontal(), cuts.average_vertical()));
+        assert!(memory.contents("winding_diagnostics.csv").is_none());
+    }
+
+    #[test]
+    fn test_broken_string_net_is_reported() {
+        let memory = MemoryOutput::new();
+        let output = EstimatorOutput::Memory(memory.clone());
+        let mut lat = build_blank_lat(Point {x: 4, y: 4});
+        // A lone link from (1, 2) to (2, 2) only crosses the cut after column 1.
+        lat.out_lower_link(&Point {x: 2, y: 2}, &Direction::W);
+        let mut estimator = WindingNumberCountEstimator::new(lat.clone(), &output);
+        estimator.measure(&mut lat);
+        estimator.measure(&mut lat);
+        estimator.flush();
+
+        let diagnostics = memory.contents("winding_diagnostics.csv").unwrap();
+        let lines: Vec<&str> = diagnostics.lines().collect();
+        assert_eq!(lines, vec!["measurement,configuration,cut,index,winding,reference",
+                               "1,0,column,1,1,0", "2,1,column,1,1,0"]);
+        let configurations = memory.contents("winding_inconsistent_configurations.csv").unwrap();
+        assert_eq!(configurations.lines().count(), 2);
+        // Both are in the one summary the flush printed.
+        assert_eq!(estimator.number_reported_configurations, 2);
+    }
+}
 
 #[derive(Debug)]
 pub struct WindingNumberCountEstimatorDisplay {
@@ -16,9 +66,17 @@ pub struct WindingNumberCountEstimatorDisplay {
 
 #[derive(Debug)]
 pub struct WindingNumberCountEstimator {
-    count_horizontal: i64,
-    count_vertical: i64,
-    result_file_buffer: BufWriter<File>,
+    count_horizontal: f64,
+    count_vertical: f64,
+    result_file_buffer: ResultSink,
+    /// Kept to open the diagnostics files when the first inconsistency shows up.
+    output: EstimatorOutput,
+    /// `winding_diagnostics.csv` and `winding_inconsistent_configurations.csv`.
+    diagnostics_file_buffers: Option<(ResultSink, ResultSink)>,
+    number_measurements: u64,
+    number_inconsistent_configurations: u64,
+    /// Inconsistent configurations already counted in a summary on stderr by `flush`.
+    number_reported_configurations: u64,
     // Additions for the iterator
     iterator_location: i64,
     cur_point: Point,
@@ -29,25 +87,22 @@ pub struct WindingNumberCountEstimator {
 }
 
 impl WindingNumberCountEstimator {
-    pub fn new(lat: Lattice) -> WindingNumberCountEstimator{
+    /// Results go to `winding_number_count_estimator.csv` in `output` (no new header if it appends).
+    pub fn new(lat: Lattice, output: &EstimatorOutput) -> WindingNumberCountEstimator{
         println!("Initializing WindingNumberCountEstimator");
 
         println!("Opening WindingNumberCountEstimator file");
-        let path = Path::new("winding_number_count_estimator.csv");
-        let display = path.display();
-        let file = match File::create(&path) {
-            Err(err) => panic!("could not create {}: {}",
-                display,
-                err),
-            Ok(good_file) => good_file,
-        };
-
-        let result_file_buffer = BufWriter::new(file);
+        let result_file_buffer = output.open("winding_number_count_estimator.csv");
 
         let mut winding_number_count_estimator = WindingNumberCountEstimator{
-            count_horizontal: 0,
-            count_vertical: 0,
+            count_horizontal: 0.0,
+            count_vertical: 0.0,
             result_file_buffer,
+            output: output.clone(),
+            diagnostics_file_buffers: None,
+            number_measurements: 0,
+            number_inconsistent_configurations: 0,
+            number_reported_configurations: 0,
             iterator_location: 0,
             cur_point: Point {x: 0, y: 0},
             cur_grab_direction: Direction::N,
@@ -56,29 +111,67 @@ impl WindingNumberCountEstimator {
             lat
         };
 
-        let mut header_string = String::new();
-        header_string.push_str("Horizontal,Vertical\n");
-        match winding_number_count_estimator.result_file_buffer.write(header_string.as_bytes()){
-            Err(_err) => panic!("Can not write winding number count header."),
-            Ok(_) => println!("Wrote total link count header."),
-        };
+        if !output.appends() {
+            let mut header_string = String::new();
+            header_string.push_str("Horizontal,Vertical\n");
+            match winding_number_count_estimator.result_file_buffer.write(header_string.as_bytes()){
+                Err(_err) => panic!("Can not write winding number count header."),
+                Ok(_) => println!("Wrote total link count header."),
+            };
+        }
 
         println!("Done initializing winding number count estimator.");
 
         winding_number_count_estimator
     }
 
-    pub fn simple_add_sub_from_link_direction(num_in: &mut i64, link_in: &Link) {
-        match link_in {
-            Link::In => *num_in -= 1,
-            Link::Out => *num_in += 1,
-            _ => ()
+    /// Write a line per cut in `inconsistencies` to `winding_diagnostics.csv`, with the number
+    /// of the measurement and of the configuration, which is added (style 2) to
+    /// `winding_inconsistent_configurations.csv`. The files are only opened the first time.
+    fn report_inconsistencies(&mut self, lat: &Lattice, inconsistencies: &[WindingInconsistency]) {
+        if self.diagnostics_file_buffers.is_none() {
+            let mut diagnostics = self.output.open("winding_diagnostics.csv");
+            let configurations = self.output.open("winding_inconsistent_configurations.csv");
+            if !self.output.appends() {
+                match diagnostics.write(b"measurement,configuration,cut,index,winding,reference\n") {
+                    Err(_err) => panic!("Can not write winding diagnostics header."),
+                    Ok(_) => (),
+                };
+            }
+            self.diagnostics_file_buffers = Some((diagnostics, configurations));
+        }
+        let mut out_string = String::new();
+        for inconsistency in inconsistencies {
+            out_string.push_str(&format!("{},{},{},{},{},{}\n",
+                                         self.number_measurements,
+                                         self.number_inconsistent_configurations,
+                                         inconsistency.cut,
+                                         inconsistency.index,
+                                         inconsistency.winding,
+                                         inconsistency.reference));
+        }
+        if let Some((ref mut diagnostics, ref mut configurations)) = self.diagnostics_file_buffers {
+            match diagnostics.write(out_string.as_bytes()) {
+                Err(err) => panic!("Can not write to winding diagnostics file {}", err),
+                Ok(_) => (),
+            }
+            match configurations.write(lattice_to_style_2_line(lat).as_bytes()) {
+                Err(err) => panic!("Can not write to winding inconsistent configurations file {}", err),
+                Ok(_) => (),
+            }
         }
+        self.number_inconsistent_configurations += 1;
     }
 
-    pub fn modulo_winding_number(x: i64) -> u64 {
+    /// Adds the flux the link carries (`In` counts as negative) to `num_in`.
+    pub fn simple_add_sub_from_link_direction(num_in: &mut i64, link_in: &Link) {
+        *num_in += link_in.signed_value();
+    }
+
+    /// Winding number modulo `modulus`, the N of the ZN string net.
+    pub fn modulo_winding_number(x: i64, modulus: u8) -> u64 {
         //println!("x {}", x);
-        let modulus_by = 3;
+        let modulus_by = modulus as i64;
         let mut to_return: u64;
         if x < 0 {
             //println!("x is less than zero (in if)");
@@ -99,7 +192,7 @@ impl Iterator for WindingNumberCountEstimator {
     type Item = WindingNumberCountEstimatorDisplay;
 
     fn next(&mut self) -> Option<WindingNumberCountEstimatorDisplay> {
-        if self.iterator_location == self.lat.size.x {
+        if self.iterator_location == self.lat.size.y {
             self.iter_done = true;
         }
         if !self.iter_done {
@@ -123,9 +216,9 @@ impl Iterator for WindingNumberCountEstimator {
                 &mut self.vert_winding_count, &maybe_flipped_link
             );
 
-            let mod_count = WindingNumberCountEstimator::modulo_winding_number(self.vert_winding_count) as i64;
+            let mod_count = WindingNumberCountEstimator::modulo_winding_number(self.vert_winding_count, self.lat.modulus) as i64;
 
-            self.count_vertical = self.vert_winding_count;
+            self.count_vertical = self.vert_winding_count as f64;
 
             self.iterator_location += 1;
         }
@@ -145,136 +238,37 @@ impl Iterator for WindingNumberCountEstimator {
 }
 
 impl Measurable for WindingNumberCountEstimator {
-
-    fn measure(&mut self, lat: &mut Lattice) {
-        // First count winding number in vertical direction along column at origin.
-        // Also count winding number in vertical direction along column at origin + 1.
-        // We can assert that this needs to be the same winding number as that found from the
-        // origin column as a safety check.
-        let mut cur_point: Point;
-        let mut cur_point_check: Point;
-        let mut cur_grab_direction: Direction;
-        let mut cur_grab_direction_check: Direction;
-
-        // Direction of horizontal links to get
-        let mut vert_winding_count: i64 = 0;
-        let mut vert_winding_count_check: i64 = 0;
-
-        for i in 0..lat.size.y {
-            //println!("i {}", i);
-            if i % 2 == 0 {
-                cur_point = Point {x: 0, y: i};
-                cur_point_check = Point {x: 2, y: i};
-                cur_grab_direction = Direction::E;
-                cur_grab_direction_check = Direction::W;
-                //println!("************************************************************");
-                //println!("In mod 0 and cur point is {:?}", cur_point);
-                //println!("In mod 0 and cur point check is {:?}", cur_point_check);
-                //println!("In mod 0 and cur grab direction is {:?}", cur_grab_direction);
-                //println!("In mod 0 and cur grab direction check is {:?}", cur_grab_direction_check);
-                //println!("************************************************************");
-            }
-            else {
-                cur_point = Point {x: 1, y: i};
-                cur_point_check = Point {x: 1, y: i};
-                cur_grab_direction = Direction::W;
-                cur_grab_direction_check = Direction::E;
-                //println!("************************************************************");
-                //println!("In mod else and cur point is {:?}", cur_point);
-                //println!("In mod else and cur point check is {:?}", cur_point_check);
-                //println!("In mod else and cur grab direction is {:?}", cur_grab_direction);
-                //println!("In mod else and cur grab direction check is {:?}", cur_grab_direction_check);
-                //println!("************************************************************");
-            }
-
-            let cur_link: &Link = lat.safe_get_link_from_point(&cur_point, &cur_grab_direction);
-            //println!("cur_link {:?}", cur_link);
-            let cur_link_check: &Link = lat.safe_get_link_from_point(&cur_point_check, &cur_grab_direction_check);
-            let maybe_flipped_link: Link;
-            let maybe_flipped_link_check: Link;
-            if i % 2 == 1 {
-                //println!("flipping cur_link (before) {:?}", cur_link);
-                maybe_flipped_link = cur_link.clone().flip();
-                //println!("flipping cur_link (after) {:?}", maybe_flipped_link);
-            }
-            else {
-                maybe_flipped_link = *cur_link;
-            }
-
-            if i % 2 == 0 {
-                maybe_flipped_link_check = cur_link_check.clone().flip();
-            }
-            else {
-                maybe_flipped_link_check = *cur_link_check;
-            }
-
-            WindingNumberCountEstimator::simple_add_sub_from_link_direction(
-                &mut vert_winding_count, &maybe_flipped_link
-            );
-            WindingNumberCountEstimator::simple_add_sub_from_link_direction(
-                &mut vert_winding_count_check, &maybe_flipped_link_check
-            );
+    fn flush(&mut self) {
+        flush_result_file(&mut self.result_file_buffer);
+        if let Some((ref mut diagnostics, ref mut configurations)) = self.diagnostics_file_buffers {
+            flush_result_file(diagnostics);
+            flush_result_file(configurations);
         }
-
-        let mod_count = WindingNumberCountEstimator::modulo_winding_number(vert_winding_count) as i64;
-        let mod_count_check= WindingNumberCountEstimator::modulo_winding_number(vert_winding_count_check) as i64;
-
-        assert_eq!(mod_count, mod_count_check);
-
-        // Do the same for the horizontal direction
-        let mut horz_winding_count: i64 = 0;
-        let mut horz_winding_count_check: i64 = 0;
-
-        for i in 0..lat.size.x {
-            //println!("i {}", i);
-            if i % 2 == 0 {
-                cur_point = Point {x: i, y: 0};
-                cur_point_check = Point {x: i, y: 2};
-                cur_grab_direction = Direction::N;
-                cur_grab_direction_check = Direction::S;
-            }
-            else {
-                cur_point = Point {x: i, y: 1};
-                cur_point_check = Point {x: i, y: 1};
-                cur_grab_direction = Direction::S;
-                cur_grab_direction_check = Direction::N;
-            }
-
-            let cur_link: &Link = lat.safe_get_link_from_point(&cur_point, &cur_grab_direction);
-            //println!("cur_link {:?}", cur_link);
-            let cur_link_check: &Link = lat.safe_get_link_from_point(&cur_point_check, &cur_grab_direction_check);
-            let maybe_flipped_link: Link;
-            let maybe_flipped_link_check: Link;
-            if i % 2 == 1 {
-                //println!("flipping cur_link (before) {:?}", cur_link);
-                maybe_flipped_link = cur_link.clone().flip();
-                //println!("flipping cur_link (after) {:?}", maybe_flipped_link);
-            }
-            else {
-                maybe_flipped_link = *cur_link;
-            }
-
-            if i % 2 == 0 {
-                maybe_flipped_link_check = cur_link_check.clone().flip();
-            }
-            else {
-                maybe_flipped_link_check = *cur_link_check;
-            }
-
-            WindingNumberCountEstimator::simple_add_sub_from_link_direction(&mut horz_winding_count, &maybe_flipped_link);
-            WindingNumberCountEstimator::simple_add_sub_from_link_direction(&mut horz_winding_count_check, &maybe_flipped_link_check );
+        // One line for everything found since the last flush instead of one per measurement.
+        if self.number_inconsistent_configurations > self.number_reported_configurations {
+            eprintln!("Winding numbers disagree between cuts in {} of {} measured configurations, \
+                       which are not closed string nets, see winding_diagnostics.csv",
+                      self.number_inconsistent_configurations, self.number_measurements);
+            self.number_reported_configurations = self.number_inconsistent_configurations;
         }
-        let mod_count= WindingNumberCountEstimator::modulo_winding_number(horz_winding_count) as i64;
-        let mod_count_check= WindingNumberCountEstimator::modulo_winding_number(horz_winding_count_check) as i64;
+    }
 
-        assert_eq!(mod_count, mod_count_check);
-        self.count_horizontal = horz_winding_count;
-        self.count_vertical = vert_winding_count;
+    /// Average the winding numbers over every row and column. Cuts that disagree modulo N
+    /// are reported with `report_inconsistencies` and the run goes on.
+    fn measure(&mut self, lat: &mut Lattice) {
+        self.number_measurements += 1;
+        let cuts = WindingCuts::of(lat);
+        let inconsistencies = cuts.inconsistencies(lat.modulus);
+        if !inconsistencies.is_empty() {
+            self.report_inconsistencies(lat, &inconsistencies);
+        }
+        self.count_horizontal = cuts.average_horizontal();
+        self.count_vertical = cuts.average_vertical();
     }
 
     fn finalize_bin_and_write(&mut self, denominator: u64) {
-        let avg_count_vertical: f64 = (self.count_vertical as f64) / (denominator as f64);
-        let avg_count_horizontal: f64 = (self.count_horizontal as f64) / (denominator as f64);
+        let avg_count_vertical: f64 = self.count_vertical / (denominator as f64);
+        let avg_count_horizontal: f64 = self.count_horizontal / (denominator as f64);
 
         let mut out_string: String = String::new();
         out_string.push_str(&format!("{},{}\n",&avg_count_horizontal,&avg_count_vertical));
@@ -288,7 +282,7 @@ impl Measurable for WindingNumberCountEstimator {
     }
 
     fn clear(&mut self) {
-        self.count_vertical = 0;
-        self.count_horizontal = 0;
+        self.count_vertical = 0.0;
+        self.count_horizontal = 0.0;
     }
 }
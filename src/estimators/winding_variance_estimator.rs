@@ -1,38 +1,125 @@
-use std::fs::File;
-use std::path::Path;
-use std::io::BufWriter;
 use super::Measurable;
+use super::EstimatorOutput;
+use super::ResultSink;
+use super::flush_result_file;
 use std::io::prelude::*;
-use super::super::datamodel::Link;
 use super::super::datamodel::Point;
 use super::super::datamodel::lattice::Lattice;
 use super::super::datamodel::Direction;
-use super::winding_number_estimator::WindingNumberCountEstimator;
 use std::vec::Vec;
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use estimators::MemoryOutput;
+    use datamodel::lattice::build_blank_lat;
+    use datamodel::lattice::build_z3_striped_lat;
+
+    #[test]
+    fn test_variance_of_integer_windings() {
+        let memory = MemoryOutput::new();
+        let output = EstimatorOutput::Memory(memory.clone());
+        let size = Point {x: 4, y: 6};
+        let mut estimator = WindingNumberVarianceEstimator::new(&output);
+        let mut striped = build_z3_striped_lat(size);
+        let vertical = winding_numbers(&striped).1 as f64;
+        estimator.measure(&mut striped);
+        estimator.measure(&mut build_blank_lat(size));
+        // A bin cut short by one measurement still divides by what it measured.
+        estimator.finalize_bin_and_write(3);
+        estimator.flush();
+        let contents = memory.contents("winding_number_variance_estimator.csv").unwrap();
+        assert_eq!(contents.lines().nth(1).unwrap(), format!("0,{}", vertical * vertical / 4.0));
+    }
+}
+
+
+/// A cut whose winding number disagrees, modulo N, with the first cut of the same kind.
+#[derive(Debug, Clone, PartialEq)]
+pub struct WindingInconsistency {
+    /// `row` (the vertical links between y and y + 1) or `column` (the horizontal links between
+    /// x and x + 1).
+    pub cut: &'static str,
+    pub index: i64,
+    pub winding: i64,
+    /// Winding number of the cut at index 0.
+    pub reference: i64,
+}
+
+/// Winding numbers counted along every row and every column of the lattice.
+///
+/// `rows[y]` is the flux through the vertical links leaving row y upwards (the horizontal
+/// winding number), `columns[x]` the flux through the horizontal links leaving column x to
+/// the east (the vertical winding number). In a closed string net all rows agree modulo N
+/// and so do all columns; the integers can differ by multiples of N where a junction sits
+/// between two cuts.
+#[derive(Debug, Clone, PartialEq)]
+pub struct WindingCuts {
+    pub rows: Vec<i64>,
+    pub columns: Vec<i64>,
+}
+
+impl WindingCuts {
+    pub fn of(lat: &Lattice) -> WindingCuts {
+        let rows = (0..lat.size.y)
+            .map(|y| (0..lat.size.x).map(|x| lat.link_flux(&Point {x, y}, &Direction::N)).sum())
+            .collect();
+        let columns = (0..lat.size.x)
+            .map(|x| (0..lat.size.y).map(|y| lat.link_flux(&Point {x, y}, &Direction::E)).sum())
+            .collect();
+        WindingCuts {rows, columns}
+    }
+
+    /// Horizontal winding number averaged over the rows.
+    pub fn average_horizontal(&self) -> f64 {
+        self.rows.iter().sum::<i64>() as f64 / self.rows.len() as f64
+    }
+
+    /// Vertical winding number averaged over the columns.
+    pub fn average_vertical(&self) -> f64 {
+        self.columns.iter().sum::<i64>() as f64 / self.columns.len() as f64
+    }
 
+    /// Every row and column that disagrees with the first one modulo `modulus`. Empty for a
+    /// closed string net.
+    pub fn inconsistencies(&self, modulus: u8) -> Vec<WindingInconsistency> {
+        let modulus = modulus as i64;
+        let mut found: Vec<WindingInconsistency> = Vec::new();
+        for &(cut, windings) in [("row", &self.rows), ("column", &self.columns)].iter() {
+            for (index, &winding) in windings.iter().enumerate() {
+                if (winding - windings[0]).rem_euclid(modulus) != 0 {
+                    found.push(WindingInconsistency {cut, index: index as i64, winding, reference: windings[0]});
+                }
+            }
+        }
+        found
+    }
+}
+
+/// The horizontal and vertical winding numbers (not reduced modulo N) of `lat`, counted along
+/// the row and column through the origin, for estimators that need integers. Estimators of a
+/// plain mean use `WindingCuts` instead and average over every cut.
+pub fn winding_numbers(lat: &Lattice) -> (i64, i64) {
+    let cuts = WindingCuts::of(lat);
+    (cuts.rows[0], cuts.columns[0])
+}
+
+/// Variance over a bin of the integer horizontal and vertical winding numbers from
+/// `winding_numbers`, the same ones `winding_histogram` squares for its stiffness.
 #[derive(Debug)]
 pub struct WindingNumberVarianceEstimator {
     counts_horizontal: Vec<i64>,
     counts_vertical: Vec<i64>,
-    result_file_buffer: BufWriter<File>,
+    result_file_buffer: ResultSink,
 }
 
 impl WindingNumberVarianceEstimator {
-    pub fn new() -> WindingNumberVarianceEstimator{
+    /// Results go to `winding_number_variance_estimator.csv` in `output` (no new header if it appends).
+    pub fn new(output: &EstimatorOutput) -> WindingNumberVarianceEstimator{
         println!("Initializing WindingNumberVarianceEstimator");
 
         println!("Opening WindingNumberVarianceEstimator file");
-        let path = Path::new("winding_number_variance_estimator.csv");
-        let display = path.display();
-        let file = match File::create(&path) {
-            Err(err) => panic!("could not create {}: {}",
-                display,
-                err),
-            Ok(good_file) => good_file,
-        };
-
-        let result_file_buffer = BufWriter::new(file);
+        let result_file_buffer = output.open("winding_number_variance_estimator.csv");
 
         let mut winding_number_variance_estimator = WindingNumberVarianceEstimator{
             counts_horizontal: Vec::new(),
@@ -40,12 +127,14 @@ impl WindingNumberVarianceEstimator {
             result_file_buffer,
         };
 
-        let mut header_string = String::new();
-        header_string.push_str("Horizontal,Vertical\n");
-        match winding_number_variance_estimator.result_file_buffer.write(header_string.as_bytes()){
-            Err(_err) => panic!("Can not write winding number variance header."),
-            Ok(_) => println!("Wrote total link count header."),
-        };
+        if !output.appends() {
+            let mut header_string = String::new();
+            header_string.push_str("Horizontal,Vertical\n");
+            match winding_number_variance_estimator.result_file_buffer.write(header_string.as_bytes()){
+                Err(_err) => panic!("Can not write winding number variance header."),
+                Ok(_) => println!("Wrote total link count header."),
+            };
+        }
 
         println!("Done initializing winding number variance estimator.");
 
@@ -55,115 +144,20 @@ impl WindingNumberVarianceEstimator {
 }
 
 impl Measurable for WindingNumberVarianceEstimator {
+    fn flush(&mut self) {
+        flush_result_file(&mut self.result_file_buffer);
+    }
 
     fn measure(&mut self, lat: &mut Lattice) {
-        // First count winding number in vertical direction along column at origin.
-        // Also count winding number in vertical direction along column at origin + 1.
-        // We can assert that this needs to be the same winding number as that found from the
-        // origin column as a safety check.
-        let mut cur_point: Point;
-        let mut cur_point_check: Point;
-        let mut cur_grab_direction: Direction;
-        let mut cur_grab_direction_check: Direction;
-
-        // Direction of horizontal links to get
-        let mut vert_winding_count: i64 = 0;
-        let mut vert_winding_count_check: i64 = 0;
-
-        for i in 0..lat.size.y {
-            //println!("i {}", i);
-            if i % 2 == 0 {
-                cur_point = Point {x: 0, y: i};
-                cur_point_check = Point {x: 2, y: i};
-                cur_grab_direction = Direction::E;
-                cur_grab_direction_check = Direction::W;
-            }
-            else {
-                cur_point = Point {x: 1, y: i};
-                cur_point_check = Point {x: 1, y: i};
-                cur_grab_direction = Direction::W;
-                cur_grab_direction_check = Direction::E;
-            }
-
-            let cur_link: &Link = lat.safe_get_link_from_point(&cur_point, &cur_grab_direction);
-            let cur_link_check: &Link = lat.safe_get_link_from_point(&cur_point_check, &cur_grab_direction_check);
-            let maybe_flipped_link: Link;
-            let maybe_flipped_link_check: Link;
-            if i % 2 == 1 {
-                maybe_flipped_link = cur_link.clone().flip();
-            }
-            else {
-                maybe_flipped_link = *cur_link;
-            }
-
-            if i % 2 == 0 {
-                maybe_flipped_link_check = cur_link_check.clone().flip();
-            }
-            else {
-                maybe_flipped_link_check = *cur_link_check;
-            }
-
-            WindingNumberCountEstimator::simple_add_sub_from_link_direction(
-                &mut vert_winding_count, &maybe_flipped_link
-            );
-            WindingNumberCountEstimator::simple_add_sub_from_link_direction(
-                &mut vert_winding_count_check, &maybe_flipped_link_check
-            );
-        }
-
-        let mod_count = WindingNumberCountEstimator::modulo_winding_number(vert_winding_count) as i64;
-        let mod_count_check= WindingNumberCountEstimator::modulo_winding_number(vert_winding_count_check) as i64;
-
-        assert_eq!(mod_count, mod_count_check);
-
-        // Do the same for the horizontal direction
-        let mut horz_winding_count: i64 = 0;
-        let mut horz_winding_count_check: i64 = 0;
-
-        for i in 0..lat.size.x {
-            if i % 2 == 0 {
-                cur_point = Point {x: i, y: 0};
-                cur_point_check = Point {x: i, y: 2};
-                cur_grab_direction = Direction::N;
-                cur_grab_direction_check = Direction::S;
-            }
-            else {
-                cur_point = Point {x: i, y: 1};
-                cur_point_check = Point {x: i, y: 1};
-                cur_grab_direction = Direction::S;
-                cur_grab_direction_check = Direction::N;
-            }
-
-            let cur_link: &Link = lat.safe_get_link_from_point(&cur_point, &cur_grab_direction);
-            let cur_link_check: &Link = lat.safe_get_link_from_point(&cur_point_check, &cur_grab_direction_check);
-            let maybe_flipped_link: Link;
-            let maybe_flipped_link_check: Link;
-            if i % 2 == 1 {
-                maybe_flipped_link = cur_link.clone().flip();
-            }
-            else {
-                maybe_flipped_link = *cur_link;
-            }
-
-            if i % 2 == 0 {
-                maybe_flipped_link_check = cur_link_check.clone().flip();
-            }
-            else {
-                maybe_flipped_link_check = *cur_link_check;
-            }
-
-            WindingNumberCountEstimator::simple_add_sub_from_link_direction(&mut horz_winding_count, &maybe_flipped_link);
-            WindingNumberCountEstimator::simple_add_sub_from_link_direction(&mut horz_winding_count_check, &maybe_flipped_link_check );
-        }
-        let mod_count= WindingNumberCountEstimator::modulo_winding_number(horz_winding_count) as i64;
-        let mod_count_check= WindingNumberCountEstimator::modulo_winding_number(horz_winding_count_check) as i64;
-
-        assert_eq!(mod_count, mod_count_check);
-        self.counts_horizontal.push(horz_winding_count);
-        self.counts_vertical.push(vert_winding_count);
+        let (horizontal, vertical) = winding_numbers(lat);
+        self.counts_horizontal.push(horizontal);
+        self.counts_vertical.push(vertical);
     }
 
-    fn finalize_bin_and_write(&mut self, denominator: u64) {
+    /// Averages over the measurements kept in the bin, which are `denominator` of them in a
+    /// full bin.
+    fn finalize_bin_and_write(&mut self, _denominator: u64) {
+        let denominator = self.counts_vertical.len();
         let mut horz_squared_avg: f64 = 0.0;
         let mut horz_avg_squared: f64 = 0.0;
         let mut vert_squared_avg: f64 = 0.0;
@@ -7,7 +7,7 @@ use conrod_core::widget::grid::Lines;
 use conrod_core::position::Position::Absolute;
 use conrod_core::Color;
 use datamodel::lattice::Lattice;
-use datamodel::{Link, BoundPoint};
+use datamodel::{Orientation, BoundPoint};
 use datamodel::Direction;
 use conrod_core::widget;
 use conrod_core::widget::Id;
@@ -337,16 +337,17 @@ fn draw_bounding_box(
     ids: &Ids,
     ui: &mut conrod_core::UiCell,
     initial_offset: &f64,
-    lattice_size: i64
+    lattice_size: &datamodel::Point
 ) {
     let mut bound_box_id_iter= ids.bounding_box_edges.iter();
     let &next_id = match bound_box_id_iter.next() { Some(id) => id, None => panic!("Need a widget ID.") };
     let float_lm = LINK_MAJOR as f64;
-    let x: f64 = (lattice_size as f64) * float_lm;
+    let x: f64 = (lattice_size.x as f64) * float_lm;
+    let y: f64 = (lattice_size.y as f64) * float_lm;
 
     let box_color = conrod_core::color::rgb(0.0, 0.1, 0.1);
 
-    widget::Line::abs([*initial_offset - float_lm, *initial_offset - float_lm], [*initial_offset - float_lm, *initial_offset + x + float_lm/3.0])
+    widget::Line::abs([*initial_offset - float_lm, *initial_offset - float_lm], [*initial_offset - float_lm, *initial_offset + y + float_lm/3.0])
         .thickness(float_lm/1.5).color(box_color)
         .set(next_id, ui);
     let &next_id = match bound_box_id_iter.next() { Some(id) => id, None => panic!("Need a widget ID.") };
@@ -354,11 +355,11 @@ fn draw_bounding_box(
         .thickness(float_lm/1.5).color(box_color)
         .set(next_id, ui);
     let &next_id = match bound_box_id_iter.next() { Some(id) => id, None => panic!("Need a widget ID.") };
-    widget::Line::abs([*initial_offset + x, *initial_offset - float_lm], [*initial_offset + x, *initial_offset + x + float_lm/3.0])
+    widget::Line::abs([*initial_offset + x, *initial_offset - float_lm], [*initial_offset + x, *initial_offset + y + float_lm/3.0])
         .thickness(float_lm/1.5).color(box_color)
         .set(next_id, ui);
     let &next_id = match bound_box_id_iter.next() { Some(id) => id, None => panic!("Need a widget ID.") };
-    widget::Line::abs([*initial_offset - float_lm * 1.3333, *initial_offset + x], [*initial_offset + x + float_lm/3.0, *initial_offset + x])
+    widget::Line::abs([*initial_offset - float_lm * 1.3333, *initial_offset + y], [*initial_offset + x + float_lm/3.0, *initial_offset + y])
         .thickness(float_lm/1.5).color(box_color)
         .set(next_id, ui);
 }
@@ -369,7 +370,6 @@ fn draw_bounding_box(
 pub fn gui(ui: &mut conrod_core::UiCell,
            ids: &mut Ids,
            app: &mut DemoApp,
-           lattice_dim: i64,
            lattice: &mut Lattice,
            winding_estimator: &mut WindingNumberCountEstimator,
            ) {
@@ -395,19 +395,20 @@ pub fn gui(ui: &mut conrod_core::UiCell,
         .set(ids.title, ui);
 
     let initial_offset = -100.0;
+    let number_sites = lattice.size.x * lattice.size.y;
     ids.lines.resize(
-        (12 * lattice_dim * lattice_dim) as usize, &mut ui.widget_id_generator()
+        (12 * number_sites) as usize, &mut ui.widget_id_generator()
     );
     let mut triangle_line_iter = ids.lines.iter();
 
     ids.lattice_links.resize(
-        (3 * lattice_dim * lattice_dim) as usize, &mut ui.widget_id_generator()
+        (3 * number_sites) as usize, &mut ui.widget_id_generator()
     );
     ids.clustering_walk_path.resize(
-        (2 * lattice_dim * lattice_dim) as usize, &mut ui.widget_id_generator()
+        (2 * number_sites) as usize, &mut ui.widget_id_generator()
     );
     ids.clustering_current_avaliable_directions.resize(
-        (2 * lattice_dim * lattice_dim) as usize, &mut ui.widget_id_generator()
+        (2 * number_sites) as usize, &mut ui.widget_id_generator()
     );
     ids.bounding_box_edges.resize(4, &mut ui.widget_id_generator());
 
@@ -427,8 +428,8 @@ pub fn gui(ui: &mut conrod_core::UiCell,
         let &id1 =  match triangle_line_iter.next() { Some(id) => id, None => panic!("Need a widget ID.") };
         let &id2 =  match triangle_line_iter.next() { Some(id) => id, None => panic!("Need a widget ID.") };
         let &id3 =  match triangle_line_iter.next() { Some(id) => id, None => panic!("Need a widget ID.") };
-        match cur_vertex.n {
-            Link::In => {
+        match cur_vertex.n.orientation() {
+            Orientation::In => {
                 add_in_lattice_link(initial_offset, x, y, next_id, ui, in_color, true, 1.0, 1.0);
                 draw_triangle([tri_x, tri_y], Compass::S, id1, id2, id3, ui, false);
                 if y == lattice.size.y - 1{
@@ -442,7 +443,7 @@ pub fn gui(ui: &mut conrod_core::UiCell,
                     draw_triangle([tri_x, tri_y], Compass::S, id1, id2, id3, ui, true);
                 }
             },
-            Link::Out => {
+            Orientation::Out => {
                 add_in_lattice_link(initial_offset, x, y, next_id, ui, out_color, true, 1.0, 1.0);
                 draw_triangle([tri_x, tri_y], Compass::N, id1, id2, id3, ui, false);
                 if y == lattice.size.y - 1 {
@@ -456,7 +457,7 @@ pub fn gui(ui: &mut conrod_core::UiCell,
                     draw_triangle([tri_x, tri_y], Compass::N, id1, id2, id3, ui, true);
                 }
             },
-            Link::Blank => {
+            Orientation::Blank => {
                 add_in_lattice_link(initial_offset, x, y, next_id, ui, theme().shape_color, true, 1.0, 1.0);
                 if y == lattice.size.y - 1 {
                     // If it is a y boundary -> draw the periodic piece on the opposite side
@@ -469,8 +470,8 @@ pub fn gui(ui: &mut conrod_core::UiCell,
         let &id1 =  match triangle_line_iter.next() { Some(id) => id, None => panic!("Need a widget ID.") };
         let &id2 =  match triangle_line_iter.next() { Some(id) => id, None => panic!("Need a widget ID.") };
         let &id3 =  match triangle_line_iter.next() { Some(id) => id, None => panic!("Need a widget ID.") };
-        match cur_vertex.e {
-            Link::In => {
+        match cur_vertex.e.orientation() {
+            Orientation::In => {
                 add_in_lattice_link(initial_offset, x, y, next_id, ui, in_color, false, 1.0, 1.0);
                 draw_triangle([tri_x, tri_y], Compass::W, id1, id2, id3, ui, false);
                 if x == lattice.size.x - 1 {
@@ -484,7 +485,7 @@ pub fn gui(ui: &mut conrod_core::UiCell,
                     draw_triangle([tri_x, tri_y], Compass::W, id1, id2, id3, ui, true);
                 }
             },
-            Link::Out => {
+            Orientation::Out => {
                 add_in_lattice_link(initial_offset, x, y, next_id, ui, out_color, false, 1.0, 1.0);
                 draw_triangle([tri_x, tri_y], Compass::E, id1, id2, id3, ui, false);
                 if x == lattice.size.x - 1 {
@@ -498,7 +499,7 @@ pub fn gui(ui: &mut conrod_core::UiCell,
                     draw_triangle([tri_x, tri_y], Compass::E, id1, id2, id3, ui, true);
                 }
             },
-            Link::Blank => {
+            Orientation::Blank => {
                 add_in_lattice_link(initial_offset, x, y, next_id, ui, theme().shape_color, false, 1.0, 1.0);
                 if x == lattice.size.x - 1 {
                     // If it is a y boundary -> draw the periodic piece on the opposite side
@@ -511,8 +512,8 @@ pub fn gui(ui: &mut conrod_core::UiCell,
         let &id1 =  match triangle_line_iter.next() { Some(id) => id, None => panic!("Need a widget ID.") };
         let &id2 =  match triangle_line_iter.next() { Some(id) => id, None => panic!("Need a widget ID.") };
         let &id3 =  match triangle_line_iter.next() { Some(id) => id, None => panic!("Need a widget ID.") };
-        match cur_vertex.s {
-            Link::In => {
+        match cur_vertex.s.orientation() {
+            Orientation::In => {
                 add_in_lattice_link(initial_offset, x, y, next_id, ui, in_color, true, -1.0, 1.0);
                 draw_triangle([tri_x, tri_y], Compass::N, id1, id2, id3, ui, true);
                 if y == 0 {
@@ -526,7 +527,7 @@ pub fn gui(ui: &mut conrod_core::UiCell,
                     draw_triangle([tri_x, tri_y], Compass::N, id1, id2, id3, ui, true);
                 }
             },
-            Link::Out => {
+            Orientation::Out => {
                 add_in_lattice_link(initial_offset, x, y, next_id, ui, out_color, true, -1.0, 1.0);
                 draw_triangle([tri_x, tri_y], Compass::S, id1, id2, id3, ui, true);
                 if y == 0 {
@@ -540,7 +541,7 @@ pub fn gui(ui: &mut conrod_core::UiCell,
                     draw_triangle([tri_x, tri_y], Compass::S, id1, id2, id3, ui, true);
                 }
             },
-            Link::Blank => {
+            Orientation::Blank => {
                 add_in_lattice_link(initial_offset, x, y, next_id, ui, theme().shape_color, true, -1.0, 1.0);
                 if y == 0 {
                     // If it is a y boundary -> draw the periodic piece on the opposite side
@@ -553,8 +554,8 @@ pub fn gui(ui: &mut conrod_core::UiCell,
         let &id1 =  match triangle_line_iter.next() { Some(id) => id, None => panic!("Need a widget ID.") };
         let &id2 =  match triangle_line_iter.next() { Some(id) => id, None => panic!("Need a widget ID.") };
         let &id3 =  match triangle_line_iter.next() { Some(id) => id, None => panic!("Need a widget ID.") };
-        match cur_vertex.w {
-            Link::In => {
+        match cur_vertex.w.orientation() {
+            Orientation::In => {
                 add_in_lattice_link(initial_offset, x, y, next_id, ui, in_color, false, -1.0, 1.0);
                 draw_triangle([tri_x, tri_y], Compass::E, id1, id2, id3, ui, true);
                 if x == 0 {
@@ -568,7 +569,7 @@ pub fn gui(ui: &mut conrod_core::UiCell,
                     draw_triangle([tri_x, tri_y], Compass::E, id1, id2, id3, ui, true);
                 }
             },
-            Link::Out => {
+            Orientation::Out => {
                 add_in_lattice_link(initial_offset, x, y, next_id, ui, out_color, false, -1.0, 1.0);
                 draw_triangle([tri_x, tri_y], Compass::W, id1, id2, id3, ui, true);
                 if x == 0 {
@@ -582,7 +583,7 @@ pub fn gui(ui: &mut conrod_core::UiCell,
                     draw_triangle([tri_x, tri_y], Compass::W, id1, id2, id3, ui, true);
                 }
             },
-            Link::Blank => {
+            Orientation::Blank => {
                 add_in_lattice_link(initial_offset, x, y, next_id, ui, theme().shape_color, false, -1.0, 1.0);
                 if x == 0 {
                     // If it is a y boundary -> draw the periodic piece on the opposite side
@@ -641,7 +642,7 @@ pub fn gui(ui: &mut conrod_core::UiCell,
         ),
         None => println!("Got no winding number display")
     };
-    draw_bounding_box(ids, ui, &initial_offset, lattice.size.x);
+    draw_bounding_box(ids, ui, &initial_offset, &lattice.size);
 }
 
 
@@ -5,10 +5,118 @@ use super::datamodel::lattice::Lattice;
 use super::datamodel::Link;
 extern crate rand;
 use rand::prelude::*;
+use rand::rngs::StdRng;
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use datamodel::lattice::build_blank_lat;
+    use datamodel::lattice::build_blank_zn_lat;
+
+    fn run_seeded_walk(seed: u64) -> Lattice {
+        let mut lat: Lattice = build_blank_lat(Point{x: 4, y: 4});
+        let mut updater = Update::new(lat.size, 1.0, seed);
+        for _ in 0..50 {
+            updater.main_update(&mut lat, &UpdateType::Walk);
+        }
+        lat
+    }
+
+    #[test]
+    fn test_same_seed_gives_same_trajectory() {
+        let lat_a = run_seeded_walk(1234);
+        let lat_b = run_seeded_walk(1234);
+        assert_eq!(format!("{:?}", lat_a.vertices), format!("{:?}", lat_b.vertices));
+        assert_eq!(lat_a.number_filled_links, lat_b.number_filled_links);
+    }
+    #[test]
+    fn test_rejected_updates_are_undone() {
+        // With a weight of zero every update that adds links is rejected, so the blank lattice
+        // has to come back exactly after each (possibly self overlapping) random walk.
+        let mut lat: Lattice = build_blank_lat(Point{x: 6, y: 6});
+        let blank_vertices = format!("{:?}", lat.vertices);
+        let mut updater = Update::new(lat.size, 0.0, 3);
+        for _ in 0..50 {
+            updater.main_update(&mut lat, &UpdateType::Walk);
+            updater.main_update(&mut lat, &UpdateType::Local);
+        }
+        assert_eq!(format!("{:?}", lat.vertices), blank_vertices);
+        assert_eq!(lat.number_filled_links, 0);
+    }
+    #[test]
+    fn test_link_count_consistent_after_accept_and_reject() {
+        let mut lat: Lattice = build_blank_lat(Point{x: 4, y: 4});
+        let mut updater = Update::new(lat.size, 0.6, 8);
+        for _ in 0..200 {
+            updater.main_update(&mut lat, &UpdateType::Walk);
+            updater.main_update(&mut lat, &UpdateType::Local);
+            assert_eq!(lat.number_filled_links as u64, lat.count_non_blank_links());
+        }
+    }
+    #[test]
+    fn test_worm_update_keeps_string_net_closed() {
+        let mut lat: Lattice = build_blank_lat(Point{x: 6, y: 4});
+        let mut updater = Update::new(lat.size, 0.8, 21);
+        for _ in 0..100 {
+            updater.main_update(&mut lat, &UpdateType::Worm);
+            assert!(lat.validate().is_valid());
+            assert_eq!(lat.number_filled_links as u64, lat.count_non_blank_links());
+        }
+    }
+    #[test]
+    fn test_worm_update_keeps_zn_string_nets_closed() {
+        for &modulus in [2, 4, 5].iter() {
+            let mut lat: Lattice = build_blank_zn_lat(Point{x: 4, y: 6}, modulus);
+            let mut updater = Update::new(lat.size, 1.0, 13);
+            for _ in 0..50 {
+                updater.main_update(&mut lat, &UpdateType::Worm);
+                updater.main_update(&mut lat, &UpdateType::Local);
+                assert!(lat.validate().is_valid());
+                assert_eq!(lat.number_filled_links as u64, lat.count_non_blank_links());
+            }
+        }
+    }
+    #[test]
+    fn test_worm_update_changes_configuration() {
+        let mut lat: Lattice = build_blank_lat(Point{x: 4, y: 4});
+        let mut updater = Update::new(lat.size, 1.0, 5);
+        for _ in 0..20 {
+            updater.main_update(&mut lat, &UpdateType::Worm);
+        }
+        assert!(lat.count_non_blank_links() > 0);
+    }
+    #[test]
+    fn test_monomer_update_keeps_charges_on_monomers() {
+        let mut lat: Lattice = build_blank_lat(Point{x: 6, y: 4});
+        lat.insert_monomer_pair(&Point{x: 1, y: 0}, &Direction::N).unwrap();
+        let mut updater = Update::new(lat.size, 0.7, 31);
+        let mut moved = false;
+        for _ in 0..200 {
+            updater.main_update(&mut lat, &UpdateType::Worm);
+            updater.main_update(&mut lat, &UpdateType::Local);
+            assert!(lat.validate().is_valid());
+            moved |= lat.monomers[0].location != Point{x: 1, y: 0};
+        }
+        assert!(moved);
+    }
+    #[test]
+    fn test_seed_is_kept_by_updater() {
+        let updater = Update::new(Point{x: 4, y: 4}, 1.0, 42);
+        assert_eq!(updater.seed, 42);
+    }
+}
+
+/// Produce a fresh seed for runs where the user did not ask for one. The seed is
+/// reported with the run output so the run can still be replayed.
+pub fn generate_seed() -> u64 {
+    thread_rng().gen()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum UpdateType {
     Local,
     Walk,
+    Worm,
 }
 #[derive(Debug)]
 pub enum AcceptReject {
@@ -16,6 +124,15 @@ pub enum AcceptReject {
     Reject,
 }
 
+/// A link changed by an update along with its value before the change. Links are always
+/// addressed from the real vertex that stores them.
+#[derive(Debug, Clone, Copy)]
+pub struct ChangedLink {
+    pub location: Point,
+    pub direction: Direction,
+    pub before: Link,
+}
+
 /// This will ergodicly update the Z3 string net model.
 /// 
 /// A couple of choices are available.
@@ -23,75 +140,82 @@ pub enum AcceptReject {
 ///   walking clockwise with the raising operator. 
 /// * The `random_walk_update` will perform a random walk to produce an
 ///   extensive change to the configuration it operates on.
+/// * The `worm_update` grows a worm whose every step is accepted or rejected with the link
+///   weights, so the finished worm never has to be rejected as a whole.
 ///
 /// Currently the random walk update will modify an attribute in lattice.
 /// This is hidden when using the update method so I'm pointing it out here.
 /// `number_filled_links` will be modified by adding (subtracting) the
 /// `link_number_change` determined by the update function.
+///
+/// The links touched by the most recent update are kept in `changed_links` so a rejected update
+/// can be undone by restoring only those links (see `undo_last_update`).
+///
+/// Every random choice made while updating is drawn from `rng`, which is seeded with `seed`,
+/// so two runs started with the same seed produce identical trajectories.
 #[derive(Debug)]
 pub struct Update {
     pub working_loc: BoundPoint,
     pub link_number_tuning: f64,
     pub link_number_change: i64,
+    pub changed_links: Vec<ChangedLink>,
+    pub seed: u64,
+    pub rng: StdRng,
 }
 impl Update {
+    /// static "constructor" method. The working location starts at the origin.
+    pub fn new(size: Point, link_number_tuning: f64, seed: u64) -> Update {
+        Update {
+            working_loc: BoundPoint {
+                size,
+                location: Point {x: 0, y: 0},
+            },
+            link_number_tuning,
+            link_number_change: 0,
+            changed_links: Vec::new(),
+            seed,
+            rng: StdRng::seed_from_u64(seed),
+        }
+    }
+
+    /// Restart the random number generator from `rng_seed`.
+    pub fn reseed(&mut self, rng_seed: u64) {
+        self.rng = StdRng::seed_from_u64(rng_seed);
+    }
+
+    /// Draw a new seed from the current generator and restart the generator from it.
+    ///
+    /// The generator state itself is not written to disk. Instead a run restarts its generator
+    /// here at the end of every bin, whether or not it writes a checkpoint there, and a
+    /// checkpoint stores the seed of the latest restart. A run resumed with `reseed` then
+    /// follows exactly the same trajectory as the uninterrupted run, for any checkpoint cadence.
+    pub fn restart_rng(&mut self) -> u64 {
+        let rng_seed: u64 = self.rng.gen();
+        self.reseed(rng_seed);
+        rng_seed
+    }
+
     /// Determine by how much the number of non blank links has changed
     /// after a raise step.
     /// Possibilities 
-    ///     1) if a raise operation just switches the orientation it returns 0.
+    ///     1) if a raise operation turns a filled link into another filled link it returns 0.
     ///     2) if it brings an occupied link to a blank link -> -1
     ///     3) Blank to occupied -> +1
-    /// We don't know which way the raise step went so we have to check all possibilities
     fn find_increase_or_decrease(before_after_links: (Link, Link)) -> i8 {
         let (before_link, after_link) = before_after_links;
-        match before_link {
-            Link::In => {
-                match after_link {
-                    Link::Blank => {
-                        return -1
-                    },
-                    Link::Out => {
-                        return 0
-                    },
-                    Link::In => {
-                         panic!("In link can't change to In from raise or lower")
-                    }
-                }
-            },
-            Link::Out => {
-                match after_link {
-                    Link::In => {
-                        return 0
-                    },
-                    Link::Blank => {
-                        return -1
-                    },
-                    Link::Out => {
-                        panic!("Out link can't change to Out from raise or lower")
-                    }
-                }
-            },
-            Link::Blank => {
-                match after_link {
-                    Link::In => {
-                        return 1
-                    },
-                    Link::Out => {
-                        return 1
-                    },
-                    Link::Blank => {
-                        panic!("Blank link can't change to Blank from raise or lower")
-                    }
-                }
-            },
+        assert!(before_link != after_link, "A raise or lower always changes the link");
+        match (before_link.is_blank(), after_link.is_blank()) {
+            (false, true) => -1,
+            (true, false) => 1,
+            _ => 0,
         }
     }
 
     pub fn get_rand_point(&mut self) {
 
         self.working_loc.location = Point {
-            x: thread_rng().gen_range(0, self.working_loc.size.x),
-            y: thread_rng().gen_range(0, self.working_loc.size.y)
+            x: self.rng.gen_range(0, self.working_loc.size.x),
+            y: self.rng.gen_range(0, self.working_loc.size.y)
                 
         };
         // for testing
@@ -107,6 +231,7 @@ impl Update {
             start_loc: self.working_loc.location,
             cur_loc: self.working_loc,
             lat,
+            changed_links: Vec::new(),
         };
         let mut totatal_link_number_change: i64 = 0;
 
@@ -131,6 +256,7 @@ impl Update {
         totatal_link_number_change += number_increase_or_decrease as i64;
 
         assert_eq!(z3string.cur_loc, z3string.start_loc);
+        self.changed_links = z3string.changed_links;
         self.link_number_change = totatal_link_number_change;
         lat.number_filled_links += self.link_number_change;
         if lat.number_filled_links < 0 {
@@ -148,19 +274,20 @@ impl Update {
             let mut z3string = Z3String {
                 start_loc: self.working_loc.location,
                 cur_loc: self.working_loc,
-                lat
+                lat,
+                changed_links: Vec::new(),
             };
             let mut total_link_number_change: i64 = 0;
             // Take first step before loop so cur_loc and start_loc
             // are different.
-            let cur_direction = Direction::get_random_direction();
+            let cur_direction = Direction::get_random_direction(&mut self.rng);
             let before_after_links: (Link, Link) = z3string.raise_step(&cur_direction);
             let number_increase_or_decrease = Update::find_increase_or_decrease(before_after_links);
             total_link_number_change += number_increase_or_decrease as i64;
 
             while z3string.cur_loc != z3string.start_loc {
                 //println!("In while loop: cur_loc {:?}, start_loc {:?}", z3string.cur_loc, z3string.start_loc);
-                let cur_direction = Direction::get_random_direction();
+                let cur_direction = Direction::get_random_direction(&mut self.rng);
                 //println!("  direction {:?}", cur_direction);
                 let before_after_links: (Link, Link) = z3string.raise_step(&cur_direction);
                 let number_increase_or_decrease = Update::find_increase_or_decrease(before_after_links);
@@ -168,6 +295,66 @@ impl Update {
             }
             assert_eq!(z3string.cur_loc, z3string.start_loc);
             //println!("total_link_number_change {:?}", total_link_number_change);
+            self.changed_links = z3string.changed_links;
+            self.link_number_change = total_link_number_change;
+        };
+
+        lat.number_filled_links += self.link_number_change;
+        assert!(lat.number_filled_links >= 0);
+    }
+
+    /// Worm (directed loop) update.
+    ///
+    /// Opening the worm at a random vertex inserts a pair of Z3 charges on top of each other:
+    /// the tail, left behind at `start_loc`, and the head at `cur_loc`. Moving the head is a
+    /// `Z3String::raise_step`, which leaves the constraint broken only at the tail and the head.
+    /// Each head move is accepted with the Metropolis probability `min(1, w^change)`, where
+    /// `change` is the change in the number of filled links. The reverse of a head move is the
+    /// raise step back across the same link so every move can be undone by the worm itself.
+    ///
+    /// Each step picks one of five equally likely options: the four directions or "close".
+    /// Closing only happens when the head sits on the tail, otherwise that step does nothing.
+    /// Offering the same five options everywhere keeps detailed balance between the closed
+    /// configurations and the configurations with a head and tail, so the closed string net
+    /// left when the worm closes is sampled with weight `w^(number of filled links)` and the
+    /// update is always accepted.
+    pub fn worm_update(&mut self, lat: &mut Lattice) {
+        self.get_rand_point();
+        let mut total_link_number_change: i64 = 0;
+        {
+            let mut z3string = Z3String {
+                start_loc: self.working_loc.location,
+                cur_loc: self.working_loc,
+                lat,
+                changed_links: Vec::new(),
+            };
+            loop {
+                let choice = self.rng.gen_range(0, 5);
+                let cur_direction = match choice {
+                    0 => Direction::N,
+                    1 => Direction::E,
+                    2 => Direction::S,
+                    3 => Direction::W,
+                    _ => {
+                        if z3string.cur_loc == z3string.start_loc {
+                            break;
+                        }
+                        continue;
+                    }
+                };
+                let before_link: Link = z3string.outward_link(&cur_direction);
+                let number_increase_or_decrease =
+                    Update::find_increase_or_decrease((before_link, before_link.raise()));
+                let acceptance: f64 =
+                    f64::powf(self.link_number_tuning, number_increase_or_decrease as f64);
+                // Gen range produces number in  [lower, upper)
+                if self.rng.gen_range(0.0, 1.0) < acceptance {
+                    z3string.raise_step(&cur_direction);
+                    total_link_number_change += number_increase_or_decrease as i64;
+                }
+            }
+            assert_eq!(z3string.cur_loc, z3string.start_loc);
+            self.changed_links = z3string.changed_links;
             self.link_number_change = total_link_number_change;
         };
 
@@ -190,9 +377,8 @@ impl Update {
         let new_weight = f64::powf(self.link_number_tuning, number_filled_links as f64);
         let old_weight = f64::powf(self.link_number_tuning, old_number_filled_links as f64);
         let check_against: f64 = new_weight / old_weight;
-        let mut rng = thread_rng();
         // Gen range produces number in  [lower, upper)
-        let rand_number: f64 = rng.gen_range(0.0, 1.0);
+        let rand_number: f64 = self.rng.gen_range(0.0, 1.0);
 
         //println!("number_filled_links {:?}", number_filled_links);
         //println!("normalization_factor {:?}", normalization_factor);
@@ -210,34 +396,74 @@ impl Update {
         return  AcceptReject::Reject
     }
 
+    /// Put back every link changed by the last update, most recent change first so links
+    /// visited more than once end up with their original value, and restore the link count.
+    pub fn undo_last_update(&mut self, lat: &mut Lattice) {
+        while let Some(changed_link) = self.changed_links.pop() {
+            *lat.get_link_from_point(&changed_link.location, &changed_link.direction) =
+                changed_link.before;
+        }
+        lat.number_filled_links -= self.link_number_change;
+        self.link_number_change = 0;
+    }
+
+    /// Move one of the monomers, picked at random, a step in a random direction.
+    ///
+    /// The link the monomer crosses changes so the string net still ends on it (see
+    /// `Lattice::move_monomer`) and the move is accepted with probability
+    /// `min(1, w^(change in filled links))`. A rejected move is undone by stepping back.
+    pub fn monomer_update(&mut self, lat: &mut Lattice) {
+        if lat.monomers.is_empty() {
+            return;
+        }
+        let index = self.rng.gen_range(0, lat.monomers.len());
+        let direction = Direction::get_random_direction(&mut self.rng);
+        let change = lat.move_monomer(index, &direction);
+        let acceptance: f64 = f64::powf(self.link_number_tuning, change as f64);
+        // Gen range produces number in  [lower, upper)
+        if self.rng.gen_range(0.0, 1.0) >= acceptance {
+            lat.move_monomer(index, &direction.flip());
+        }
+    }
+
     /// Organizes the calling of the update functions while taking care of high level
     /// accept reject decisions.
+    ///
+    /// Only the link count of the original configuration is kept. If the move is rejected the
+    /// links it changed are restored with `undo_last_update`, so the cost of a move does not
+    /// grow with the size of the lattice.
+    ///
+    /// If the lattice has monomers every update is followed by a `monomer_update`.
     pub fn main_update(&mut self, lat: &mut Lattice, update_type: &UpdateType) {
 
-        // Save the original configuration of the lattice because moves might end up being
-        // rejected.
-        let original_lat: Lattice = lat.clone();
+        // How many links on the old configuration.
+        let old_number_links: i64 = lat.number_filled_links;
 
         match update_type {
             UpdateType::Local => self.update(lat),
-            UpdateType::Walk => self.random_walk_update(lat)
+            UpdateType::Walk => self.random_walk_update(lat),
+            UpdateType::Worm => self.worm_update(lat),
         };
 
-        // How many links on the new configuration.
-        let new_number_links: i64 = lat.number_filled_links;
-        let old_number_links: i64 = original_lat.number_filled_links;
-        // How many links on the old configuration.
-        //let old_number_links: u64 = original_lat.number_filled_links;
-
-        // Determine accept or reject. This function will return AcceptReject enum
-        match self.accept_or_reject_update(
-            lat.size,
-            new_number_links,
-            old_number_links
-        ) {
-            AcceptReject::Reject => {*lat = original_lat},
-            AcceptReject::Accept => {},
-        };
+        // Every step of the worm was already accepted or rejected on its own.
+        if *update_type != UpdateType::Worm {
+            // How many links on the new configuration.
+            let new_number_links: i64 = lat.number_filled_links;
+
+            // Determine accept or reject. This function will return AcceptReject enum
+            match self.accept_or_reject_update(
+                lat.size,
+                new_number_links,
+                old_number_links
+            ) {
+                AcceptReject::Reject => self.undo_last_update(lat),
+                AcceptReject::Accept => {},
+            };
+        }
+
+        if !lat.monomers.is_empty() {
+            self.monomer_update(lat);
+        }
     }
 }
 
@@ -248,6 +474,8 @@ pub struct Z3String<'a> {
     pub start_loc: Point,
     pub cur_loc: BoundPoint,
     lat: &'a mut Lattice, 
+    /// Every link this string has changed, in order, with its value before the change.
+    pub changed_links: Vec<ChangedLink>,
     //pub path: Vec<Point>,
 }
 impl<'a> Z3String<'a> {
@@ -272,6 +500,22 @@ impl<'a> Z3String<'a> {
             None => panic!("No step taken for some reason. No increment."),    
         }
     }
+    /// The link that a raise step in `direction` would change, as seen from `cur_loc` (that is,
+    /// oriented outward from the current vertex). Nothing is changed.
+    pub fn outward_link(&mut self, direction: &Direction) -> Link {
+        if self.lat.point_real(&self.cur_loc.location) {
+            *self.lat.get_link_from_point(&self.cur_loc.location, direction)
+        }
+        else {
+            let next_loc: BoundPoint = &self.cur_loc + match *direction {
+                Direction::N => Point {x: 0, y: 1},
+                Direction::E => Point {x: 1, y: 0},
+                Direction::S => Point {x: 0, y: -1},
+                Direction::W => Point {x: -1, y: 0},
+            };
+            self.lat.get_link_from_point(&next_loc.location, &direction.flip()).flip()
+        }
+    }
     pub fn raise_step(&mut self, direction: &Direction) -> (Link, Link) {
         //println!("cur location before {:?}",self.cur_loc.location);
         //println!("cur direction before {:?}",direction);
@@ -291,6 +535,11 @@ impl<'a> Z3String<'a> {
                 &self.cur_loc.location,
                 &direction
             ).clone();
+            self.changed_links.push(ChangedLink {
+                location: self.cur_loc.location,
+                direction: *direction,
+                before: pre_raise_link,
+            });
 
             post_raise_link =
                 self.lat.out_raise_link(&self.cur_loc.location, &direction);
@@ -314,6 +563,11 @@ impl<'a> Z3String<'a> {
                 &self.cur_loc.location,
                 &fliped_dir
             ).clone().flip();
+            self.changed_links.push(ChangedLink {
+                location: self.cur_loc.location,
+                direction: fliped_dir,
+                before: pre_raise_link.flip(),
+            });
 
             post_raise_link =
                 self.lat.out_lower_link(&self.cur_loc.location, &fliped_dir).flip();
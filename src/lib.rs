@@ -1,4 +1,6 @@
 extern crate rand;
+extern crate yaml_rust;
+extern crate num_complex;
 #[macro_use]
 extern crate conrod_core;
 extern crate glium;
@@ -8,6 +10,12 @@ pub mod datamodel;
 pub mod lattice_updates;
 pub mod estimators;
 pub mod oio;
+pub mod replay;
+pub mod config;
+pub mod simulation;
+pub mod sweep;
+pub mod tempering;
+pub mod analysis;
 pub mod gui;
 
 
@@ -6,140 +6,433 @@ extern crate conrod_glium;
 extern crate glium;
 extern crate conrod_core;
 
-
+use std::process;
+use std::fs;
+use std::path::PathBuf;
 use clap::App;
+use clap::ArgMatches;
 use conrod_glium::Renderer;
 use conrod_core::Dimensions;
 use z3stringnet::datamodel::Point;
-use z3stringnet::datamodel::BoundPoint;
-use z3stringnet::datamodel::lattice::Lattice;
-use z3stringnet::datamodel::lattice::build_z3_striped_lat;
-use z3stringnet::datamodel::lattice::build_z3_striped_vertical_lat;
-use z3stringnet::datamodel::lattice::build_blank_lat;
-use z3stringnet::datamodel::lattice::build_z3_fully_packed_lat;
-use z3stringnet::datamodel::lattice::build_z3_messy_lat;
-use z3stringnet::lattice_updates::Update;
+use z3stringnet::datamodel::lattice::build_blank_zn_lat;
+use z3stringnet::datamodel::lattice::check_lattice_size;
 use z3stringnet::lattice_updates::UpdateType;
 use z3stringnet::estimators::density_estimator::DensityEstimator;
 use z3stringnet::estimators::correlation_origin_estimator::CorrelationOriginEstimator;
 use z3stringnet::estimators::total_link_count_estimator::TotalLinkCountEstimator;
 use z3stringnet::estimators::winding_number_estimator::WindingNumberCountEstimator;
 use z3stringnet::estimators::winding_variance_estimator::WindingNumberVarianceEstimator;
-use z3stringnet::estimators::Measurable;
-use z3stringnet::oio::*;
+use z3stringnet::estimators::EstimatorOutput;
+use z3stringnet::estimators::MemoryOutput;
+use z3stringnet::config::RunConfig;
+use z3stringnet::config::InitialState;
+use z3stringnet::config::read_run_config;
+use z3stringnet::simulation::Simulation;
+use z3stringnet::sweep::*;
+use z3stringnet::tempering::ParallelTempering;
+use z3stringnet::analysis::*;
+use z3stringnet::analysis::bins::*;
+use z3stringnet::analysis::expression::parse_derived;
+use z3stringnet::replay::replay_configurations;
 use z3stringnet::gui::*;
 use glium::Surface;
 use conrod_core::widget::Image;
 use z3stringnet::estimators::cluster_size_estimator::FullClusterSizeEstimator;
 
 
-fn main() {
-    // Parse arguments
-    let yaml = load_yaml!("cli.yml");
+/// Lattice size from `--size` (square) or `--lx` and `--ly`. Exits if the size can not be used.
+fn lattice_size_from_matches(matches: &ArgMatches) -> Point {
+    let lattice_size_x_arg_str = matches.value_of("lx").or(matches.value_of("size")).unwrap_or("4");
+    let lattice_size_y_arg_str = matches.value_of("ly").or(matches.value_of("size")).unwrap_or("4");
+    let lattice_size_x_arg: i64 = lattice_size_x_arg_str.parse().unwrap();
+    let lattice_size_y_arg: i64 = lattice_size_y_arg_str.parse().unwrap();
+    println!("Lattice size from arguments: x {}, y {}", lattice_size_x_arg, lattice_size_y_arg);
 
-    let matches = App::from_yaml(yaml).get_matches();
+    let size: Point = Point {
+        x: lattice_size_x_arg,
+        y: lattice_size_y_arg,
+    };
+    if let Err(e) = check_lattice_size(&size) {
+        eprintln!("Can not build the lattice: {}", e);
+        process::exit(1);
+    }
+    size
+}
 
-    let lattice_size_arg_str = matches.value_of("size").unwrap_or("4");
-    let lattice_size_arg: i64 = lattice_size_arg_str.parse().unwrap();
-    println!("Lattice size from argument: {}", lattice_size_arg);
-    let run_with_gui_str = matches.value_of("gui").unwrap_or("false");
-    let run_with_gui: bool = run_with_gui_str.parse().unwrap();
-    println!("Run with GUI? {}", run_with_gui);
+/// The N of the ZN string net from `--modulus`. Exits if it can not be used.
+fn modulus_from_matches(matches: &ArgMatches) -> u8 {
+    let modulus_arg_str = matches.value_of("modulus").unwrap_or("3");
+    let modulus_arg: u8 = modulus_arg_str.parse().unwrap();
+    if modulus_arg < 2 {
+        eprintln!("Can not build the lattice: the modulus has to be at least 2, got {}", modulus_arg);
+        process::exit(1);
+    }
+    println!("Simulating a Z{} string net", modulus_arg);
+    modulus_arg
+}
 
-    let size: Point = Point {
-        x: lattice_size_arg,
-        y: lattice_size_arg,
+/// The run configuration from `--config` (or the defaults) with every flag that was passed on
+/// top. Exits if the configuration can not be read or used.
+fn run_config_from_matches(matches: &ArgMatches) -> RunConfig {
+    let mut config = match matches.value_of("config") {
+        Some(config_path) => {
+            println!("Reading run configuration from {}", config_path);
+            match read_run_config(config_path) {
+                Ok(config) => config,
+                Err(e) => {
+                    eprintln!("Can not read the run configuration: {}", e);
+                    process::exit(1);
+                }
+            }
+        },
+        None => RunConfig::default(),
     };
-    // lat now owns size -> That is good and intentional
-    let mut lat: Lattice = build_blank_lat(size);
-    //let mut lat: Lattice = build_z3_striped_lat(size);
-    //let mut lat: Lattice = build_z3_messy_lat(size);
-    //let mut lat: Lattice = build_z3_striped_vertical_lat(size);
-    //let mut lat: Lattice = build_z3_fully_packed_lat(size);
-    //lat.vertices[0].e = lat.vertices[0].e.flip();
-
-    let equilibrate = true;
-
-    let weights_arg_str = matches.value_of("weights").unwrap_or("1.0");
-    let weights_arg: f64 = weights_arg_str.parse().unwrap();
-    println!("Weight parameter from argument: {}", weights_arg);
-
-    let n_updates_arg_str = matches.value_of("nupdate").unwrap_or("5");
-    let n_updates_arg: u64 = n_updates_arg_str.parse().unwrap();
-    println!("Number of updates from argument: {}", n_updates_arg);
-
-    let n_measure_arg_str = matches.value_of("nmeasure").unwrap_or("500");
-    let n_measure_arg: u64 = n_measure_arg_str.parse().unwrap();
-    println!("Number of measurements to make per bin: {}", n_measure_arg);
-
-    let n_bins_arg_str = matches.value_of("nbins").unwrap_or("10");
-    let n_bins_arg: u64 = n_bins_arg_str.parse().unwrap();
-    println!("Number of bins: {}", n_bins_arg);
-
-    let write_update_configurations_str = matches.value_of("write-update-confs").unwrap_or("false");
-    let write_update_configurations: bool = write_update_configurations_str.parse().unwrap();
-    println!("Write update configs: {}", write_update_configurations);
-
-    let write_measure_configurations_str = matches.value_of("write-measure-confs").unwrap_or("false");
-    let write_measure_configurations: bool = write_measure_configurations_str.parse().unwrap();
-    println!("Write measure configs: {}", write_measure_configurations);
-
-    let write_bin_configurations_str = matches.value_of("write-bin-confs").unwrap_or("false");
-    let write_bin_configurations: bool = write_bin_configurations_str.parse().unwrap();
-    println!("Write bin configs: {}", write_bin_configurations);
-
-    let write_configuration_style_str: &str = matches.value_of("write-configuration-style").unwrap_or("2");
-    let write_configuration_style: u8 = write_configuration_style_str.parse().unwrap();
-    println!("Write configuration style: {}", write_configuration_style);
-
-    let update_type: &UpdateType = &UpdateType::Local;
-    if matches.is_present("loop-update") {
-        let update_type: &UpdateType = &UpdateType::Walk;
-        println!("Lattice will be updated using random walk.");
-    } else {
-        println!("Lattice will be updated using plaquette flips.");
-    }
-
-    // number_bins: The number of lines in the data file (10000)
-    let number_bins: u64 = n_bins_arg;
-    // number_measure: How many measurements to average over per bin (500)
-    let number_measure: u64 = n_measure_arg;
-    // number_update: How many updated before a measurement (5)
-    let number_update: u64 = n_updates_arg;
-    // for local updates it should be
-    //let number_update: u64 = 2 * lat.size.x * lat.size.y;
-
-    // Initialize the object to update the lattice
-    let mut updater = Update{
-        working_loc: BoundPoint{
-            size: lat.size,
-            location: Point{x: 0, y: 0},
+
+    // Either a square lattice from --size or a rectangular one from --lx and --ly.
+    if matches.is_present("size") || matches.is_present("lx") {
+        config.size = lattice_size_from_matches(matches);
+    }
+    if matches.is_present("modulus") {
+        config.modulus = modulus_from_matches(matches);
+    }
+    if let Some(name) = matches.value_of("initial-state") {
+        config.initial_state = match InitialState::from_name(name) {
+            Ok(initial_state) => initial_state,
+            Err(e) => {
+                eprintln!("{}", e);
+                process::exit(1);
+            }
+        };
+    }
+    if let Some(configuration_path) = matches.value_of("initial-configuration") {
+        config.initial_state = InitialState::File {
+            path: String::from(configuration_path),
+            style: matches.value_of("initial-configuration-style").unwrap_or("2").parse().unwrap(),
+            line: matches.value_of("initial-configuration-line").map(|line_str| line_str.parse().unwrap()),
+        };
+    }
+    if matches.is_present("worm-update") {
+        config.update_type = UpdateType::Worm;
+    } else if matches.is_present("loop-update") {
+        config.update_type = UpdateType::Walk;
+    }
+    if let Some(weights_str) = matches.value_of("weights") {
+        config.weights = weights_str.parse().unwrap();
+    }
+    if let Some(n_updates_str) = matches.value_of("nupdate") {
+        config.nupdate = n_updates_str.parse().unwrap();
+    }
+    if let Some(n_measure_str) = matches.value_of("nmeasure") {
+        config.nmeasure = n_measure_str.parse().unwrap();
+    }
+    if let Some(n_bins_str) = matches.value_of("nbins") {
+        config.nbins = n_bins_str.parse().unwrap();
+    }
+    if let Some(seed_str) = matches.value_of("seed") {
+        config.seed = Some(seed_str.parse().unwrap());
+    }
+    if let Some(paranoid_str) = matches.value_of("paranoid") {
+        config.paranoid = paranoid_str.parse().unwrap();
+    }
+    if let Some(b) = matches.value_of("write-update-confs") {
+        config.write_update_confs = b.parse().unwrap();
+    }
+    if let Some(b) = matches.value_of("write-measure-confs") {
+        config.write_measure_confs = b.parse().unwrap();
+    }
+    if let Some(b) = matches.value_of("write-bin-confs") {
+        config.write_bin_confs = b.parse().unwrap();
+    }
+    if let Some(style_str) = matches.value_of("write-configuration-style") {
+        config.write_configuration_style = style_str.parse().unwrap();
+    }
+    if let Some(every_str) = matches.value_of("checkpoint-every") {
+        config.checkpoint_every = every_str.parse().unwrap();
+    }
+    if let Some(checkpoint_file) = matches.value_of("checkpoint-file") {
+        config.checkpoint_file = String::from(checkpoint_file);
+    }
+    if let Some(resume_path) = matches.value_of("resume") {
+        config.resume = Some(String::from(resume_path));
+    }
+    if let Some(output_dir) = matches.value_of("output-dir") {
+        config.output_dir = String::from(output_dir);
+    }
+    if let Some(gui_str) = matches.value_of("gui") {
+        config.gui = gui_str.parse().unwrap();
+    }
+
+    if let Err(e) = config.check() {
+        eprintln!("Can not use the run configuration: {}", e);
+        process::exit(1);
+    }
+    println!("Lattice {}x{}, Z{}, weight {}, {} bins of {} measurements {} updates apart",
+             config.size.x, config.size.y, config.modulus, config.weights,
+             config.nbins, config.nmeasure, config.nupdate);
+    config
+}
+
+/// The directory from `--output-dir` (the current directory by default), created if needed.
+/// Exits if it can not be created.
+fn output_dir_from_matches(matches: &ArgMatches) -> PathBuf {
+    let output_dir = PathBuf::from(matches.value_of("output-dir").unwrap_or("."));
+    if let Err(e) = fs::create_dir_all(&output_dir) {
+        eprintln!("Can not create output directory {}: {}", output_dir.display(), e);
+        process::exit(1);
+    }
+    println!("Writing results to {}", output_dir.display());
+    output_dir
+}
+
+/// The `replay` subcommand: run the estimators of a live run over saved configurations.
+fn replay(matches: &ArgMatches) {
+    let size = lattice_size_from_matches(matches);
+    let modulus_arg = modulus_from_matches(matches);
+    let configuration_path = matches.value_of("configurations").unwrap_or("lattice_configurations.csv");
+    let n_measure_arg: u64 = matches.value_of("nmeasure").unwrap().parse().unwrap();
+    println!("Replaying {} in bins of {} configurations", configuration_path, n_measure_arg);
+
+    let output = EstimatorOutput::directory(output_dir_from_matches(matches), false);
+
+    let lat = build_blank_zn_lat(size, modulus_arg);
+    let mut density_estimator = DensityEstimator::new(&lat.size, &output);
+    let mut correlation_origin_estimator = CorrelationOriginEstimator::new(&lat.size, &output);
+    let mut total_link_count_estimator = TotalLinkCountEstimator::new(&output);
+    let mut winding_count_estimator = WindingNumberCountEstimator::new(lat.clone(), &output);
+    let mut winding_variance_estimator = WindingNumberVarianceEstimator::new(&output);
+    let mut cluster_size_estimator = FullClusterSizeEstimator::new(&lat, &output);
+
+    let replay_result = replay_configurations(
+        configuration_path,
+        size,
+        modulus_arg,
+        n_measure_arg,
+        &mut [
+            &mut density_estimator,
+            &mut correlation_origin_estimator,
+            &mut total_link_count_estimator,
+            &mut winding_variance_estimator,
+            &mut cluster_size_estimator,
+        ],
+        &mut [&mut winding_count_estimator],
+    );
+    match replay_result {
+        Ok(bins) => println!("Replayed {} bins", bins),
+        Err(e) => {
+            eprintln!("Replay failed: {}", e);
+            process::exit(1);
+        }
+    }
+}
+
+/// The run configuration every point of a sweep (or replica) starts from: `--config` or the
+/// defaults, with the binning and seed flags of the subcommand on top.
+fn base_config_from_matches(matches: &ArgMatches) -> RunConfig {
+    let mut base = match matches.value_of("config") {
+        Some(config_path) => match read_run_config(config_path) {
+            Ok(config) => config,
+            Err(e) => {
+                eprintln!("Can not read the run configuration: {}", e);
+                process::exit(1);
+            }
         },
-        link_number_tuning: weights_arg,
-        link_number_change: 0,
+        None => RunConfig::default(),
     };
+    if let Some(n_bins_str) = matches.value_of("nbins") {
+        base.nbins = n_bins_str.parse().unwrap();
+    }
+    if let Some(n_measure_str) = matches.value_of("nmeasure") {
+        base.nmeasure = n_measure_str.parse().unwrap();
+    }
+    if let Some(n_updates_str) = matches.value_of("nupdate") {
+        base.nupdate = n_updates_str.parse().unwrap();
+    }
+    if let Some(seed_str) = matches.value_of("seed") {
+        base.seed = Some(seed_str.parse().unwrap());
+    }
+    base
+}
 
-    // Initialize the object to measure the string density,
-    let mut density_estimator = DensityEstimator::new(&lat.size);
-    let mut correlation_origin_estimator = CorrelationOriginEstimator::new(&lat.size);
-    let mut total_link_count_estimator = TotalLinkCountEstimator::new();
-    let mut winding_count_estimator = WindingNumberCountEstimator::new(lat.clone());
-    let mut winding_variance_estimator = WindingNumberVarianceEstimator::new();
-    let mut cluster_size_estimator = FullClusterSizeEstimator::new(&lat);
-
-    // Equilibrate
-    if equilibrate {
-        println!("Equilibrating");
-        let equilibration_time = lat.size.x * lat.size.y * 2;
-
-        println!("Number of updates in equilibration: {}", equilibration_time);
-        for _ in 0..equilibration_time {
-            updater.main_update(&mut lat, &update_type);
+/// The `temper` subcommand: parallel tempering over a ladder of weights.
+fn temper(matches: &ArgMatches) {
+    let mut base = base_config_from_matches(matches);
+    if matches.is_present("size") {
+        base.size = lattice_size_from_matches(matches);
+    }
+    let weights = match parse_value_list(matches.value_of("weights").unwrap()) {
+        Ok(weights) => weights,
+        Err(e) => {
+            eprintln!("Can not read the weights: {}", e);
+            process::exit(1);
         }
-        println!("Done equilibrating");
+    };
+    let swap_every: u64 = matches.value_of("swap-every").unwrap_or("1").parse().unwrap();
+    let root = PathBuf::from(matches.value_of("output-dir").unwrap_or("tempering"));
+
+    let mut tempering = match ParallelTempering::new(&base, &weights, &root, swap_every) {
+        Ok(tempering) => tempering,
+        Err(e) => {
+            eprintln!("Can not set up parallel tempering: {}", e);
+            process::exit(1);
+        }
+    };
+    tempering.run();
+    for (pair, (accepted, attempts)) in tempering.swap_accepted().iter()
+        .zip(tempering.swap_attempts()).enumerate() {
+        println!("Swaps between weights {} and {}: {} of {} accepted",
+                 weights[pair], weights[pair + 1], accepted, attempts);
+    }
+}
+
+/// The `sweep` subcommand: one run per lattice size and weight.
+fn sweep(matches: &ArgMatches) {
+    let base = base_config_from_matches(matches);
+    let sizes = match matches.value_of("sizes") {
+        Some(sizes_str) => parse_size_list(sizes_str),
+        None => Ok(vec![base.size]),
+    };
+    let weights = parse_value_list(matches.value_of("weights").unwrap());
+    let threads: usize = matches.value_of("threads").unwrap_or("1").parse().unwrap();
+    let root = PathBuf::from(matches.value_of("output-dir").unwrap_or("sweep"));
+
+    let points = match (sizes, weights) {
+        (Ok(sizes), Ok(weights)) => sweep_points(&base, &sizes, &weights, &root),
+        (Err(e), _) | (_, Err(e)) => Err(e),
+    };
+    let points = match points {
+        Ok(points) => points,
+        Err(e) => {
+            eprintln!("Can not set up the sweep: {}", e);
+            process::exit(1);
+        }
+    };
+    println!("Sweeping {} points on {} threads into {}", points.len(), threads, root.display());
+    write_sweep_index(&root, &points);
+    if let Err(e) = run_sweep(&points, threads) {
+        eprintln!("Some sweep points failed:\n{}", e);
+        process::exit(1);
+    }
+}
+
+/// The `autocorrelation` subcommand: how correlated are consecutive measurements.
+fn autocorrelation(matches: &ArgMatches) {
+    let file = matches.value_of("file").unwrap_or("time_series_estimator.csv");
+    let column = matches.value_of("column").unwrap_or("link_count");
+    let window_factor: f64 = matches.value_of("window-factor").unwrap_or("5").parse().unwrap();
+    let min_bins: usize = matches.value_of("min-bins").unwrap_or("16").parse().unwrap();
+    let series = match read_csv_column(file, column) {
+        Ok(series) => series,
+        Err(e) => {
+            eprintln!("Can not read the time series: {}", e);
+            process::exit(1);
+        }
+    };
+    println!("{} measurements of {} in {}", series.len(), column, file);
+
+    println!("bin_size,number_bins,mean,error");
+    let levels = binning_analysis(&series, min_bins);
+    for level in &levels {
+        println!("{},{},{},{}", level.bin_size, level.number_bins, level.mean, level.error);
+    }
+    if let Some(tau) = binning_tau(&levels) {
+        println!("tau_int from the binning analysis: {}", tau);
+    }
+
+    match integrated_autocorrelation_time(&series, window_factor) {
+        Ok(tau) => {
+            println!("tau_int: {} +- {} (window {})", tau.tau_int, tau.error, tau.window);
+            if !tau.converged {
+                println!("The window never closed, the series is too short for its \
+                          autocorrelation time and tau_int is too small.");
+            }
+            println!("Measurements are about {} apart in independent samples, nmeasure should be \
+                      much larger than that.", 2.0 * tau.tau_int);
+        },
+        Err(e) => println!("No autocorrelation time: {}", e),
     }
+}
 
-    if run_with_gui {
+/// The `analyze` subcommand: error bars of the binned estimator results of a run.
+fn analyze(matches: &ArgMatches) {
+    let dir = PathBuf::from(matches.value_of("directory").unwrap_or("."));
+    let resamples: usize = matches.value_of("bootstrap").unwrap_or("1000").parse().unwrap();
+    let seed: u64 = matches.value_of("seed").unwrap_or("0").parse().unwrap();
+    let discard: usize = matches.value_of("discard").unwrap_or("0").parse().unwrap();
+
+    let mut derived = Vec::new();
+    for spec in matches.values_of("derived").into_iter().flatten() {
+        match parse_derived(spec) {
+            Ok(observable) => derived.push(observable),
+            Err(e) => {
+                eprintln!("Can not read the derived observable {}: {}", spec, e);
+                process::exit(1);
+            }
+        }
+    }
+    let results = read_estimator_bins(&dir)
+        .and_then(|mut observables| {
+            if discard > 0 {
+                observables.discard(discard)?;
+            }
+            analyze_observables(&observables, &derived, resamples, seed)
+        });
+    let results = match results {
+        Ok(results) => results,
+        Err(e) => {
+            eprintln!("Can not analyze {}: {}", dir.display(), e);
+            process::exit(1);
+        }
+    };
+
+    println!("observable,number_bins,mean,jackknife_error,bootstrap_error");
+    for result in results {
+        println!("{},{},{},{},{}",
+                 result.name,
+                 result.number_bins,
+                 result.jackknife.value,
+                 result.jackknife.error,
+                 result.bootstrap.map(|b| b.error.to_string()).unwrap_or_default());
+    }
+}
+
+fn main() {
+    // Parse arguments
+    let yaml = load_yaml!("cli.yml");
+
+    let matches = App::from_yaml(yaml).get_matches();
+
+    if let Some(replay_matches) = matches.subcommand_matches("replay") {
+        replay(replay_matches);
+        return;
+    }
+    if let Some(sweep_matches) = matches.subcommand_matches("sweep") {
+        sweep(sweep_matches);
+        return;
+    }
+    if let Some(temper_matches) = matches.subcommand_matches("temper") {
+        temper(temper_matches);
+        return;
+    }
+    if let Some(autocorrelation_matches) = matches.subcommand_matches("autocorrelation") {
+        autocorrelation(autocorrelation_matches);
+        return;
+    }
+    if let Some(analyze_matches) = matches.subcommand_matches("analyze") {
+        analyze(analyze_matches);
+        return;
+    }
+
+    let config = run_config_from_matches(&matches);
+    let mut simulation = match Simulation::new(config) {
+        Ok(simulation) => simulation,
+        Err(e) => {
+            eprintln!("Can not start the run: {}", e);
+            process::exit(1);
+        }
+    };
+    simulation.equilibrate();
+
+    if simulation.config.gui {
         // Conrod Start
         let mut events_loop = glium::glutin::event_loop::EventLoop::new();
         let window = glium::glutin::window::WindowBuilder::new()
@@ -175,7 +468,11 @@ fn main() {
         // - a `Vec` of commands that describe how to draw the vertices.
         let mut renderer = Renderer::new(&display).unwrap();
         // A demonstration of some app state that we want to control with the conrod GUI.
-        let mut app = DemoApp::new(&lat);
+        let mut app = DemoApp::new(&simulation.lat);
+        // The GUI only shows the winding numbers, nothing is written.
+        let mut winding_count_estimator = WindingNumberCountEstimator::new(
+            simulation.lat.clone(), &EstimatorOutput::Memory(MemoryOutput::new()));
+        let mut lat = simulation.lat;
         // Start the loop:
         //
         // - Send available events to the `Ui`.
@@ -214,7 +511,7 @@ fn main() {
                     }
                 }
                 Request::SetUi { needs_redraw } => {
-                    gui(&mut ui.set_widgets(), &mut ids, &mut app, lattice_size_arg,
+                    gui(&mut ui.set_widgets(), &mut ids, &mut app,
                         &mut lat, &mut winding_count_estimator);
                     // Instantiate a GUI demonstrating every widget type provided by conrod.
                     //conrod_example_shared::gui(&mut ui.set_widgets(), &ids, &mut app);
@@ -234,48 +531,6 @@ fn main() {
         });
         // Conrod End
     } else {
-        // Actual run
-        let mut total_update_count: u64 = 0;
-        for _i in 0..number_bins {
-            println!("Working on bin {}", _i);
-            if write_bin_configurations {
-                write_lattice(String::from(format!("lattice_bin_{}.csv", total_update_count)), &mut lat, write_configuration_style);
-            }
-            for _j in 0..number_measure {
-                //println!("j {}", _j);
-                if write_measure_configurations {
-                    write_lattice(String::from(format!("lattice_measure_{}.csv", total_update_count)), &mut lat, write_configuration_style);
-                }
-                for _k in 0..number_update {
-                    //println!("k {}", _k);
-                    if write_update_configurations {
-                        write_lattice(String::from(format!("lattice_{}.csv", total_update_count)), &mut lat, write_configuration_style);
-                    }
-                    updater.main_update(&mut lat, &update_type);
-                    total_update_count += 1;
-                }
-                density_estimator.measure(&mut lat);
-                correlation_origin_estimator.measure(&mut lat);
-                total_link_count_estimator.measure(&mut lat);
-                winding_variance_estimator.measure(&mut lat);
-                cluster_size_estimator.measure(&mut lat);
-            }
-
-            density_estimator.finalize_bin_and_write(number_measure);
-            correlation_origin_estimator.finalize_bin_and_write(number_measure);
-            total_link_count_estimator.finalize_bin_and_write(number_measure);
-            winding_variance_estimator.finalize_bin_and_write(number_measure);
-            cluster_size_estimator.finalize_bin_and_write(number_measure);
-
-            density_estimator.clear();
-            correlation_origin_estimator.clear();
-            total_link_count_estimator.clear();
-            winding_variance_estimator.clear();
-            cluster_size_estimator.clear();
-
-            winding_count_estimator.measure(&mut lat);
-            winding_count_estimator.finalize_bin_and_write(1);
-        }
+        simulation.run();
     }
-    winding_count_estimator.clear();
 }
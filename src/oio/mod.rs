@@ -1,43 +1,466 @@
 use std::io::prelude::*;
+use std::fs;
 use std::fs::File;
 use std::fs::OpenOptions;
 use std::path::Path;
 use super::datamodel::Link;
+use super::datamodel::Orientation;
 use super::datamodel::Direction;
 use super::datamodel::Vertex;
 use super::datamodel::Point;
+use super::datamodel::Monomer;
 use super::datamodel::BoundPoint;
 use super::datamodel::lattice::Lattice;
+use super::datamodel::lattice::build_blank_zn_lat;
+use super::datamodel::lattice::check_lattice_size;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use datamodel::lattice::build_blank_lat;
+    use datamodel::lattice::build_blank_zn_lat;
+    use lattice_updates::Update;
+    use lattice_updates::UpdateType;
+
+    #[test]
+    fn test_checkpoint_round_trip() {
+        let mut lat: Lattice = build_blank_lat(Point{x: 4, y: 4});
+        let mut updater = Update::new(lat.size, 1.0, 7);
+        for _ in 0..20 {
+            updater.main_update(&mut lat, &UpdateType::Walk);
+        }
+        let checkpoint = Checkpoint {
+            lat: lat.clone(),
+            link_number_tuning: 1.0,
+            seed: 7,
+            rng_seed: 99,
+            bins_completed: 3,
+            total_measure_count: 30,
+            total_update_count: 600,
+            result_files: vec![(String::from("density_estimator.csv"), 1234),
+                               (String::from("lattice_configurations.csv"), 0)],
+        };
+        let path = env::temp_dir().join("z3stringnet_test_checkpoint_round_trip.csv");
+        let f_str = path.to_str().unwrap();
+        write_checkpoint(f_str, &checkpoint);
+        let read_back = match read_checkpoint(f_str) {
+            Ok(c) => c,
+            Err(e) => panic!("Could not read checkpoint back: {}", e),
+        };
+        assert_eq!(format!("{:?}", read_back.lat.vertices), format!("{:?}", lat.vertices));
+        assert_eq!(read_back.lat.size, lat.size);
+        assert_eq!(read_back.lat.number_filled_links, lat.number_filled_links);
+        assert_eq!(read_back.seed, 7);
+        assert_eq!(read_back.rng_seed, 99);
+        assert_eq!(read_back.bins_completed, 3);
+        assert_eq!(read_back.total_measure_count, 30);
+        assert_eq!(read_back.total_update_count, 600);
+        assert_eq!(read_back.result_files, checkpoint.result_files);
+    }
+
+    #[test]
+    fn test_checkpoint_keeps_monomers() {
+        let mut lat: Lattice = build_blank_lat(Point{x: 4, y: 4});
+        lat.insert_monomer_pair(&Point{x: 1, y: 2}, &Direction::E).unwrap();
+        let mut updater = Update::new(lat.size, 1.0, 5);
+        for _ in 0..20 {
+            updater.main_update(&mut lat, &UpdateType::Worm);
+        }
+        let checkpoint = Checkpoint {
+            lat: lat.clone(),
+            link_number_tuning: 1.0,
+            seed: 5,
+            rng_seed: 6,
+            bins_completed: 1,
+            total_measure_count: 10,
+            total_update_count: 20,
+            result_files: Vec::new(),
+        };
+        let path = env::temp_dir().join("z3stringnet_test_checkpoint_monomers.csv");
+        let f_str = path.to_str().unwrap();
+        write_checkpoint(f_str, &checkpoint);
+        let read_back = read_checkpoint(f_str).unwrap();
+        assert_eq!(read_back.lat.monomers, lat.monomers);
+        assert_eq!(format!("{:?}", read_back.lat.vertices), format!("{:?}", lat.vertices));
+    }
+
+    #[test]
+    fn test_checkpoint_rejects_bad_vertices() {
+        let lat: Lattice = build_blank_lat(Point{x: 4, y: 4});
+        let checkpoint = Checkpoint {
+            lat,
+            link_number_tuning: 1.0,
+            seed: 1,
+            rng_seed: 2,
+            bins_completed: 1,
+            total_measure_count: 10,
+            total_update_count: 20,
+            result_files: Vec::new(),
+        };
+        let path = env::temp_dir().join("z3stringnet_test_checkpoint_bad_vertices.csv");
+        let f_str = path.to_str().unwrap();
+        write_checkpoint(f_str, &checkpoint);
+        let contents = fs::read_to_string(&path).unwrap();
+        let vertices_start = contents.find("x,y,N,E,S,W\n").unwrap() + "x,y,N,E,S,W\n".len();
+        let (header, vertex_lines) = contents.split_at(vertices_start);
+        let mut lines: Vec<String> = vertex_lines.lines().map(String::from).collect();
+
+        // 259 would wrap around to a valid 3 in a u8 cast.
+        let mut too_large = lines.clone();
+        too_large[0] = too_large[0].rsplitn(2, ',').last().unwrap().to_string() + ",259";
+        fs::write(&path, format!("{}{}\n", header, too_large.join("\n"))).unwrap();
+        assert!(read_checkpoint(f_str).is_err());
+
+        // Every vertex is fine on its own but the first two are listed in the wrong order.
+        lines.swap(0, 1);
+        fs::write(&path, format!("{}{}\n", header, lines.join("\n"))).unwrap();
+        assert!(read_checkpoint(f_str).err().unwrap().contains("is listed where"));
+    }
+
+    fn updated_lattice(size: Point, modulus: u8, seed: u64) -> Lattice {
+        let mut lat: Lattice = build_blank_zn_lat(size, modulus);
+        let mut updater = Update::new(lat.size, 1.0, seed);
+        for _ in 0..30 {
+            updater.main_update(&mut lat, &UpdateType::Worm);
+        }
+        lat
+    }
+
+    #[test]
+    fn test_style_2_round_trip() {
+        for &modulus in [2, 3, 4].iter() {
+            let lat = updated_lattice(Point{x: 6, y: 4}, modulus, 17);
+            let line = lattice_to_style_2_line(&lat);
+            let read_back = lattice_from_style_2_line(&line, lat.size, modulus).unwrap();
+            assert_eq!(format!("{:?}", read_back.vertices), format!("{:?}", lat.vertices));
+            assert_eq!(read_back.number_filled_links, lat.number_filled_links);
+        }
+    }
+
+    #[test]
+    fn test_style_2_line_selection() {
+        let first = updated_lattice(Point{x: 4, y: 4}, 3, 1);
+        let second = updated_lattice(Point{x: 4, y: 4}, 3, 2);
+        let path = env::temp_dir().join("z3stringnet_test_style_2_line_selection.csv");
+        let f_str = path.to_str().unwrap();
+        {
+            let mut file = File::create(f_str).unwrap();
+            file.write_all(lattice_to_style_2_line(&first).as_bytes()).unwrap();
+            file.write_all(lattice_to_style_2_line(&second).as_bytes()).unwrap();
+        }
+        let read_first = read_lattice_style_2(f_str, first.size, 3, Some(0)).unwrap();
+        let read_last = read_lattice_style_2(f_str, first.size, 3, None).unwrap();
+        assert_eq!(format!("{:?}", read_first.vertices), format!("{:?}", first.vertices));
+        assert_eq!(format!("{:?}", read_last.vertices), format!("{:?}", second.vertices));
+        assert_eq!(read_lattices_style_2(f_str, first.size, 3).unwrap().len(), 2);
+        assert!(read_lattice_style_2(f_str, first.size, 3, Some(2)).is_err());
+    }
+
+    #[test]
+    fn test_style_2_rejects_broken_configuration() {
+        let lat = build_blank_zn_lat(Point{x: 4, y: 4}, 3);
+        let mut line = lattice_to_style_2_line(&lat);
+        // Fill a single link, which leaves a charge on both of its ends.
+        line.replace_range(0..1, "1");
+        assert!(lattice_from_style_2_line(&line, lat.size, 3).is_err());
+        assert!(lattice_from_style_2_line("0,1,2", lat.size, 3).is_err());
+    }
+
+    #[test]
+    fn test_style_1_round_trip() {
+        let lat = updated_lattice(Point{x: 4, y: 6}, 3, 23);
+        let path = env::temp_dir().join("z3stringnet_test_style_1.csv");
+        write_lattice_style_1(String::from(path.to_str().unwrap()), &lat);
+        let vertex_path = env::temp_dir().join("vertex_z3stringnet_test_style_1.csv");
+        let read_back = read_lattice_style_1(vertex_path.to_str().unwrap(), 3).unwrap();
+        assert_eq!(read_back.size, lat.size);
+        assert_eq!(format!("{:?}", read_back.vertices), format!("{:?}", lat.vertices));
+        assert_eq!(read_back.number_filled_links, lat.number_filled_links);
+    }
+}
+
+/// Everything needed to continue a run where it stopped.
+///
+/// Checkpoints are only taken at bin boundaries, after the estimators have finalized, written,
+/// and cleared their bin. At that point no estimator holds any partially accumulated state (it
+/// is all in the estimator files, which are flushed before the checkpoint is written) so the
+/// lattice, the generator and the counters are enough to resume, along with the length of
+/// every result file so anything written after the checkpoint can be dropped.
+///
+/// The random number generator is captured by `rng_seed`, see `Update::restart_rng`.
+#[derive(Debug, Clone)]
+pub struct Checkpoint {
+    pub lat: Lattice,
+    pub link_number_tuning: f64,
+    /// The seed the run was originally started with.
+    pub seed: u64,
+    /// The seed the generator was restarted from when this checkpoint was taken.
+    pub rng_seed: u64,
+    pub bins_completed: u64,
+    pub total_measure_count: u64,
+    pub total_update_count: u64,
+    /// Length in bytes of every result file of the run when the checkpoint was taken, by file
+    /// name in the output directory. Resuming cuts the files back to these lengths, which drops
+    /// whatever the stopped run wrote after the checkpoint.
+    pub result_files: Vec<(String, u64)>,
+}
+
+/// Integer used for a link in configuration and checkpoint files. This is the link value so
+/// for Z3 it is `Out` 1, `In` 2, `Blank` 0.
+pub fn link_to_int(link: &Link) -> u8 {
+    link.value()
+}
+
+/// Inverse of `link_to_int` for a lattice with the given modulus.
+pub fn int_to_link(value: u8, modulus: u8) -> Result<Link, String> {
+    if value < modulus {
+        Ok(Link::new(value, modulus))
+    }
+    else {
+        Err(format!("{} is not a valid link value for Z{}", value, modulus))
+    }
+}
+
+/// Write the checkpoint to `f_str`. The file is first written next to the target and then
+/// moved into place so an interrupted write never leaves a broken checkpoint behind.
+pub fn write_checkpoint(f_str: &str, checkpoint: &Checkpoint) {
+    let lat = &checkpoint.lat;
+    let mut out_string = String::new();
+    out_string.push_str("checkpoint_version,4\n");
+    out_string.push_str(&format!("size_x,{}\n", lat.size.x));
+    out_string.push_str(&format!("size_y,{}\n", lat.size.y));
+    out_string.push_str(&format!("modulus,{}\n", lat.modulus));
+    out_string.push_str(&format!("number_filled_links,{}\n", lat.number_filled_links));
+    out_string.push_str(&format!("link_number_tuning,{}\n", checkpoint.link_number_tuning));
+    out_string.push_str(&format!("seed,{}\n", checkpoint.seed));
+    out_string.push_str(&format!("rng_seed,{}\n", checkpoint.rng_seed));
+    out_string.push_str(&format!("bins_completed,{}\n", checkpoint.bins_completed));
+    out_string.push_str(&format!("total_measure_count,{}\n", checkpoint.total_measure_count));
+    out_string.push_str(&format!("total_update_count,{}\n", checkpoint.total_update_count));
+    out_string.push_str(&format!("monomers,{}\n", lat.monomers.len()));
+    for monomer in &lat.monomers {
+        out_string.push_str(&format!("{},{},{}\n", monomer.location.x, monomer.location.y, monomer.charge));
+    }
+    out_string.push_str(&format!("result_files,{}\n", checkpoint.result_files.len()));
+    for &(ref name, length) in &checkpoint.result_files {
+        out_string.push_str(&format!("{},{}\n", name, length));
+    }
+    out_string.push_str("vertices\n");
+    out_string.push_str("x,y,N,E,S,W\n");
+    for vertex in &lat.vertices {
+        out_string.push_str(
+            &format!("{},{},{},{},{},{}\n",
+                vertex.xy.x,
+                vertex.xy.y,
+                link_to_int(&vertex.n),
+                link_to_int(&vertex.e),
+                link_to_int(&vertex.s),
+                link_to_int(&vertex.w),
+            )
+        );
+    }
+
+    let tmp_f_str = format!("{}.tmp", f_str);
+    {
+        let mut file = match File::create(&tmp_f_str) {
+            Ok(f) => f,
+            Err(e) => panic!("Problem creating checkpoint file {}: {}", tmp_f_str, e)
+        };
+        match file.write_all(out_string.as_bytes()) {
+            Ok(()) => (),
+            Err(e) => panic!("Problem writing checkpoint {}: {}", tmp_f_str, e)
+        }
+    }
+    match fs::rename(&tmp_f_str, f_str) {
+        Ok(()) => println!("Wrote checkpoint to {}", f_str),
+        Err(e) => panic!("Problem moving checkpoint into place at {}: {}", f_str, e)
+    }
+}
+
+fn parse_checkpoint_value<T: ::std::str::FromStr>(line: Option<&str>, key: &str) -> Result<T, String> {
+    let line = match line {
+        Some(l) => l,
+        None => return Err(format!("Checkpoint ended before {}", key)),
+    };
+    let mut parts = line.split(',');
+    match (parts.next(), parts.next()) {
+        (Some(found_key), Some(value)) if found_key == key => match value.trim().parse() {
+            Ok(v) => Ok(v),
+            Err(_) => Err(format!("Could not parse value of {} in checkpoint: {}", key, value)),
+        },
+        _ => Err(format!("Expected {} in checkpoint but found: {}", key, line)),
+    }
+}
+
+fn read_file_to_string(f_str: &str) -> Result<String, String> {
+    let mut contents = String::new();
+    match File::open(f_str) {
+        Ok(mut f) => match f.read_to_string(&mut contents) {
+            Ok(_) => Ok(contents),
+            Err(e) => Err(format!("Problem reading {}: {}", f_str, e)),
+        },
+        Err(e) => Err(format!("Problem opening {}: {}", f_str, e)),
+    }
+}
+
+/// Read a checkpoint written by `write_checkpoint`.
+pub fn read_checkpoint(f_str: &str) -> Result<Checkpoint, String> {
+    let contents = read_file_to_string(f_str)?;
+    let mut lines = contents.lines();
+
+    let version: u64 = parse_checkpoint_value(lines.next(), "checkpoint_version")?;
+    if !(1..=4).contains(&version) {
+        return Err(format!("Unknown checkpoint version {}", version));
+    }
+    let size = Point {
+        x: parse_checkpoint_value(lines.next(), "size_x")?,
+        y: parse_checkpoint_value(lines.next(), "size_y")?,
+    };
+    // Version 1 checkpoints were written before other ZN models existed and are all Z3.
+    let modulus: u8 = if version == 1 {
+        3
+    } else {
+        parse_checkpoint_value(lines.next(), "modulus")?
+    };
+    let number_filled_links: i64 = parse_checkpoint_value(lines.next(), "number_filled_links")?;
+    let link_number_tuning: f64 = parse_checkpoint_value(lines.next(), "link_number_tuning")?;
+    let seed: u64 = parse_checkpoint_value(lines.next(), "seed")?;
+    let rng_seed: u64 = parse_checkpoint_value(lines.next(), "rng_seed")?;
+    let bins_completed: u64 = parse_checkpoint_value(lines.next(), "bins_completed")?;
+    let total_measure_count: u64 = parse_checkpoint_value(lines.next(), "total_measure_count")?;
+    let total_update_count: u64 = parse_checkpoint_value(lines.next(), "total_update_count")?;
+    // Monomers came with version 3, older checkpoints are closed string nets.
+    let mut monomers: Vec<Monomer> = Vec::new();
+    if version >= 3 {
+        let number_monomers: usize = parse_checkpoint_value(lines.next(), "monomers")?;
+        for _ in 0..number_monomers {
+            let line = match lines.next() {
+                Some(l) => l,
+                None => return Err(String::from("Checkpoint ended in the monomers section")),
+            };
+            let numbers: Vec<i64> = match line.split(',').map(|v| v.trim().parse()).collect() {
+                Ok(n) => n,
+                Err(_) => return Err(format!("Bad monomer line in checkpoint: {}", line)),
+            };
+            if numbers.len() != 3 {
+                return Err(format!("Bad monomer line in checkpoint: {}", line));
+            }
+            monomers.push(Monomer {location: Point {x: numbers[0], y: numbers[1]}, charge: numbers[2]});
+        }
+    }
+    // Result file lengths came with version 4. Older checkpoints leave the files as they are.
+    let mut result_files: Vec<(String, u64)> = Vec::new();
+    if version >= 4 {
+        let number_result_files: usize = parse_checkpoint_value(lines.next(), "result_files")?;
+        for _ in 0..number_result_files {
+            let line = match lines.next() {
+                Some(l) => l,
+                None => return Err(String::from("Checkpoint ended in the result files section")),
+            };
+            // File names do not contain commas, so the length is after the last one.
+            match line.rfind(',').map(|i| (&line[..i], line[i + 1..].trim().parse::<u64>())) {
+                Some((name, Ok(length))) if !name.is_empty() => result_files.push((String::from(name), length)),
+                _ => return Err(format!("Bad result file line in checkpoint: {}", line)),
+            }
+        }
+    }
+    match (lines.next(), lines.next()) {
+        (Some("vertices"), Some("x,y,N,E,S,W")) => (),
+        _ => return Err(String::from("Checkpoint is missing the vertices section")),
+    }
+
+    let mut vertices: Vec<Vertex> = Vec::new();
+    for line in lines {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let values: Vec<&str> = line.split(',').map(|v| v.trim()).collect();
+        if values.len() != 6 {
+            return Err(format!("Bad vertex line in checkpoint: {}", line));
+        }
+        let (x, y): (i64, i64) = match (values[0].parse(), values[1].parse()) {
+            (Ok(x), Ok(y)) => (x, y),
+            _ => return Err(format!("Bad vertex line in checkpoint: {}", line)),
+        };
+        // Parsed straight into u8 so an out of range link value is an error, not a wrap around.
+        let mut links: Vec<Link> = Vec::new();
+        for value in &values[2..] {
+            match value.parse() {
+                Ok(v) => links.push(int_to_link(v, modulus)?),
+                Err(_) => return Err(format!("Bad vertex line in checkpoint: {}", line)),
+            }
+        }
+        vertices.push(Vertex {n: links[0], e: links[1], s: links[2], w: links[3], xy: Point {x, y}});
+    }
+    if vertices.len() as i64 != (size.x * size.y) / 2 {
+        return Err(format!("Checkpoint has {} vertices but a {}x{} lattice needs {}",
+                           vertices.len(), size.x, size.y, (size.x * size.y) / 2));
+    }
+    check_lattice_size(&size)?;
+    // The lattice finds a vertex by its position in the list, so the positions have to be the
+    // ones a freshly built lattice has.
+    let blank = build_blank_zn_lat(size, modulus);
+    for (vertex, expected) in vertices.iter().zip(blank.vertices.iter()) {
+        if vertex.xy != expected.xy {
+            return Err(format!("Checkpoint vertex at ({}, {}) is listed where ({}, {}) belongs",
+                               vertex.xy.x, vertex.xy.y, expected.xy.x, expected.xy.y));
+        }
+    }
+
+    let lat = Lattice {
+        vertices,
+        size,
+        modulus,
+        number_filled_links,
+        monomers,
+    };
+    let report = lat.validate();
+    if !report.is_valid() {
+        return Err(format!("Checkpoint lattice is not a valid string net:\n{}", report));
+    }
+
+    Ok(Checkpoint {
+        lat,
+        link_number_tuning,
+        seed,
+        rng_seed,
+        bins_completed,
+        total_measure_count,
+        total_update_count,
+        result_files,
+    })
+}
 
 /// real_bool: If true this is link from a real vertex (lower left of plaquett)
 /// If false this is link from a fake vertex (upper right of plaquett)
 fn get_plaquett_out_string_from_link<'a>(link: &'a Link, real_bool: bool, direction: &'a Direction) -> &'a str {
     if real_bool {
         match direction {
-            &Direction::N => match link{
-                &Link::In => "S",
-                &Link::Out => "N",
-                &Link::Blank => "B",
+            &Direction::N => match link.orientation() {
+                Orientation::In => "S",
+                Orientation::Out => "N",
+                Orientation::Blank => "B",
             },
-            &Direction::E => match link {
-                &Link::In => "W",
-                &Link::Out => "E",
-                &Link::Blank => "B",
+            &Direction::E => match link.orientation() {
+                Orientation::In => "W",
+                Orientation::Out => "E",
+                Orientation::Blank => "B",
             }
             _ => panic!("Unexpected direction for determining plaquett string.")
         }
     }
     else {
         match direction {
-            &Direction::S => match link{
-                &Link::In => "N",
-                &Link::Out => "S",
-                &Link::Blank => "B",
+            &Direction::S => match link.orientation() {
+                Orientation::In => "N",
+                Orientation::Out => "S",
+                Orientation::Blank => "B",
             },
-            &Direction::W => match link {
-                &Link::In => "E",
-                &Link::Out => "W",
-                &Link::Blank => "B",
+            &Direction::W => match link.orientation() {
+                Orientation::In => "E",
+                Orientation::Out => "W",
+                Orientation::Blank => "B",
             }
             _ => panic!("Unexpected direction for determining plaquett string.")
         }
@@ -45,10 +468,10 @@ fn get_plaquett_out_string_from_link<'a>(link: &'a Link, real_bool: bool, direct
 }
 
 fn get_out_string_from_link(link: &Link) -> &str {
-    match link{
-        &Link::In => "In",
-        &Link::Out => "Out",
-        &Link::Blank => "Blank",
+    match link.orientation() {
+        Orientation::In => "In",
+        Orientation::Out => "Out",
+        Orientation::Blank => "Blank",
     }
 }
 
@@ -74,22 +497,47 @@ pub fn increment_loc(direction: &Direction, location_in: &BoundPoint) -> BoundPo
     }
 }
 
-pub fn write_lattice(f_str: String, lat: &mut Lattice, style: u8) {
+/// Inverse of `get_out_string_from_link`.
+fn get_link_from_out_string(link_str: &str, modulus: u8) -> Result<Link, String> {
+    match link_str.trim() {
+        "In" => Ok(Link::in_link(modulus)),
+        "Out" => Ok(Link::out_link(modulus)),
+        "Blank" => Ok(Link::blank(modulus)),
+        _ => Err(format!("{} is not a valid link", link_str)),
+    }
+}
+
+/// `prefix` joined to the file name of `f_str` with an `_`, keeping any directories in front.
+fn prefixed_file_name(prefix: &str, f_str: &str) -> String {
+    let path = Path::new(f_str);
+    match path.file_name() {
+        Some(file_name) => path.with_file_name(
+            format!("{}_{}", prefix, file_name.to_string_lossy())
+        ).to_string_lossy().into_owned(),
+        None => format!("{}_{}", prefix, f_str),
+    }
+}
+
+/// Write `lat` to `dir`. Style 1 writes the `vertex_` and `plaquett_` files named after
+/// `f_str`, style 2 adds a line to `lattice_configurations.csv` and style 0 does both.
+pub fn write_lattice(dir: &Path, f_str: String, lat: &mut Lattice, style: u8) {
+    let style_1_f_str = dir.join(f_str).to_string_lossy().into_owned();
+    let style_2_f_str = dir.join("lattice_configurations.csv").to_string_lossy().into_owned();
     if style == 1 {
-        write_lattice_style_1(f_str, lat);
+        write_lattice_style_1(style_1_f_str, lat);
     }
     else if style == 2 {
-        write_lattice_style_2(lat);
+        write_lattice_style_2(&style_2_f_str, lat);
     }
     else if style == 0 {
-        write_lattice_style_1(f_str, lat);
-        write_lattice_style_2(lat);
+        write_lattice_style_1(style_1_f_str, lat);
+        write_lattice_style_2(&style_2_f_str, lat);
     }
 }
 
-pub fn write_lattice_style_2(lat: &mut Lattice) {
-    let file_and_path = Path::new("lattice_configurations.csv");
-    let mut line_out_str= String::new();
+/// Add `lat` as a line to the style 2 file `f_str`, creating it if needed.
+pub fn write_lattice_style_2(f_str: &str, lat: &mut Lattice) {
+    let file_and_path = Path::new(f_str);
 
     if !Path::new(&file_and_path).exists() {
         let mut file_obj = match File::create(&file_and_path) {
@@ -103,6 +551,17 @@ pub fn write_lattice_style_2(lat: &mut Lattice) {
         Err(e) => panic!("Problem creating/opening file to write configurations: {}", e)
     };
 
+    let line_out_str = lattice_to_style_2_line(lat);
+
+    match file_obj.write_all(line_out_str.as_bytes()) {
+        Ok(()) => println!("Wrote configuration to file (2)"),
+        Err(_) => panic!("Problem writing configuration (2)")
+    }
+}
+
+/// One line of a style 2 configuration file, newline included.
+pub fn lattice_to_style_2_line(lat: &Lattice) -> String {
+    let mut line_out_str= String::new();
     for y in 0..lat.size.y {
         for x in 0..lat.size.x {
 
@@ -123,33 +582,180 @@ pub fn write_lattice_style_2(lat: &mut Lattice) {
             line_out_str.push_str(
                 &format!(
                     "{},{}{}",
-                     match current_vertex.e{
-                         Link::In => 2,
-                         Link::Out => 1,
-                         Link::Blank => 0,
-                     },
-                     match current_vertex.n{
-                         Link::In => 2,
-                         Link::Out => 1,
-                         Link::Blank => 0,
-                     },
+                     link_to_int(&current_vertex.e),
+                     link_to_int(&current_vertex.n),
                     final_comma_str
                 )
             )
         }
     }
     line_out_str.push_str("\n");
+    line_out_str
+}
 
-    match file_obj.write_all(line_out_str.as_bytes()) {
-        Ok(()) => println!("Wrote configuration to file (2)"),
-        Err(_) => panic!("Problem writing configuration (2)")
+/// Build a lattice from one line of a style 2 configuration file.
+///
+/// The line only holds the E and N link of every vertex so the size and the modulus have to
+/// be passed in. The W and S links of the stored vertices are the E and N links of the
+/// vertices to their west and south, seen from the other end.
+pub fn lattice_from_style_2_line(line: &str, size: Point, modulus: u8) -> Result<Lattice, String> {
+    let mut values: Vec<u8> = Vec::new();
+    for value in line.trim().split(',') {
+        match value.trim().parse() {
+            Ok(v) => values.push(v),
+            Err(_) => return Err(format!("Could not parse link value {} in configuration", value)),
+        }
+    }
+    if values.len() as i64 != 2 * size.x * size.y {
+        return Err(format!("Configuration has {} link values but a {}x{} lattice needs {}",
+                           values.len(), size.x, size.y, 2 * size.x * size.y));
+    }
+    // (E, N) outward from the vertex at `p`.
+    let east_north = |p: &BoundPoint| -> Result<(Link, Link), String> {
+        let i = 2 * (p.location.y * size.x + p.location.x) as usize;
+        Ok((int_to_link(values[i], modulus)?, int_to_link(values[i + 1], modulus)?))
+    };
+
+    let mut lat = build_blank_zn_lat(size, modulus);
+    for vertex in lat.vertices.iter_mut() {
+        let here = BoundPoint {size, location: vertex.xy};
+        let (e, n) = east_north(&here)?;
+        let (west_neighbour_e, _) = east_north(&(&here + Point {x: -1, y: 0}))?;
+        let (_, south_neighbour_n) = east_north(&(&here + Point {x: 0, y: -1}))?;
+        vertex.e = e;
+        vertex.n = n;
+        vertex.w = west_neighbour_e.flip();
+        vertex.s = south_neighbour_n.flip();
+    }
+    lat.number_filled_links = lat.count_non_blank_links() as i64;
+
+    let report = lat.validate();
+    if !report.is_valid() {
+        return Err(format!("Configuration is not a valid string net:\n{}", report));
+    }
+    Ok(lat)
+}
+
+/// Read every configuration in a style 2 file, in the order they were written.
+pub fn read_lattices_style_2(f_str: &str, size: Point, modulus: u8) -> Result<Vec<Lattice>, String> {
+    let contents = read_file_to_string(f_str)?;
+    let mut lattices: Vec<Lattice> = Vec::new();
+    for (line_number, line) in contents.lines().filter(|l| !l.trim().is_empty()).enumerate() {
+        match lattice_from_style_2_line(line, size, modulus) {
+            Ok(lat) => lattices.push(lat),
+            Err(e) => return Err(format!("{} configuration {}: {}", f_str, line_number, e)),
+        }
+    }
+    Ok(lattices)
+}
+
+/// Read a single configuration from a style 2 file. `line_number` counts configurations (blank
+/// lines are skipped) from 0; `None` picks the last configuration in the file.
+pub fn read_lattice_style_2(f_str: &str, size: Point, modulus: u8, line_number: Option<usize>)
+    -> Result<Lattice, String> {
+    let contents = read_file_to_string(f_str)?;
+    let lines: Vec<&str> = contents.lines().filter(|l| !l.trim().is_empty()).collect();
+    if lines.is_empty() {
+        return Err(format!("{} holds no configurations", f_str));
+    }
+    let line_number = line_number.unwrap_or(lines.len() - 1);
+    match lines.get(line_number) {
+        Some(line) => match lattice_from_style_2_line(line, size, modulus) {
+            Ok(lat) => Ok(lat),
+            Err(e) => Err(format!("{} configuration {}: {}", f_str, line_number, e)),
+        },
+        None => Err(format!("{} only holds {} configurations, can not read configuration {}",
+                            f_str, lines.len(), line_number)),
+    }
+}
+
+/// Build a lattice from the contents of a style 1 `vertex_` file.
+///
+/// The file lists the vertices of both sublattices with their links as `In`, `Out` or `Blank`.
+/// That is all there is to a Z2 or Z3 link, for larger N the file does not hold enough to
+/// rebuild the lattice so those are refused. The size is taken from the largest coordinates.
+/// The vertices of the second sublattice are checked against the ones that were rebuilt.
+pub fn lattice_from_style_1_vertex_string(contents: &str, modulus: u8) -> Result<Lattice, String> {
+    if modulus > 3 {
+        return Err(format!("Style 1 files only store the orientation of a link, Z{} needs more", modulus));
+    }
+    let mut lines = contents.lines();
+    match lines.next() {
+        Some("x,y,N,E,S,W") => (),
+        _ => return Err(String::from("Vertex configuration is missing the x,y,N,E,S,W header")),
+    }
+    let mut listed_vertices: Vec<Vertex> = Vec::new();
+    for line in lines {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let values: Vec<&str> = line.split(',').collect();
+        if values.len() != 6 {
+            return Err(format!("Bad vertex line in configuration: {}", line));
+        }
+        let x: i64 = match values[0].trim().parse() {
+            Ok(v) => v,
+            Err(_) => return Err(format!("Bad vertex line in configuration: {}", line)),
+        };
+        let y: i64 = match values[1].trim().parse() {
+            Ok(v) => v,
+            Err(_) => return Err(format!("Bad vertex line in configuration: {}", line)),
+        };
+        listed_vertices.push(Vertex {
+            n: get_link_from_out_string(values[2], modulus)?,
+            e: get_link_from_out_string(values[3], modulus)?,
+            s: get_link_from_out_string(values[4], modulus)?,
+            w: get_link_from_out_string(values[5], modulus)?,
+            xy: Point {x, y},
+        });
+    }
+    let size = Point {
+        x: listed_vertices.iter().map(|v| v.xy.x).max().unwrap_or(-1) + 1,
+        y: listed_vertices.iter().map(|v| v.xy.y).max().unwrap_or(-1) + 1,
+    };
+    if listed_vertices.len() as i64 != size.x * size.y {
+        return Err(format!("Configuration has {} vertices but a {}x{} lattice needs {}",
+                           listed_vertices.len(), size.x, size.y, size.x * size.y));
+    }
+    check_lattice_size(&size)?;
+
+    let mut lat = build_blank_zn_lat(size, modulus);
+    for listed in &listed_vertices {
+        if lat.point_real(&listed.xy) {
+            let vloc = lat.get_vector_location_of_vertex(&listed.xy) as usize;
+            lat.vertices[vloc] = *listed;
+        }
+    }
+    lat.number_filled_links = lat.count_non_blank_links() as i64;
+    for listed in &listed_vertices {
+        let rebuilt = lat.get_vertex_from_point(&BoundPoint {size, location: listed.xy});
+        if (rebuilt.n, rebuilt.e, rebuilt.s, rebuilt.w) != (listed.n, listed.e, listed.s, listed.w) {
+            return Err(format!("Vertex x: {}, y: {} does not agree with its neighbours",
+                               listed.xy.x, listed.xy.y));
+        }
+    }
+
+    let report = lat.validate();
+    if !report.is_valid() {
+        return Err(format!("Configuration is not a valid string net:\n{}", report));
+    }
+    Ok(lat)
+}
+
+/// Read a style 1 configuration. `f_str` is the `vertex_` file, the `plaquett_` file holds the
+/// same links and is not needed.
+pub fn read_lattice_style_1(f_str: &str, modulus: u8) -> Result<Lattice, String> {
+    let contents = read_file_to_string(f_str)?;
+    match lattice_from_style_1_vertex_string(&contents, modulus) {
+        Ok(lat) => Ok(lat),
+        Err(e) => Err(format!("{}: {}", f_str, e)),
     }
 }
 
 /// Style 1 <- read the cli.yml file for verbose description
 pub fn write_lattice_style_1(f_str: String, lat: &Lattice) {
-    let vertex_f_str: String = format!("{}_{}", "vertex", f_str);
-    let plaquett_f_str: String = format!("{}_{}", "plaquett", f_str);
+    let vertex_f_str: String = prefixed_file_name("vertex", &f_str);
+    let plaquett_f_str: String = prefixed_file_name("plaquett", &f_str);
     let vertex_path = Path::new(&vertex_f_str);
     let plaquett_path = Path::new(&plaquett_f_str);
 
@@ -266,6 +872,33 @@ pub fn write_lattice_style_1(f_str: String, lat: &Lattice) {
 
 }
 
+/// Write the parameters of a run as `parameter,value` lines. This is where the seed of the
+/// random number generator is recorded so any run can be reproduced.
+pub fn write_run_parameters(f_str: String, parameters: &Vec<(String, String)>) {
+    let path = Path::new(&f_str);
+    let display = path.display();
+
+    let mut file = match File::create(&path){
+        Err(err) => panic!("could not create {}: {}",
+                           display,
+                           err),
+        Ok(good_file) => good_file,
+    };
+
+    let mut out_string = String::new();
+    out_string.push_str("parameter,value\n");
+    for (name, value) in parameters {
+        out_string.push_str(&format!("{},{}\n", name, value));
+    }
+
+    match file.write_all(out_string.as_bytes()){
+        Err(err) => panic!("could not write {}: {}",
+                           display,
+                           err),
+        Ok(_) => println!("Wrote run parameters to {}", display),
+    }
+}
+
 pub fn write_vec(f_str: String, vec: &Vec<u8>) {
     let path = Path::new(&f_str);
     let display = path.display();
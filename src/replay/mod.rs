@@ -0,0 +1,179 @@
+use std::fs::File;
+use std::io::BufReader;
+use std::io::prelude::*;
+use super::datamodel::Point;
+use super::datamodel::lattice::Lattice;
+use super::estimators::Measurable;
+use super::oio::lattice_from_style_2_line;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use datamodel::lattice::build_blank_zn_lat;
+    use lattice_updates::Update;
+    use lattice_updates::UpdateType;
+    use oio::lattice_to_style_2_line;
+
+    /// Keeps the link count of every measurement and the averages it was asked to write.
+    struct LinkCountRecorder {
+        measured: Vec<i64>,
+        current_sum: i64,
+        written: Vec<f64>,
+        clears: u64,
+    }
+    impl LinkCountRecorder {
+        fn new() -> LinkCountRecorder {
+            LinkCountRecorder {measured: Vec::new(), current_sum: 0, written: Vec::new(), clears: 0}
+        }
+    }
+    impl Measurable for LinkCountRecorder {
+        fn measure(&mut self, lat: &mut Lattice) {
+            self.measured.push(lat.number_filled_links);
+            self.current_sum += lat.number_filled_links;
+        }
+        fn finalize_bin_and_write(&mut self, denominator: u64) {
+            self.written.push(self.current_sum as f64 / denominator as f64);
+        }
+        fn clear(&mut self) {
+            self.current_sum = 0;
+            self.clears += 1;
+        }
+        fn flush(&mut self) {}
+    }
+
+    #[test]
+    fn test_replay_bins_like_a_live_run() {
+        let size = Point {x: 4, y: 4};
+        let mut lat: Lattice = build_blank_zn_lat(size, 3);
+        let mut updater = Update::new(size, 1.0, 31);
+        let mut live = LinkCountRecorder::new();
+        let mut live_bin = LinkCountRecorder::new();
+        let path = env::temp_dir().join("z3stringnet_test_replay.csv");
+        let f_str = path.to_str().unwrap();
+        {
+            let mut file = File::create(f_str).unwrap();
+            // 3 full bins of 4 measurements and a partial bin that is never finalized.
+            for i in 0..14 {
+                updater.main_update(&mut lat, &UpdateType::Worm);
+                file.write_all(lattice_to_style_2_line(&lat).as_bytes()).unwrap();
+                if i < 12 {
+                    live.measure(&mut lat);
+                    if i % 4 == 3 {
+                        live.finalize_bin_and_write(4);
+                        live.clear();
+                        live_bin.measure(&mut lat);
+                        live_bin.finalize_bin_and_write(1);
+                        live_bin.clear();
+                    }
+                }
+            }
+        }
+
+        let mut replayed = LinkCountRecorder::new();
+        let mut replayed_bin = LinkCountRecorder::new();
+        let bins = replay_configurations(
+            f_str, size, 3, 4, &mut [&mut replayed], &mut [&mut replayed_bin]
+        ).unwrap();
+        assert_eq!(bins, 3);
+        // The leftover configurations are measured but never make it into a bin.
+        assert_eq!(replayed.measured.len(), 14);
+        assert_eq!(replayed.measured[..12].to_vec(), live.measured);
+        assert_eq!(replayed.written, live.written);
+        assert_eq!(replayed_bin.written, live_bin.written);
+        assert_eq!(replayed.clears, 3);
+    }
+
+    #[test]
+    fn test_replay_reports_bad_configuration() {
+        let path = env::temp_dir().join("z3stringnet_test_replay_bad.csv");
+        let f_str = path.to_str().unwrap();
+        {
+            let mut file = File::create(f_str).unwrap();
+            file.write_all(b"0,0,0\n").unwrap();
+        }
+        let mut recorder = LinkCountRecorder::new();
+        assert!(replay_configurations(
+            f_str, Point {x: 4, y: 4}, 3, 1, &mut [&mut recorder], &mut []
+        ).is_err());
+    }
+}
+
+/// Run estimators over the configurations saved in a style 2 file instead of over a live
+/// Monte Carlo run.
+///
+/// The file is read one configuration at a time and binned the same way `main` bins a live
+/// run: every `estimators` entry measures every configuration and is finalized (with
+/// `number_measure` as the denominator) and cleared after each `number_measure`
+/// configurations. `bin_estimators` only measure the last configuration of each bin and are
+/// finalized with a denominator of 1, like the winding number count estimator in a live run.
+/// Configurations left over after the last full bin are not used, a live run never writes a
+/// partial bin either.
+///
+/// To get exactly the files of a live run, replay the `lattice_configurations.csv` written with
+/// `--write-measure-confs true` using the same `--nmeasure`. Returns the number of bins written.
+pub fn replay_configurations(
+    f_str: &str,
+    size: Point,
+    modulus: u8,
+    number_measure: u64,
+    estimators: &mut [&mut Measurable],
+    bin_estimators: &mut [&mut Measurable],
+) -> Result<u64, String> {
+    if number_measure == 0 {
+        return Err(String::from("Need at least one measurement per bin"));
+    }
+    let file = match File::open(f_str) {
+        Ok(f) => f,
+        Err(e) => return Err(format!("Problem opening {}: {}", f_str, e)),
+    };
+
+    let mut bins_written: u64 = 0;
+    let mut measured_in_bin: u64 = 0;
+    let mut configuration_number: u64 = 0;
+    for line in BufReader::new(file).lines() {
+        let line = match line {
+            Ok(l) => l,
+            Err(e) => return Err(format!("Problem reading {}: {}", f_str, e)),
+        };
+        if line.trim().is_empty() {
+            continue;
+        }
+        let mut lat: Lattice = match lattice_from_style_2_line(&line, size, modulus) {
+            Ok(l) => l,
+            Err(e) => return Err(format!("{} configuration {}: {}", f_str, configuration_number, e)),
+        };
+        configuration_number += 1;
+
+        for estimator in estimators.iter_mut() {
+            estimator.measure(&mut lat);
+        }
+        measured_in_bin += 1;
+
+        if measured_in_bin == number_measure {
+            for estimator in estimators.iter_mut() {
+                estimator.finalize_bin_and_write(number_measure);
+                estimator.clear();
+            }
+            for estimator in bin_estimators.iter_mut() {
+                estimator.measure(&mut lat);
+                estimator.finalize_bin_and_write(1);
+                estimator.clear();
+            }
+            measured_in_bin = 0;
+            bins_written += 1;
+        }
+    }
+    if measured_in_bin != 0 {
+        println!("Ignoring the last {} configurations of {}, they do not fill a bin",
+                 measured_in_bin, f_str);
+    }
+    for estimator in estimators.iter_mut() {
+        estimator.flush();
+    }
+    for estimator in bin_estimators.iter_mut() {
+        estimator.flush();
+    }
+
+    Ok(bins_written)
+}
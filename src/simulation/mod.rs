@@ -0,0 +1,586 @@
+use std::fs;
+use std::fs::OpenOptions;
+use std::path::Path;
+use std::path::PathBuf;
+use super::config::InitialState;
+use super::config::RunConfig;
+use super::config::write_run_config;
+use super::datamodel::Direction;
+use super::datamodel::Point;
+use super::datamodel::lattice::Lattice;
+use super::datamodel::lattice::build_blank_zn_lat;
+use super::datamodel::lattice::build_z3_striped_lat;
+use super::datamodel::lattice::build_z3_striped_vertical_lat;
+use super::datamodel::lattice::build_z3_fully_packed_lat;
+use super::lattice_updates::Update;
+use super::lattice_updates::generate_seed;
+use super::estimators::EstimatorOutput;
+use super::estimators::Measurable;
+use super::estimators::density_estimator::DensityEstimator;
+use super::estimators::correlation_origin_estimator::CorrelationOriginEstimator;
+use super::estimators::correlation_function_estimator::CorrelationFunctionEstimator;
+use super::estimators::total_link_count_estimator::TotalLinkCountEstimator;
+use super::estimators::winding_number_estimator::WindingNumberCountEstimator;
+use super::estimators::winding_variance_estimator::WindingNumberVarianceEstimator;
+use super::estimators::cluster_size_estimator::FullClusterSizeEstimator;
+use super::estimators::time_series_estimator::TimeSeriesEstimator;
+use super::estimators::structure_factor_estimator::StructureFactorEstimator;
+use super::estimators::wilson_loop_estimator::WilsonLoopEstimator;
+use super::estimators::monomer_correlation_estimator::MonomerCorrelationEstimator;
+use super::estimators::vertex_type_estimator::VertexTypeEstimator;
+use super::estimators::winding_histogram_estimator::WindingHistogramEstimator;
+use super::estimators::cluster_geometry_estimator::ClusterGeometryEstimator;
+use super::oio::Checkpoint;
+use super::oio::read_checkpoint;
+use super::oio::write_checkpoint;
+use super::oio::read_lattice_style_1;
+use super::oio::read_lattice_style_2;
+use super::oio::write_lattice;
+use super::oio::write_run_parameters;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use std::fs::File;
+    use std::io::prelude::*;
+    use config::read_run_config;
+    use lattice_updates::UpdateType;
+
+    fn read_to_string(path: &PathBuf) -> String {
+        let mut contents = String::new();
+        File::open(path).unwrap().read_to_string(&mut contents).unwrap();
+        contents
+    }
+
+    fn test_config(name: &str) -> RunConfig {
+        let dir = env::temp_dir().join(name);
+        let _ = fs::remove_dir_all(&dir);
+        let mut config = RunConfig::default();
+        config.update_type = UpdateType::Worm;
+        config.nbins = 3;
+        config.nmeasure = 4;
+        config.nupdate = 2;
+        config.seed = Some(11);
+        config.paranoid = 1;
+        config.output_dir = dir.to_string_lossy().into_owned();
+        config
+    }
+
+    #[test]
+    fn test_run_writes_results_and_effective_config() {
+        let mut config = test_config("z3stringnet_test_simulation_run");
+        config.seed = None;
+        config.estimators = vec![String::from("total_link_count"), String::from("winding_number_count")];
+        let mut simulation = Simulation::new(config.clone()).unwrap();
+        simulation.equilibrate();
+        simulation.run();
+        assert_eq!(simulation.bins_completed(), 3);
+
+        let dir = PathBuf::from(&config.output_dir);
+        // Header and one line per bin.
+        assert_eq!(read_to_string(&dir.join("total_link_count_estimator.csv")).lines().count(), 4);
+        assert!(dir.join("winding_number_count_estimator.csv").exists());
+        assert!(!dir.join("density_estimator.csv").exists());
+        assert!(dir.join("run_parameters.csv").exists());
+
+        // The written configuration has the generated seed so it repeats the run exactly.
+        let written = read_run_config(dir.join("run_config.yaml").to_str().unwrap()).unwrap();
+        assert_eq!(written.seed, Some(simulation.seed));
+        config.seed = Some(simulation.seed);
+        assert_eq!(written, config);
+    }
+
+    #[test]
+    fn test_same_config_same_results() {
+        let config = test_config("z3stringnet_test_simulation_same_a");
+        let other_config = test_config("z3stringnet_test_simulation_same_b");
+        for c in vec![config.clone(), other_config.clone()] {
+            let mut simulation = Simulation::new(c).unwrap();
+            simulation.equilibrate();
+            simulation.run();
+        }
+        for name in &["density_estimator.csv", "winding_number_count_estimator.csv"] {
+            assert_eq!(read_to_string(&PathBuf::from(&config.output_dir).join(name)),
+                       read_to_string(&PathBuf::from(&other_config.output_dir).join(name)));
+        }
+    }
+
+    #[test]
+    fn test_resumed_run_matches_uninterrupted_run() {
+        // Without any checkpoints.
+        let mut config = test_config("z3stringnet_test_simulation_uninterrupted");
+        config.nbins = 4;
+        config.estimators = vec![String::from("total_link_count")];
+        let mut uninterrupted = Simulation::new(config.clone()).unwrap();
+        uninterrupted.equilibrate();
+        uninterrupted.run();
+
+        // Checkpoints every bin, stopped after two bins and resumed from the last checkpoint.
+        let mut interrupted_config = test_config("z3stringnet_test_simulation_interrupted");
+        interrupted_config.nbins = 2;
+        interrupted_config.estimators = config.estimators.clone();
+        interrupted_config.checkpoint_every = 1;
+        let mut interrupted = Simulation::new(interrupted_config.clone()).unwrap();
+        interrupted.equilibrate();
+        interrupted.run();
+        let mut resumed_config = interrupted_config.clone();
+        resumed_config.nbins = 4;
+        resumed_config.resume = Some(interrupted_config.checkpoint_path().to_string_lossy().into_owned());
+        let mut resumed = Simulation::new(resumed_config).unwrap();
+        resumed.equilibrate();
+        resumed.run();
+
+        assert_eq!(format!("{:?}", resumed.lat.vertices), format!("{:?}", uninterrupted.lat.vertices));
+        let name = "total_link_count_estimator.csv";
+        assert_eq!(read_to_string(&PathBuf::from(&interrupted_config.output_dir).join(name)),
+                   read_to_string(&PathBuf::from(&config.output_dir).join(name)));
+    }
+
+    #[test]
+    fn test_resume_drops_bins_after_the_checkpoint() {
+        let mut config = test_config("z3stringnet_test_simulation_past_uninterrupted");
+        config.nbins = 4;
+        config.estimators = vec![String::from("total_link_count"), String::from("winding_number_count")];
+        config.write_measure_confs = true;
+        let mut uninterrupted = Simulation::new(config.clone()).unwrap();
+        uninterrupted.equilibrate();
+        uninterrupted.run();
+
+        // Checkpoint after bin 2, but stop only after bin 3 so the files are a bin ahead.
+        let mut interrupted_config = test_config("z3stringnet_test_simulation_past_interrupted");
+        interrupted_config.nbins = 3;
+        interrupted_config.estimators = config.estimators.clone();
+        interrupted_config.write_measure_confs = true;
+        interrupted_config.checkpoint_every = 2;
+        let mut interrupted = Simulation::new(interrupted_config.clone()).unwrap();
+        interrupted.equilibrate();
+        interrupted.run();
+        let mut resumed_config = interrupted_config.clone();
+        resumed_config.nbins = 4;
+        resumed_config.resume = Some(interrupted_config.checkpoint_path().to_string_lossy().into_owned());
+        let mut resumed = Simulation::new(resumed_config).unwrap();
+        resumed.run();
+
+        for name in &["total_link_count_estimator.csv", "winding_number_count_estimator.csv",
+                      "lattice_configurations.csv"] {
+            assert_eq!(read_to_string(&PathBuf::from(&interrupted_config.output_dir).join(name)),
+                       read_to_string(&PathBuf::from(&config.output_dir).join(name)));
+        }
+    }
+
+    #[test]
+    fn test_initial_state_and_bad_config() {
+        let mut config = test_config("z3stringnet_test_simulation_initial");
+        config.initial_state = InitialState::FullyPacked;
+        let simulation = Simulation::new(config.clone()).unwrap();
+        assert_eq!(simulation.lat.number_filled_links, build_z3_fully_packed_lat(config.size).number_filled_links);
+
+        config.modulus = 4;
+        assert!(Simulation::new(config).is_err());
+
+        for name in &["blank", "striped", "striped_vertical", "fully_packed"] {
+            let mut config = test_config("z3stringnet_test_simulation_initial_states");
+            config.initial_state = InitialState::from_name(name).unwrap();
+            assert!(build_initial_lattice(&config).unwrap().validate().is_valid());
+        }
+    }
+}
+
+/// A Monte Carlo run set up from a `RunConfig`.
+///
+/// `Simulation::new` builds (or reads) the starting lattice, opens the selected estimators in
+/// the output directory and records the run there in `run_parameters.csv` and
+/// `run_config.yaml`. The latter is the configuration actually used, with the seed filled in,
+/// and can be passed back with `--config` to repeat the run. Then `equilibrate` and `run` do
+/// the work, one bin at a time with `run_bin` for drivers that need to step in between bins.
+pub struct Simulation {
+    pub config: RunConfig,
+    pub lat: Lattice,
+    pub updater: Update,
+    /// The seed the run was started with, generated if the configuration has none.
+    pub seed: u64,
+    /// The seed the generator was restarted from at the end of the latest bin (see
+    /// `Update::restart_rng`), `seed` before the first bin.
+    rng_seed: u64,
+    output_dir: PathBuf,
+    output: EstimatorOutput,
+    /// Measure every configuration, finalized once per bin.
+    estimators: Vec<Box<Measurable + Send>>,
+    /// Measure only the last configuration of each bin (the winding number count).
+    bin_estimators: Vec<Box<Measurable + Send>>,
+    resumed: bool,
+    bins_completed: u64,
+    total_measure_count: u64,
+    total_update_count: u64,
+}
+
+/// The lattice the run starts from, see `InitialState`.
+pub fn build_initial_lattice(config: &RunConfig) -> Result<Lattice, String> {
+    let mut lat = match config.initial_state {
+        InitialState::Blank => build_blank_zn_lat(config.size, config.modulus),
+        InitialState::Striped => build_z3_striped_lat(config.size),
+        InitialState::StripedVertical => build_z3_striped_vertical_lat(config.size),
+        InitialState::FullyPacked => build_z3_fully_packed_lat(config.size),
+        InitialState::File { ref path, style, line } => {
+            let read_result = if style == 1 {
+                read_lattice_style_1(path, config.modulus)
+            } else {
+                read_lattice_style_2(path, config.size, config.modulus, line)
+            };
+            match read_result {
+                Ok(read_lat) => {
+                    println!("Starting from configuration in {}", path);
+                    read_lat
+                },
+                Err(e) => return Err(format!("Can not start from {}: {}", path, e)),
+            }
+        },
+    };
+    if lat.size != config.size {
+        return Err(format!("Initial configuration is {}x{} but the run is {}x{}",
+                           lat.size.x, lat.size.y, config.size.x, config.size.y));
+    }
+    if lat.modulus != config.modulus {
+        return Err(format!("Initial configuration is Z{} but the run is Z{}",
+                           lat.modulus, config.modulus));
+    }
+    if config.monomers {
+        lat.insert_monomer_pair(&Point {x: 0, y: 0}, &Direction::E)?;
+    }
+    let report = lat.validate();
+    if !report.is_valid() {
+        return Err(format!("Initial {} lattice is not a valid string net:\n{}",
+                           config.initial_state.name(), report));
+    }
+    Ok(lat)
+}
+
+impl Simulation {
+    pub fn new(config: RunConfig) -> Result<Simulation, String> {
+        config.check()?;
+
+        // When resuming, the lattice, the generator and the counters come from the checkpoint.
+        let resume_checkpoint: Option<Checkpoint> = match config.resume {
+            Some(ref resume_path) => {
+                println!("Resuming from checkpoint: {}", resume_path);
+                match read_checkpoint(resume_path) {
+                    Ok(checkpoint) => Some(checkpoint),
+                    Err(e) => return Err(format!("Could not resume from {}: {}", resume_path, e)),
+                }
+            },
+            None => None,
+        };
+
+        let lat: Lattice = match resume_checkpoint {
+            Some(ref checkpoint) => {
+                if checkpoint.lat.size != config.size {
+                    return Err(format!("Checkpoint lattice is {}x{} but the run is {}x{}",
+                                       checkpoint.lat.size.x, checkpoint.lat.size.y,
+                                       config.size.x, config.size.y));
+                }
+                if checkpoint.lat.modulus != config.modulus {
+                    return Err(format!("Checkpoint lattice is Z{} but the run is Z{}",
+                                       checkpoint.lat.modulus, config.modulus));
+                }
+                if checkpoint.lat.monomers.is_empty() == config.monomers {
+                    return Err(format!("Checkpoint lattice has {} monomers but the run {} them",
+                                       checkpoint.lat.monomers.len(),
+                                       if config.monomers { "needs" } else { "does not use" }));
+                }
+                checkpoint.lat.clone()
+            },
+            None => build_initial_lattice(&config)?,
+        };
+
+        let seed: u64 = match (&resume_checkpoint, config.seed) {
+            (&Some(ref checkpoint), _) => checkpoint.seed,
+            (&None, Some(seed)) => seed,
+            (&None, None) => generate_seed(),
+        };
+        println!("Random number generator seed: {}", seed);
+
+        let mut updater = Update::new(lat.size, config.weights, seed);
+        let mut rng_seed = seed;
+        let mut bins_completed: u64 = 0;
+        let mut total_measure_count: u64 = 0;
+        let mut total_update_count: u64 = 0;
+        if let Some(ref checkpoint) = resume_checkpoint {
+            if checkpoint.link_number_tuning != config.weights {
+                println!("Using weight {} from the checkpoint instead of {}",
+                         checkpoint.link_number_tuning, config.weights);
+            }
+            updater.link_number_tuning = checkpoint.link_number_tuning;
+            updater.reseed(checkpoint.rng_seed);
+            rng_seed = checkpoint.rng_seed;
+            bins_completed = checkpoint.bins_completed;
+            total_measure_count = checkpoint.total_measure_count;
+            total_update_count = checkpoint.total_update_count;
+            println!("Continuing after bin {}", bins_completed);
+        }
+
+        let output_dir = PathBuf::from(&config.output_dir);
+        if let Err(e) = fs::create_dir_all(&output_dir) {
+            return Err(format!("Can not create output directory {}: {}", output_dir.display(), e));
+        }
+        println!("Writing results to {}", output_dir.display());
+
+        // Record the parameters, most importantly the seed, so this run can be replayed exactly.
+        let mut effective_config = config.clone();
+        effective_config.seed = Some(seed);
+        effective_config.weights = updater.link_number_tuning;
+        write_run_config(&output_dir.join("run_config.yaml").to_string_lossy(), &effective_config);
+        let initial_configuration = match config.initial_state {
+            InitialState::File { ref path, .. } => path.clone(),
+            _ => String::new(),
+        };
+        write_run_parameters(output_dir.join("run_parameters.csv").to_string_lossy().into_owned(), &vec![
+            (String::from("lx"), format!("{}", config.size.x)),
+            (String::from("ly"), format!("{}", config.size.y)),
+            (String::from("modulus"), format!("{}", lat.modulus)),
+            (String::from("weights"), format!("{}", updater.link_number_tuning)),
+            (String::from("nbins"), format!("{}", config.nbins)),
+            (String::from("nmeasure"), format!("{}", config.nmeasure)),
+            (String::from("nupdate"), format!("{}", config.nupdate)),
+            (String::from("seed"), format!("{}", seed)),
+            (String::from("resume"), config.resume.clone().unwrap_or_default()),
+            (String::from("initial_configuration"), initial_configuration),
+        ]);
+
+        // A resumed run keeps adding bins to the files it was writing before, once whatever the
+        // stopped run wrote after its checkpoint is gone.
+        if let Some(ref checkpoint) = resume_checkpoint {
+            truncate_result_files(&output_dir, &checkpoint.result_files)?;
+        }
+        let output = EstimatorOutput::directory(output_dir.clone(), resume_checkpoint.is_some());
+        let mut estimators: Vec<Box<Measurable + Send>> = Vec::new();
+        let mut bin_estimators: Vec<Box<Measurable + Send>> = Vec::new();
+        if config.uses_estimator("density") {
+            estimators.push(Box::new(DensityEstimator::new(&lat.size, &output)));
+        }
+        if config.uses_estimator("correlation_origin") {
+            estimators.push(Box::new(CorrelationOriginEstimator::new(&lat.size, &output)));
+        }
+        if config.uses_estimator("correlation_function") {
+            estimators.push(Box::new(CorrelationFunctionEstimator::new(&lat.size, &output)));
+        }
+        if config.uses_estimator("total_link_count") {
+            estimators.push(Box::new(TotalLinkCountEstimator::new(&output)));
+        }
+        if config.uses_estimator("winding_number_variance") {
+            estimators.push(Box::new(WindingNumberVarianceEstimator::new(&output)));
+        }
+        if config.uses_estimator("cluster_size") {
+            estimators.push(Box::new(FullClusterSizeEstimator::new(&lat, &output)));
+        }
+        if config.uses_estimator("cluster_geometry") {
+            estimators.push(Box::new(ClusterGeometryEstimator::new(&lat.size, &output)));
+        }
+        if config.uses_estimator("structure_factor") {
+            estimators.push(Box::new(StructureFactorEstimator::new(&lat.size, lat.modulus, &output)));
+        }
+        if config.uses_estimator("wilson_loop") {
+            estimators.push(Box::new(WilsonLoopEstimator::new(&lat.size, lat.modulus, &output)));
+        }
+        if config.uses_estimator("winding_histogram") {
+            estimators.push(Box::new(WindingHistogramEstimator::new(&lat.size, lat.modulus, &output)));
+        }
+        if config.uses_estimator("vertex_type") {
+            estimators.push(Box::new(VertexTypeEstimator::new(&output)));
+        }
+        if config.uses_estimator("monomer_correlation") {
+            estimators.push(Box::new(MonomerCorrelationEstimator::new(&lat.size, &output)));
+        }
+        if config.uses_estimator("time_series") {
+            estimators.push(Box::new(TimeSeriesEstimator::new(&output)));
+        }
+        if config.uses_estimator("winding_number_count") {
+            bin_estimators.push(Box::new(WindingNumberCountEstimator::new(lat.clone(), &output)));
+        }
+
+        Ok(Simulation {
+            config,
+            lat,
+            updater,
+            seed,
+            rng_seed,
+            output_dir,
+            output,
+            estimators,
+            bin_estimators,
+            resumed: resume_checkpoint.is_some(),
+            bins_completed,
+            total_measure_count,
+            total_update_count,
+        })
+    }
+
+    pub fn bins_completed(&self) -> u64 {
+        self.bins_completed
+    }
+
+    pub fn output_dir(&self) -> &PathBuf {
+        &self.output_dir
+    }
+
+    /// Update the lattice before the first measurement. Does nothing for a resumed run, it was
+    /// already equilibrated before its checkpoint was written, or if the configuration says not
+    /// to.
+    pub fn equilibrate(&mut self) {
+        if self.resumed || !self.config.equilibrate {
+            return;
+        }
+        println!("Equilibrating");
+        let equilibration_time = self.config.equilibration_updates();
+        println!("Number of updates in equilibration: {}", equilibration_time);
+        for _ in 0..equilibration_time {
+            self.updater.main_update(&mut self.lat, &self.config.update_type);
+        }
+        println!("Done equilibrating");
+    }
+
+    /// A single update, checking the lattice if the run is paranoid.
+    pub fn update(&mut self) {
+        if self.config.write_update_confs {
+            write_lattice(&self.output_dir, format!("lattice_{}.csv", self.total_update_count),
+                          &mut self.lat, self.config.write_configuration_style);
+        }
+        self.updater.main_update(&mut self.lat, &self.config.update_type);
+        self.total_update_count += 1;
+        let paranoid_every = self.config.paranoid;
+        if paranoid_every != 0 && self.total_update_count % paranoid_every == 0 {
+            let report = self.lat.validate();
+            if !report.is_valid() {
+                panic!("Lattice is broken after update {}:\n{}", self.total_update_count, report);
+            }
+        }
+    }
+
+    /// Fill, write and clear the next bin, then write a checkpoint if one is due.
+    pub fn run_bin(&mut self) {
+        self.start_bin();
+        for _ in 0..self.config.nmeasure {
+            self.update_and_measure();
+        }
+        self.finish_bin();
+    }
+
+    /// First part of `run_bin`, for drivers that do something between the measurements of a
+    /// bin. Call `update_and_measure` `nmeasure` times and then `finish_bin`.
+    pub fn start_bin(&mut self) {
+        println!("Working on bin {}", self.bins_completed);
+        if self.config.write_bin_confs {
+            write_lattice(&self.output_dir, format!("lattice_bin_{}.csv", self.total_update_count),
+                          &mut self.lat, self.config.write_configuration_style);
+        }
+    }
+
+    /// `nupdate` updates followed by a measurement with every estimator.
+    pub fn update_and_measure(&mut self) {
+        for _ in 0..self.config.nupdate {
+            self.update();
+        }
+        self.total_measure_count += 1;
+        // Written right before measuring so the file holds exactly the configurations
+        // that were measured (which is what `replay` needs).
+        if self.config.write_measure_confs {
+            write_lattice(&self.output_dir, format!("lattice_measure_{}.csv", self.total_update_count),
+                          &mut self.lat, self.config.write_configuration_style);
+        }
+        for estimator in self.estimators.iter_mut() {
+            estimator.measure(&mut self.lat);
+        }
+    }
+
+    /// Write and clear the bin, then write a checkpoint if one is due.
+    pub fn finish_bin(&mut self) {
+        for estimator in self.estimators.iter_mut() {
+            estimator.finalize_bin_and_write(self.config.nmeasure);
+            estimator.clear();
+        }
+        for estimator in self.bin_estimators.iter_mut() {
+            estimator.measure(&mut self.lat);
+            estimator.finalize_bin_and_write(1);
+        }
+        self.bins_completed += 1;
+        self.rng_seed = self.updater.restart_rng();
+
+        let checkpoint_every = self.config.checkpoint_every;
+        if checkpoint_every > 0 && self.bins_completed % checkpoint_every == 0 {
+            self.write_checkpoint();
+        }
+    }
+
+    /// Run bins until there are `nbins` of them, then flush every estimator.
+    pub fn run(&mut self) {
+        while self.bins_completed < self.config.nbins {
+            self.run_bin();
+        }
+        self.flush();
+    }
+
+    pub fn flush(&mut self) {
+        for estimator in self.estimators.iter_mut().chain(self.bin_estimators.iter_mut()) {
+            estimator.flush();
+        }
+    }
+
+    /// Write a checkpoint to the configured checkpoint file, flushing the estimators first so
+    /// their files agree with it. Only meant for the end of a bin, where the generator was
+    /// just restarted.
+    pub fn write_checkpoint(&mut self) {
+        // Estimators were just cleared so all they hold is already in their buffers.
+        self.flush();
+        let mut file_names = self.output.opened_file_names();
+        let config = &self.config;
+        if (config.write_update_confs || config.write_measure_confs || config.write_bin_confs)
+            && config.write_configuration_style != 1 {
+            file_names.push(String::from("lattice_configurations.csv"));
+        }
+        // A file that does not exist yet has nothing to keep.
+        let result_files = file_names.into_iter()
+            .map(|name| {
+                let length = fs::metadata(self.output_dir.join(&name)).map(|m| m.len()).unwrap_or(0);
+                (name, length)
+            })
+            .collect();
+        let checkpoint = Checkpoint {
+            lat: self.lat.clone(),
+            link_number_tuning: self.updater.link_number_tuning,
+            seed: self.seed,
+            rng_seed: self.rng_seed,
+            bins_completed: self.bins_completed,
+            total_measure_count: self.total_measure_count,
+            total_update_count: self.total_update_count,
+            result_files,
+        };
+        write_checkpoint(&self.config.checkpoint_path().to_string_lossy(), &checkpoint);
+    }
+}
+
+/// Cut every result file in `output_dir` back to the length it had when a checkpoint was
+/// written, see `Checkpoint::result_files`.
+fn truncate_result_files(output_dir: &Path, result_files: &[(String, u64)]) -> Result<(), String> {
+    for &(ref name, length) in result_files {
+        let path = output_dir.join(name);
+        if length == 0 && !path.exists() {
+            continue;
+        }
+        let file = match OpenOptions::new().write(true).open(&path) {
+            Ok(f) => f,
+            Err(e) => return Err(format!("Can not open {} to resume: {}", path.display(), e)),
+        };
+        let current_length = match file.metadata() {
+            Ok(metadata) => metadata.len(),
+            Err(e) => return Err(format!("Can not read the length of {}: {}", path.display(), e)),
+        };
+        if current_length < length {
+            return Err(format!("{} has {} bytes but had {} when the checkpoint was written",
+                               path.display(), current_length, length));
+        }
+        if let Err(e) = file.set_len(length) {
+            return Err(format!("Can not cut {} back to the checkpoint: {}", path.display(), e));
+        }
+    }
+    Ok(())
+}
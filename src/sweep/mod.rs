@@ -0,0 +1,268 @@
+use std::fs;
+use std::fs::File;
+use std::io::prelude::*;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use super::config::RunConfig;
+use super::datamodel::Point;
+use super::lattice_updates::generate_seed;
+use super::simulation::Simulation;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use config::read_run_config;
+
+    #[test]
+    fn test_parse_value_list() {
+        assert_eq!(parse_value_list("0.5").unwrap(), vec![0.5]);
+        assert_eq!(parse_value_list("0.5, 1,2.5").unwrap(), vec![0.5, 1.0, 2.5]);
+        let range = parse_value_list("0.1:0.5:0.1").unwrap();
+        assert_eq!(range.len(), 5);
+        assert!((range[4] - 0.5).abs() < 1e-12);
+        assert_eq!(parse_value_list("4:8:2,16").unwrap(), vec![4.0, 6.0, 8.0, 16.0]);
+        assert_eq!(parse_value_list("0.6:1.2:0.2").unwrap(), vec![0.6, 0.8, 1.0, 1.2]);
+        assert!(parse_value_list("").is_err());
+        assert!(parse_value_list("1:2").is_err());
+        assert!(parse_value_list("1:2:0").is_err());
+        assert!(parse_value_list("2:1:0.5").is_err());
+        assert!(parse_value_list("one").is_err());
+    }
+
+    #[test]
+    fn test_parse_size_list() {
+        assert_eq!(parse_size_list("4, 8x6,12:16:4").unwrap(),
+                   vec![Point {x: 4, y: 4}, Point {x: 8, y: 6}, Point {x: 12, y: 12}, Point {x: 16, y: 16}]);
+        assert!(parse_size_list("4.5").is_err());
+        assert!(parse_size_list("8x").is_err());
+    }
+
+    #[test]
+    fn test_sweep_points() {
+        let mut base = RunConfig::default();
+        base.seed = Some(100);
+        let sizes = vec![Point {x: 4, y: 4}, Point {x: 8, y: 6}];
+        let points = sweep_points(&base, &sizes, &[0.5, 1.0], Path::new("out")).unwrap();
+        assert_eq!(points.len(), 4);
+        assert_eq!(points[1].size, Point {x: 4, y: 4});
+        assert_eq!(points[1].weights, 1.0);
+        assert_eq!(points[2].size, Point {x: 8, y: 6});
+        assert_eq!(points[2].weights, 0.5);
+        assert_eq!(Path::new(&points[2].output_dir), Path::new("out").join("002_L8x6_w0.5").as_path());
+        assert_eq!(points[2].checkpoint_path(), Path::new("out").join("002_L8x6_w0.5").join("checkpoint.csv"));
+        // Every point gets its own, but reproducible, seed.
+        assert_eq!(points.iter().map(|p| p.seed.unwrap()).collect::<Vec<u64>>(), vec![100, 101, 102, 103]);
+
+        base.resume = Some(String::from("checkpoint.csv"));
+        assert!(sweep_points(&base, &sizes, &[0.5], Path::new("out")).is_err());
+    }
+
+    #[test]
+    fn test_run_sweep() {
+        let root = env::temp_dir().join("z3stringnet_test_sweep");
+        let _ = fs::remove_dir_all(&root);
+        let mut base = RunConfig::default();
+        base.nbins = 2;
+        base.nmeasure = 3;
+        base.nupdate = 1;
+        base.estimators = vec![String::from("total_link_count")];
+        let points = sweep_points(&base, &[Point {x: 4, y: 4}], &[0.5, 1.0, 2.0], &root).unwrap();
+        write_sweep_index(&root, &points);
+        run_sweep(&points, 2).unwrap();
+
+        let mut index = String::new();
+        File::open(root.join("sweep_index.csv")).unwrap().read_to_string(&mut index).unwrap();
+        let lines: Vec<&str> = index.lines().collect();
+        assert_eq!(lines.len(), 4);
+        assert_eq!(lines[0], "directory,lx,ly,modulus,weights,seed");
+        for (line, point) in lines[1..].iter().zip(points.iter()) {
+            assert!(line.starts_with(&format!("{},4,4,3,{}", point.output_dir, point.weights)));
+            let dir = Path::new(&point.output_dir);
+            assert!(dir.join("total_link_count_estimator.csv").exists());
+            assert_eq!(read_run_config(dir.join("run_config.yaml").to_str().unwrap()).unwrap(), *point);
+        }
+    }
+}
+
+/// Parse a list of numbers like `0.5,0.6,0.7`. Entries can also be ranges `start:stop:step`,
+/// which include `stop` if the steps land on it, e.g. `0.5:0.7:0.1` is the same list.
+pub fn parse_value_list(list_str: &str) -> Result<Vec<f64>, String> {
+    let mut values: Vec<f64> = Vec::new();
+    for entry in list_str.split(',') {
+        let parts: Vec<&str> = entry.split(':').map(|p| p.trim()).collect();
+        let mut numbers: Vec<f64> = Vec::new();
+        for part in &parts {
+            match part.parse::<f64>() {
+                Ok(number) => numbers.push(number),
+                Err(_) => return Err(format!("{} in {} is not a number", part, list_str)),
+            }
+        }
+        match numbers.len() {
+            1 => values.push(numbers[0]),
+            3 => {
+                let (start, stop, step) = (numbers[0], numbers[1], numbers[2]);
+                if step <= 0.0 || stop < start {
+                    return Err(format!("Range {} needs start <= stop and a positive step", entry));
+                }
+                // Counting steps instead of adding them up keeps rounding errors from
+                // piling up (and from dropping `stop`). What is left is rounded away so
+                // 0.6:1.2:0.2 ends in 1.2 and not 1.2000000000000002, which would end up
+                // in directory names.
+                let number_steps = ((stop - start) / step + 1e-9).floor() as u64;
+                for i in 0..(number_steps + 1) {
+                    let value = start + (i as f64) * step;
+                    values.push((value * 1e12).round() / 1e12);
+                }
+            },
+            _ => return Err(format!("{} is neither a number nor a range start:stop:step", entry)),
+        }
+    }
+    Ok(values)
+}
+
+/// Parse a list of lattice sizes like `4,8,16`. A number is a square lattice, `8x6` a
+/// rectangular one and ranges `start:stop:step` (see `parse_value_list`) give square lattices.
+pub fn parse_size_list(list_str: &str) -> Result<Vec<Point>, String> {
+    let mut sizes: Vec<Point> = Vec::new();
+    for entry in list_str.split(',') {
+        let entry = entry.trim();
+        if entry.contains('x') {
+            let edges: Vec<Result<i64, _>> = entry.split('x').map(|e| e.trim().parse::<i64>()).collect();
+            match (edges.len(), edges.get(0), edges.get(1)) {
+                (2, Some(&Ok(x)), Some(&Ok(y))) => sizes.push(Point {x, y}),
+                _ => return Err(format!("{} is not a size LXxLY", entry)),
+            }
+        } else {
+            for edge in parse_value_list(entry)? {
+                if edge.fract() != 0.0 {
+                    return Err(format!("Lattice size {} is not a whole number", edge));
+                }
+                sizes.push(Point {x: edge as i64, y: edge as i64});
+            }
+        }
+    }
+    Ok(sizes)
+}
+
+/// One run configuration per pair of size and weight, sizes varying slowest.
+///
+/// Each point writes into its own directory below `root`, named after its index, size and
+/// weight, and keeps its checkpoints there. If `base` has a seed, point `i` uses `seed + i`,
+/// otherwise every point gets a generated seed now so the index file can record it.
+pub fn sweep_points(base: &RunConfig, sizes: &[Point], weights: &[f64], root: &Path)
+    -> Result<Vec<RunConfig>, String> {
+    if base.resume.is_some() {
+        return Err(String::from("A sweep can not resume from a checkpoint"));
+    }
+    let checkpoint_file_name = match Path::new(&base.checkpoint_file).file_name() {
+        Some(name) => name.to_os_string(),
+        None => return Err(format!("{} is not a checkpoint file name", base.checkpoint_file)),
+    };
+    let mut points: Vec<RunConfig> = Vec::new();
+    for size in sizes {
+        for weight in weights {
+            let index = points.len();
+            let point_dir = root.join(format!("{:03}_L{}x{}_w{}", index, size.x, size.y, weight));
+            let mut config = base.clone();
+            config.size = *size;
+            config.weights = *weight;
+            config.seed = Some(match base.seed {
+                Some(seed) => seed.wrapping_add(index as u64),
+                None => generate_seed(),
+            });
+            config.output_dir = point_dir.to_string_lossy().into_owned();
+            config.checkpoint_file = checkpoint_file_name.to_string_lossy().into_owned();
+            config.gui = false;
+            config.check()?;
+            points.push(config);
+        }
+    }
+    Ok(points)
+}
+
+/// Write `sweep_index.csv` to `root`, one line per point with its directory and parameters.
+pub fn write_sweep_index(root: &Path, points: &[RunConfig]) {
+    match fs::create_dir_all(root) {
+        Err(err) => panic!("could not create {}: {}", root.display(), err),
+        Ok(_) => (),
+    }
+    let path = root.join("sweep_index.csv");
+    let display = path.display();
+
+    let mut file = match File::create(&path){
+        Err(err) => panic!("could not create {}: {}",
+                           display,
+                           err),
+        Ok(good_file) => good_file,
+    };
+
+    let mut out_string = String::new();
+    out_string.push_str("directory,lx,ly,modulus,weights,seed\n");
+    for point in points {
+        out_string.push_str(&format!("{},{},{},{},{},{}\n",
+                                     point.output_dir,
+                                     point.size.x,
+                                     point.size.y,
+                                     point.modulus,
+                                     point.weights,
+                                     point.seed.map(|s| s.to_string()).unwrap_or_default()));
+    }
+
+    match file.write_all(out_string.as_bytes()){
+        Err(err) => panic!("could not write {}: {}",
+                           display,
+                           err),
+        Ok(_) => println!("Wrote sweep index to {}", display),
+    }
+}
+
+/// Run every point, `threads` at a time. Points are handed out in order to whichever thread is
+/// free. Returns an error listing every point that could not be run; the others still finish.
+pub fn run_sweep(points: &[RunConfig], threads: usize) -> Result<(), String> {
+    let next_point = Arc::new(Mutex::new(0usize));
+    let failures: Arc<Mutex<Vec<String>>> = Arc::new(Mutex::new(Vec::new()));
+    let points: Arc<Vec<RunConfig>> = Arc::new(points.to_vec());
+
+    let mut handles = Vec::new();
+    for _ in 0..threads.max(1) {
+        let next_point = next_point.clone();
+        let failures = failures.clone();
+        let points = points.clone();
+        handles.push(thread::spawn(move || {
+            loop {
+                let index = {
+                    let mut next = next_point.lock().unwrap();
+                    let index = *next;
+                    *next += 1;
+                    index
+                };
+                let config = match points.get(index) {
+                    Some(config) => config.clone(),
+                    None => break,
+                };
+                println!("Sweep point {} of {}: {}", index + 1, points.len(), config.output_dir);
+                match Simulation::new(config.clone()) {
+                    Ok(mut simulation) => {
+                        simulation.equilibrate();
+                        simulation.run();
+                    },
+                    Err(e) => failures.lock().unwrap().push(format!("{}: {}", config.output_dir, e)),
+                }
+            }
+        }));
+    }
+    for handle in handles {
+        if handle.join().is_err() {
+            failures.lock().unwrap().push(String::from("a sweep thread panicked"));
+        }
+    }
+
+    let failures = failures.lock().unwrap();
+    if failures.is_empty() {
+        Ok(())
+    } else {
+        Err(failures.join("\n"))
+    }
+}
@@ -0,0 +1,287 @@
+use std::fs::File;
+use std::io::prelude::*;
+use std::mem;
+use std::path::Path;
+use std::path::PathBuf;
+use rand::prelude::*;
+use rand::rngs::StdRng;
+use super::config::RunConfig;
+use super::lattice_updates::generate_seed;
+use super::simulation::Simulation;
+use super::sweep::write_sweep_index;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use std::fs;
+    use datamodel::Point;
+
+    #[test]
+    fn test_swap_probability() {
+        // Equal weights or equal link counts: the swap changes nothing.
+        assert_eq!(swap_probability(0.5, 0.5, 10, 20), 1.0);
+        assert_eq!(swap_probability(0.5, 2.0, 10, 10), 1.0);
+        // Moving the configuration with more links to the larger weight is always accepted.
+        assert_eq!(swap_probability(0.5, 2.0, 20, 10), 1.0);
+        // The other way costs (w_high / w_low)^(n_high - n_low).
+        let p = swap_probability(0.5, 2.0, 10, 12);
+        assert!((p - 4.0_f64.powi(-2)).abs() < 1e-12);
+    }
+
+    fn tempering_config(name: &str) -> (RunConfig, PathBuf) {
+        let root = env::temp_dir().join(name);
+        let _ = fs::remove_dir_all(&root);
+        let mut base = RunConfig::default();
+        base.size = Point {x: 6, y: 6};
+        base.nbins = 3;
+        base.nmeasure = 10;
+        base.nupdate = 3;
+        base.seed = Some(5);
+        base.paranoid = 1;
+        base.estimators = vec![String::from("total_link_count")];
+        (base, root)
+    }
+
+    #[test]
+    fn test_tempering_run() {
+        let (base, root) = tempering_config("z3stringnet_test_tempering");
+        let weights = [0.4, 0.7, 1.0, 1.5];
+        let mut tempering = ParallelTempering::new(&base, &weights, &root, 1).unwrap();
+        tempering.run();
+
+        assert_eq!(tempering.swap_attempts().len(), 3);
+        // 30 rounds of proposals, alternating between pairs (0, 1), (2, 3) and pair (1, 2).
+        assert_eq!(tempering.swap_attempts(), &[15, 15, 15]);
+        for (accepted, attempted) in tempering.swap_accepted().iter().zip(tempering.swap_attempts()) {
+            assert!(accepted <= attempted);
+        }
+        for replica in tempering.replicas() {
+            assert!(replica.lat.validate().is_valid());
+            assert_eq!(replica.bins_completed(), 3);
+        }
+
+        let mut contents = String::new();
+        File::open(root.join("swap_acceptance.csv")).unwrap().read_to_string(&mut contents).unwrap();
+        let lines: Vec<&str> = contents.lines().collect();
+        assert_eq!(lines[0], "weight_low,weight_high,attempts,accepted,acceptance");
+        assert_eq!(lines.len(), 4);
+        assert!(lines[1].starts_with("0.4,0.7,"));
+        assert!(root.join("sweep_index.csv").exists());
+        assert!(root.join("003_w1.5").join("total_link_count_estimator.csv").exists());
+    }
+
+    #[test]
+    fn test_tempering_is_reproducible() {
+        let mut accepted = Vec::new();
+        for name in &["z3stringnet_test_tempering_a", "z3stringnet_test_tempering_b"] {
+            let (base, root) = tempering_config(name);
+            let mut tempering = ParallelTempering::new(&base, &[0.5, 0.9, 1.3], &root, 2).unwrap();
+            tempering.run();
+            accepted.push(tempering.swap_accepted().to_vec());
+        }
+        assert_eq!(accepted[0], accepted[1]);
+    }
+
+    #[test]
+    fn test_tempering_needs_a_ladder() {
+        let (base, root) = tempering_config("z3stringnet_test_tempering_bad");
+        assert!(ParallelTempering::new(&base, &[1.0], &root, 1).is_err());
+        assert!(ParallelTempering::new(&base, &[1.0, 0.5], &root, 1).is_err());
+        assert!(ParallelTempering::new(&base, &[0.5, 1.0], &root, 0).is_err());
+        let mut checkpointing = base.clone();
+        checkpointing.checkpoint_every = 1;
+        assert!(ParallelTempering::new(&checkpointing, &[0.5, 1.0], &root, 1).is_err());
+    }
+}
+
+/// Probability to swap the configurations of two replicas, one at weight `weight_low` holding
+/// `links_low` links and one at `weight_high` holding `links_high`.
+///
+/// A configuration with n links has weight w^n, so the ratio of the weights after and before
+/// the swap is exp((links_high - links_low) * (ln weight_low - ln weight_high)).
+pub fn swap_probability(weight_low: f64, weight_high: f64, links_low: i64, links_high: i64) -> f64 {
+    let exponent = ((links_high - links_low) as f64) * (weight_low.ln() - weight_high.ln());
+    if exponent >= 0.0 {
+        1.0
+    } else {
+        exponent.exp()
+    }
+}
+
+/// Replica exchange (parallel tempering) over a ladder of weights.
+///
+/// Every weight has its own `Simulation`: lattice, updater and estimators, the latter writing
+/// to their own directory below the root directory. After every `swap_every` measurements
+/// swaps of the lattices of neighbouring weights are proposed with `swap_probability`,
+/// alternating between the pairs (0, 1), (2, 3), ... and (1, 2), (3, 4), ... . Swapping
+/// moves only the lattices, so every estimator keeps measuring at its own weight.
+/// The acceptance of every pair is written to `swap_acceptance.csv` in the root directory.
+pub struct ParallelTempering {
+    replicas: Vec<Simulation>,
+    swap_every: u64,
+    rng: StdRng,
+    root: PathBuf,
+    measurements: u64,
+    swap_rounds: u64,
+    /// Per pair of neighbouring weights (i, i + 1).
+    swap_attempts: Vec<u64>,
+    swap_accepted: Vec<u64>,
+}
+
+impl ParallelTempering {
+    /// Set up one replica per weight from `base`. Replica i writes to `root/{i}_w{weight}` and
+    /// uses the seed of `base` plus i, the swaps use the seed of `base` plus the number of
+    /// weights. Without a seed in `base` one is generated. Weights have to be increasing.
+    pub fn new(base: &RunConfig, weights: &[f64], root: &Path, swap_every: u64)
+        -> Result<ParallelTempering, String> {
+        if weights.len() < 2 {
+            return Err(String::from("Parallel tempering needs at least two weights"));
+        }
+        for pair in weights.windows(2) {
+            if !(pair[0] < pair[1]) {
+                return Err(format!("Weights have to be increasing, got {} before {}", pair[0], pair[1]));
+            }
+        }
+        if weights[0] <= 0.0 {
+            return Err(format!("Weights have to be positive, got {}", weights[0]));
+        }
+        if swap_every == 0 {
+            return Err(String::from("Swaps have to be proposed after at least one measurement"));
+        }
+        if base.resume.is_some() || base.checkpoint_every > 0 {
+            return Err(String::from("Checkpoints are not supported with parallel tempering"));
+        }
+
+        let seed = match base.seed {
+            Some(seed) => seed,
+            None => generate_seed(),
+        };
+        let mut configs: Vec<RunConfig> = Vec::new();
+        for (index, weight) in weights.iter().enumerate() {
+            let mut config = base.clone();
+            config.weights = *weight;
+            config.seed = Some(seed.wrapping_add(index as u64));
+            config.output_dir = root.join(format!("{:03}_w{}", index, weight)).to_string_lossy().into_owned();
+            config.gui = false;
+            configs.push(config);
+        }
+        write_sweep_index(root, &configs);
+
+        let mut replicas: Vec<Simulation> = Vec::new();
+        for config in configs {
+            replicas.push(Simulation::new(config)?);
+        }
+        let number_pairs = weights.len() - 1;
+        Ok(ParallelTempering {
+            replicas,
+            swap_every,
+            rng: StdRng::seed_from_u64(seed.wrapping_add(weights.len() as u64)),
+            root: root.to_path_buf(),
+            measurements: 0,
+            swap_rounds: 0,
+            swap_attempts: vec![0; number_pairs],
+            swap_accepted: vec![0; number_pairs],
+        })
+    }
+
+    pub fn replicas(&self) -> &[Simulation] {
+        &self.replicas
+    }
+
+    pub fn swap_attempts(&self) -> &[u64] {
+        &self.swap_attempts
+    }
+
+    pub fn swap_accepted(&self) -> &[u64] {
+        &self.swap_accepted
+    }
+
+    /// Equilibrate every replica, run `nbins` bins of all of them with swaps in between and
+    /// write the swap acceptance.
+    pub fn run(&mut self) {
+        for replica in self.replicas.iter_mut() {
+            replica.equilibrate();
+        }
+        let number_bins = self.replicas[0].config.nbins;
+        let number_measure = self.replicas[0].config.nmeasure;
+        for _ in 0..number_bins {
+            for replica in self.replicas.iter_mut() {
+                replica.start_bin();
+            }
+            for _ in 0..number_measure {
+                for replica in self.replicas.iter_mut() {
+                    replica.update_and_measure();
+                }
+                self.measurements += 1;
+                if self.measurements % self.swap_every == 0 {
+                    self.propose_swaps();
+                }
+            }
+            for replica in self.replicas.iter_mut() {
+                replica.finish_bin();
+            }
+        }
+        for replica in self.replicas.iter_mut() {
+            replica.flush();
+        }
+        self.write_swap_acceptance();
+    }
+
+    /// One round of swap proposals on every other pair of neighbouring weights.
+    pub fn propose_swaps(&mut self) {
+        let first_pair = (self.swap_rounds % 2) as usize;
+        self.swap_rounds += 1;
+        let mut pair = first_pair;
+        while pair + 1 < self.replicas.len() {
+            let (low_part, high_part) = self.replicas.split_at_mut(pair + 1);
+            let low = &mut low_part[pair];
+            let high = &mut high_part[0];
+            let probability = swap_probability(low.updater.link_number_tuning,
+                                               high.updater.link_number_tuning,
+                                               low.lat.number_filled_links,
+                                               high.lat.number_filled_links);
+            self.swap_attempts[pair] += 1;
+            if self.rng.gen::<f64>() < probability {
+                mem::swap(&mut low.lat, &mut high.lat);
+                self.swap_accepted[pair] += 1;
+            }
+            pair += 2;
+        }
+    }
+
+    /// Write `swap_acceptance.csv`: for every pair of neighbouring weights the number of
+    /// proposed and accepted swaps and their ratio.
+    pub fn write_swap_acceptance(&self) {
+        let path = self.root.join("swap_acceptance.csv");
+        let display = path.display();
+
+        let mut file = match File::create(&path){
+            Err(err) => panic!("could not create {}: {}",
+                               display,
+                               err),
+            Ok(good_file) => good_file,
+        };
+
+        let mut out_string = String::new();
+        out_string.push_str("weight_low,weight_high,attempts,accepted,acceptance\n");
+        for pair in 0..self.swap_attempts.len() {
+            let attempts = self.swap_attempts[pair];
+            let accepted = self.swap_accepted[pair];
+            let acceptance = if attempts > 0 { accepted as f64 / attempts as f64 } else { 0.0 };
+            out_string.push_str(&format!("{},{},{},{},{}\n",
+                                         self.replicas[pair].config.weights,
+                                         self.replicas[pair + 1].config.weights,
+                                         attempts,
+                                         accepted,
+                                         acceptance));
+        }
+
+        match file.write_all(out_string.as_bytes()){
+            Err(err) => panic!("could not write {}: {}",
+                               display,
+                               err),
+            Ok(_) => println!("Wrote swap acceptance to {}", display),
+        }
+    }
+}